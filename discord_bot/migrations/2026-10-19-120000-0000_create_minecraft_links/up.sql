@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "minecraft_links"(
+	"id" UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+	"guild_id" VARCHAR NOT NULL,
+	"discord_user_id" VARCHAR NOT NULL,
+	"minecraft_uuid" UUID NOT NULL UNIQUE,
+	"minecraft_name" VARCHAR NOT NULL,
+	"status" VARCHAR NOT NULL,
+	"created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	"decided_by" VARCHAR,
+	UNIQUE ("guild_id", "discord_user_id")
+);
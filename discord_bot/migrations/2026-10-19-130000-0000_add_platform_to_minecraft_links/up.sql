@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE "minecraft_links" ADD COLUMN "platform" VARCHAR NOT NULL DEFAULT 'Java';
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE "chat_bridge_channels"(
+	"channel_id" VARCHAR NOT NULL PRIMARY KEY,
+	"enabled" BOOLEAN NOT NULL,
+	"updated_by" VARCHAR NOT NULL,
+	"updated_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "play_sessions"(
+	"id" UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+	"server" VARCHAR NOT NULL,
+	"player_id" VARCHAR NOT NULL,
+	"player_name" VARCHAR NOT NULL,
+	"platform" VARCHAR,
+	"joined_at" TIMESTAMPTZ NOT NULL,
+	"left_at" TIMESTAMPTZ
+);
+CREATE INDEX "play_sessions_open" ON "play_sessions" ("server", "player_id") WHERE "left_at" IS NULL;
+CREATE INDEX "play_sessions_left_at" ON "play_sessions" ("left_at");
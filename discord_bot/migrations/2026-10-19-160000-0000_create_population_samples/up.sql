@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE "population_samples"(
+	"id" BIGSERIAL NOT NULL PRIMARY KEY,
+	"server" VARCHAR NOT NULL,
+	"sampled_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	"online" BOOLEAN NOT NULL,
+	"players" INTEGER NOT NULL,
+	"max_players" INTEGER
+);
+CREATE INDEX "population_samples_sampled_at" ON "population_samples" ("sampled_at");
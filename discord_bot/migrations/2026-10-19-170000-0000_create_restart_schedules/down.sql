@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS "maintenance_windows";
+DROP TABLE IF EXISTS "restart_schedules";
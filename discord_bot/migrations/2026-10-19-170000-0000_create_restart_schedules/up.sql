@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE "restart_schedules"(
+	"id" UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+	"server" VARCHAR NOT NULL,
+	"cron" VARCHAR NOT NULL,
+	"created_by" VARCHAR NOT NULL,
+	"created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	"last_run_at" TIMESTAMPTZ
+);
+
+CREATE TABLE "maintenance_windows"(
+	"id" UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+	"server" VARCHAR NOT NULL,
+	"starts_at" TIMESTAMPTZ NOT NULL,
+	"ends_at" TIMESTAMPTZ NOT NULL,
+	"reason" VARCHAR NOT NULL,
+	"created_by" VARCHAR NOT NULL
+);
+CREATE INDEX "maintenance_windows_ends_at" ON "maintenance_windows" ("ends_at");
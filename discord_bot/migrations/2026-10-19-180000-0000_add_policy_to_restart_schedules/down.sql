@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "restart_schedules" DROP COLUMN "max_wait_minutes";
+ALTER TABLE "restart_schedules" DROP COLUMN "policy";
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE "restart_schedules" ADD COLUMN "policy" VARCHAR NOT NULL DEFAULT 'Force';
+ALTER TABLE "restart_schedules" ADD COLUMN "max_wait_minutes" INTEGER NOT NULL DEFAULT 30;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "reaction_messages" RENAME COLUMN "message_id" TO "role_id";
+ALTER TABLE "reaction_messages" ALTER COLUMN "id" DROP DEFAULT;
+ALTER TABLE "self_assignable_roles" ALTER COLUMN "id" DROP DEFAULT;
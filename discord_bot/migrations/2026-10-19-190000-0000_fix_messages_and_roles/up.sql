@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE "self_assignable_roles" ALTER COLUMN "id" SET DEFAULT gen_random_uuid();
+ALTER TABLE "reaction_messages" ALTER COLUMN "id" SET DEFAULT gen_random_uuid();
+ALTER TABLE "reaction_messages" RENAME COLUMN "role_id" TO "message_id";
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "play_sessions" DROP COLUMN "last_seen_at";
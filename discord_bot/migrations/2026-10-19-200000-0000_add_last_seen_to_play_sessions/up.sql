@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE "play_sessions" ADD COLUMN "last_seen_at" TIMESTAMPTZ NOT NULL DEFAULT now();
+UPDATE "play_sessions" SET "last_seen_at" = "left_at" WHERE "left_at" IS NOT NULL;
@@ -1,5 +1,9 @@
 use {
-    crate::{components::Navbar, views::{Home, Logs, TermsOfService, PrivacyPolicy}},
+    crate::{
+        components::{Navbar, initial_locale},
+        i18n::{FALLBACK, resolve},
+        views::{DataRequests, Home, Logs, Playtime, Population, PrivacyPolicy, TermsOfService},
+    },
     dioxus::prelude::*,
 };
 
@@ -27,10 +31,16 @@ pub enum Route {
     Home {},
     #[route("/logs")]
     Logs {},
+    #[route("/playtime")]
+    Playtime {},
+    #[route("/population")]
+    Population {},
     #[route("/terms-of-service")]
     TermsOfService {},
     #[route("/privacy-policy")]
     PrivacyPolicy {},
+    #[route("/data-requests")]
+    DataRequests {},
 }
 
 /// App is the main component of our app. Components are the building blocks of dioxus apps. Each component is a function
@@ -39,6 +49,19 @@ pub enum Route {
 /// Components should be annotated with `#[component]` to support props, better error messages, and autocomplete
 #[component]
 pub fn App() -> Element {
+    // Pages are rendered on the server in the visitor's language, which the client then keeps using
+    let negotiated = use_server_future(initial_locale)?;
+    use_context_provider(|| {
+        let locale = negotiated
+            .read()
+            .as_ref()
+            .and_then(|r| r.as_ref().ok().cloned());
+        Signal::new(
+            locale
+                .and_then(|locale| resolve([locale.as_str()]))
+                .unwrap_or(FALLBACK),
+        )
+    });
     // Build cool things ✌️
     // The `rsx!` macro lets us define HTML inside of rust. It expands to an Element with all of our HTML inside.
     rsx! {
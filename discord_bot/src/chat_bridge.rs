@@ -0,0 +1,469 @@
+//! Relays chat between Discord channels and game servers.
+//!
+//! Game chat is read from `game_manager` by the poller and posted to the bridged channel with all mentions
+//! disabled. Discord messages in a bridged channel are delivered in game with `tellraw` over RCON, after
+//! mentions and custom emoji are turned into plain text and the author's rate limit is checked. Terraria
+//! has no `tellraw`, so messages for it go through the `TShock` broadcast endpoint instead.
+//!
+//! Admins can switch bridging off for a channel with `/chat_bridge`, which is remembered in the database.
+
+use {
+    crate::{
+        error::AppError,
+        game_manager::{ChatMessage, GAME_MANAGER, GameServer},
+        models::NewChatBridgeChannel,
+        queries::{get_chat_bridge_channels, set_chat_bridge_channel},
+    },
+    poise::serenity_prelude as serenity,
+    std::{
+        collections::{HashMap, HashSet, VecDeque},
+        sync::{Mutex, OnceLock, PoisonError},
+        time::{Duration, Instant},
+    },
+};
+
+pub static CHAT_BRIDGE: OnceLock<ChatBridge> = OnceLock::new();
+
+pub fn set_chat_bridge(bridge: ChatBridge) {
+    let _ = CHAT_BRIDGE.set(bridge);
+}
+
+/// Longest message relayed into a game, longer ones are cut off
+const MAX_GAME_MESSAGE: usize = 256;
+
+/// Allows each user at most `limit` messages in any `window`
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    sent: HashMap<serenity::UserId, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Record a message from `user` at `now`, returning false if it is over the limit
+    pub fn check(&mut self, user: serenity::UserId, now: Instant) -> bool {
+        let window = self.window;
+        self.sent.retain(|_, times| {
+            times
+                .back()
+                .is_some_and(|t| now.duration_since(*t) < window)
+        });
+        let times = self.sent.entry(user).or_default();
+        while times
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= window)
+        {
+            times.pop_front();
+        }
+        if times.len() >= self.limit {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+pub struct ChatBridge {
+    channels: Mutex<HashMap<serenity::ChannelId, GameServer>>,
+    /// Bridged channels an admin has switched off
+    disabled: Mutex<HashSet<serenity::ChannelId>>,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl ChatBridge {
+    /// Bridge each server to the channel id configured for it. Servers with an empty or invalid channel
+    /// id are not bridged.
+    #[must_use]
+    pub fn new(channels: &[(GameServer, String)], limiter: RateLimiter) -> Self {
+        Self {
+            channels: Mutex::new(Self::parse_channels(channels)),
+            disabled: Mutex::new(HashSet::new()),
+            limiter: Mutex::new(limiter),
+        }
+    }
+
+    fn parse_channels(
+        channels: &[(GameServer, String)],
+    ) -> HashMap<serenity::ChannelId, GameServer> {
+        channels
+            .iter()
+            .filter_map(|(server, id)| {
+                let id = id.parse::<u64>().ok().filter(|id| *id != 0)?;
+                Some((serenity::ChannelId::new(id), *server))
+            })
+            .collect()
+    }
+
+    /// Bridge other channels or change the rate limit, keeping the channels switched off and the messages
+    /// already counted against the limit
+    pub fn reconfigure(&self, channels: &[(GameServer, String)], limit: usize, window: Duration) {
+        *self.channels.lock().unwrap_or_else(PoisonError::into_inner) =
+            Self::parse_channels(channels);
+        let mut limiter = self.limiter.lock().unwrap_or_else(PoisonError::into_inner);
+        limiter.limit = limit;
+        limiter.window = window;
+    }
+
+    /// Restore the channels switched off before the last restart
+    ///
+    /// # Errors
+    ///
+    /// Fails if the database fails.
+    pub async fn load_toggles(
+        &self,
+        pool: deadpool_diesel::postgres::Pool,
+    ) -> Result<(), AppError> {
+        let disabled = get_chat_bridge_channels(pool)
+            .await?
+            .into_iter()
+            .filter(|channel| !channel.enabled)
+            .filter_map(|channel| channel.channel_id.parse().ok())
+            .map(serenity::ChannelId::new)
+            .collect();
+        *self.disabled.lock().unwrap_or_else(PoisonError::into_inner) = disabled;
+        Ok(())
+    }
+
+    /// The server bridged to `channel`, whether or not bridging is switched on
+    #[must_use]
+    pub fn bridged_server(&self, channel: serenity::ChannelId) -> Option<GameServer> {
+        self.channels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&channel)
+            .copied()
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self, channel: serenity::ChannelId) -> bool {
+        !self
+            .disabled
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(&channel)
+    }
+
+    /// Switch bridging for `channel` on or off and remember the choice
+    ///
+    /// # Errors
+    ///
+    /// Fails if the database fails.
+    pub async fn set_enabled(
+        &self,
+        pool: deadpool_diesel::postgres::Pool,
+        channel: serenity::ChannelId,
+        enabled: bool,
+        updated_by: String,
+    ) -> Result<(), AppError> {
+        set_chat_bridge_channel(
+            pool,
+            NewChatBridgeChannel {
+                channel_id: u64::from(channel).to_string(),
+                enabled,
+                updated_by,
+            },
+        )
+        .await?;
+        let mut disabled = self.disabled.lock().unwrap_or_else(PoisonError::into_inner);
+        if enabled {
+            disabled.remove(&channel);
+        } else {
+            disabled.insert(channel);
+        }
+        drop(disabled);
+        Ok(())
+    }
+
+    /// The server chat in `channel` is relayed to, if it is bridged and switched on
+    #[must_use]
+    pub fn server(&self, channel: serenity::ChannelId) -> Option<GameServer> {
+        self.bridged_server(channel)
+            .filter(|_| self.is_enabled(channel))
+    }
+
+    /// The channel chat from `server` is relayed to, if it is bridged and switched on
+    #[must_use]
+    pub fn channel(&self, server: GameServer) -> Option<serenity::ChannelId> {
+        let channel = self
+            .channels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find_map(|(channel, s)| (*s == server).then_some(*channel));
+        channel.filter(|channel| self.is_enabled(*channel))
+    }
+
+    fn allow(&self, user: serenity::UserId) -> bool {
+        self.limiter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .check(user, Instant::now())
+    }
+}
+
+/// Escape Discord markdown so game chat is shown exactly as typed
+#[must_use]
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '-' | '[' | ']'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Replace custom emoji such as `<:pog:123>` with `:pog:`, since the game cannot show them
+#[must_use]
+pub fn replace_custom_emoji(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let emoji = tail.find('>').and_then(|end| {
+            let inner = &tail[1..end];
+            let inner = inner.strip_prefix('a').unwrap_or(inner);
+            let (name, id) = inner.strip_prefix(':')?.split_once(':')?;
+            (!name.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+                .then(|| (format!(":{name}:"), end))
+        });
+        if let Some((name, end)) = emoji {
+            out.push_str(&name);
+            rest = &tail[end + 1..];
+        } else {
+            out.push('<');
+            rest = &tail[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Make text from Discord safe to show in game: no formatting codes, no line breaks and a bounded length
+#[must_use]
+pub fn sanitize_for_game(text: &str) -> String {
+    let text: String = replace_custom_emoji(text)
+        .chars()
+        .filter(|c| *c != '§')
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > MAX_GAME_MESSAGE {
+        let mut text: String = text.chars().take(MAX_GAME_MESSAGE - 1).collect();
+        text.push('…');
+        text
+    } else {
+        text
+    }
+}
+
+/// The `tellraw` command showing a Discord message to every player
+#[must_use]
+pub fn tellraw(author: &str, message: &str) -> String {
+    let components = serde_json::json!([
+        "",
+        { "text": "[Discord] ", "color": "blue" },
+        { "text": format!("<{author}> ") },
+        { "text": message },
+    ]);
+    format!("tellraw @a {components}")
+}
+
+/// The name a member is shown with in the guild: their nickname, else their display name
+#[must_use]
+pub fn display_name<'a>(nick: Option<&'a str>, user: &'a serenity::User) -> &'a str {
+    nick.unwrap_or_else(|| user.display_name())
+}
+
+/// Relay a Discord message into the game bridged to its channel, if any
+///
+/// # Errors
+///
+/// Fails if the message could not be delivered to `game_manager`.
+pub async fn relay_to_game(
+    ctx: &serenity::Context,
+    message: &serenity::Message,
+) -> Result<(), AppError> {
+    let Some(bridge) = CHAT_BRIDGE.get() else {
+        return Ok(());
+    };
+    let Some(server) = bridge.server(message.channel_id) else {
+        return Ok(());
+    };
+    if message.author.bot || message.webhook_id.is_some() {
+        return Ok(());
+    }
+    if !bridge.allow(message.author.id) {
+        message
+            .react(&ctx.http, serenity::ReactionType::Unicode("⏳".into()))
+            .await?;
+        return Ok(());
+    }
+    let mut content = sanitize_for_game(&message.content_safe(&ctx.cache));
+    if !message.attachments.is_empty() {
+        if !content.is_empty() {
+            content.push(' ');
+        }
+        content.push_str("[attachment]");
+    }
+    if content.is_empty() {
+        return Ok(());
+    }
+    let nick = message.member.as_ref().and_then(|m| m.nick.as_deref());
+    let author = sanitize_for_game(display_name(nick, &message.author));
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    match server {
+        GameServer::MinecraftGeyser | GameServer::MinecraftModded => {
+            client.rcon(server, &tellraw(&author, &content)).await?;
+        }
+        GameServer::Terraria => {
+            client
+                .broadcast(server, &format!("[Discord] <{author}> {content}"))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Post chat read from a game into its bridged channel
+///
+/// # Errors
+///
+/// Fails if Discord rejects the message.
+pub async fn relay_to_discord(
+    http: &serenity::Http,
+    channel: serenity::ChannelId,
+    messages: &[ChatMessage],
+) -> Result<(), AppError> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let content = messages
+        .iter()
+        .map(|m| {
+            format!(
+                "**{}**: {}",
+                escape_markdown(&m.author),
+                escape_markdown(&m.content)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    // Discord rejects messages over 2000 characters, split on line boundaries
+    let mut chunk = String::new();
+    for line in content.lines() {
+        if !chunk.is_empty() && chunk.len() + line.len() + 1 > 2000 {
+            send_unmentionable(http, channel, &chunk).await?;
+            chunk.clear();
+        }
+        if !chunk.is_empty() {
+            chunk.push('\n');
+        }
+        chunk.push_str(&line.chars().take(2000).collect::<String>());
+    }
+    send_unmentionable(http, channel, &chunk).await
+}
+
+async fn send_unmentionable(
+    http: &serenity::Http,
+    channel: serenity::ChannelId,
+    content: &str,
+) -> Result<(), AppError> {
+    channel
+        .send_message(
+            http,
+            serenity::CreateMessage::new()
+                .content(content)
+                .allowed_mentions(serenity::CreateAllowedMentions::new()),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_text_for_the_game() {
+        for (text, expected) in [
+            ("hello", "hello"),
+            ("§cred §lbold§", "cred lbold"),
+            ("two\nlines\r\nand\ta tab", "two lines and a tab"),
+            ("  lots   of\u{0}space  ", "lots of space"),
+            ("gg <:pog:123456> <a:dance:42>", "gg :pog: :dance:"),
+            ("<not an emoji> <:x:abc>", "<not an emoji> <:x:abc>"),
+            (
+                r#"{"text":"fake","clickEvent":{"action":"run_command"}}"#,
+                r#"{"text":"fake","clickEvent":{"action":"run_command"}}"#,
+            ),
+            ("", ""),
+        ] {
+            assert_eq!(sanitize_for_game(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn cuts_long_messages_off_by_character() {
+        let text = "é".repeat(MAX_GAME_MESSAGE);
+        assert_eq!(sanitize_for_game(&text), text);
+        let sanitized = sanitize_for_game(&format!("{text}é"));
+        assert_eq!(sanitized.chars().count(), MAX_GAME_MESSAGE);
+        assert!(sanitized.ends_with("é…"));
+    }
+
+    #[test]
+    fn tellraw_keeps_json_in_messages_as_text() {
+        let message = r#"", {"text":"injected"}, ""#;
+        let command = tellraw("Steve\"", message);
+        let components: serde_json::Value = serde_json::from_str(
+            command
+                .strip_prefix("tellraw @a ")
+                .expect("not a tellraw command"),
+        )
+        .expect("tellraw components are not JSON");
+        assert_eq!(components[2]["text"], "<Steve\"> ");
+        assert_eq!(components[3]["text"], message);
+        assert_eq!(components.as_array().map(Vec::len), Some(4));
+    }
+
+    #[test]
+    fn rate_limits_each_user_within_the_window() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(30));
+        let (alice, bob) = (serenity::UserId::new(1), serenity::UserId::new(2));
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        assert!(limiter.check(alice, at(0)));
+        assert!(limiter.check(alice, at(10)));
+        assert!(!limiter.check(alice, at(20)));
+        // a rejected message doesn't count against the user
+        assert!(limiter.check(bob, at(20)));
+        assert!(limiter.check(alice, at(30)));
+        assert!(!limiter.check(alice, at(39)));
+        assert!(limiter.check(alice, at(40)));
+        // users who went quiet are forgotten
+        assert!(limiter.check(alice, at(100)));
+        assert_eq!(limiter.sent.len(), 1);
+    }
+
+    #[test]
+    fn a_limit_of_zero_rejects_everything() {
+        let mut limiter = RateLimiter::new(0, Duration::from_secs(30));
+        assert!(!limiter.check(serenity::UserId::new(1), Instant::now()));
+    }
+}
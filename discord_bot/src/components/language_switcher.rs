@@ -0,0 +1,67 @@
+use {crate::i18n::resolve, dioxus::prelude::*, rust_i18n::t};
+
+/// Cookie remembering the language picked with [`LanguageSwitcher`], read while rendering on the server
+pub const LOCALE_COOKIE: &str = "locale";
+
+/// The locale the frontend is shown in, re-rendering the calling component when it changes. Provided by
+/// [`crate::app::App`].
+#[must_use]
+pub fn use_locale() -> &'static str {
+    use_context::<Signal<&'static str>>()()
+}
+
+/// The locale to render a page in: the one picked with [`LanguageSwitcher`] if any, else the best match
+/// for the browser's `Accept-Language`
+#[server]
+pub async fn initial_locale() -> Result<String, ServerFnError> {
+    use {
+        crate::i18n::{FALLBACK, negotiate},
+        axum::http::{HeaderMap, header},
+        axum_extra::extract::CookieJar,
+    };
+
+    let headers: HeaderMap = dioxus::fullstack::FullstackContext::extract().await?;
+    let picked = CookieJar::from_headers(&headers)
+        .get(LOCALE_COOKIE)
+        .and_then(|cookie| resolve([cookie.value()]));
+    let accepted = || {
+        headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(negotiate)
+    };
+    Ok(picked.or_else(accepted).unwrap_or(FALLBACK).to_owned())
+}
+
+#[component]
+pub fn LanguageSwitcher() -> Element {
+    let mut locale = use_context::<Signal<&'static str>>();
+    rsx! {
+        label {
+            r#for: "language-select",
+            {t!("web.language", locale = locale()).into_owned()}
+        }
+        select {
+            id: "language-select",
+            onchange: move |event| {
+                let Some(picked) = resolve([event.value().as_str()]) else {
+                    return;
+                };
+                locale.set(picked);
+                // kept for a year and sent with every request, so pages are rendered in it from the start
+                let _ = document::eval(&format!(
+                    "document.cookie = '{LOCALE_COOKIE}={picked}; path=/; max-age=31536000; samesite=lax'; \
+                     document.documentElement.lang = '{picked}';"
+                ));
+            },
+            for option in rust_i18n::available_locales!() {
+                option {
+                    key: "{option}",
+                    value: option,
+                    selected: option == locale(),
+                    {t!("web.language_name", locale = option).into_owned()}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use {
+    crate::{
+        components::use_locale,
+        i18n::FALLBACK,
+        legal::{LegalDocument, RenderedDocument},
+    },
+    dioxus::prelude::*,
+    rust_i18n::t,
+};
+
+/// A legal document with its version and the date it took effect
+#[component]
+pub fn LegalPage(document: LegalDocument) -> Element {
+    let locale = use_locale();
+    let rendered = use_server_future(move || fetch_legal_document(document))?;
+    let rendered = match &*rendered.read() {
+        Some(Ok(rendered)) => rendered.clone(),
+        Some(Err(e)) => {
+            return rsx! { p { {t!("web.legal.failed", locale = locale, error = e).into_owned()} } };
+        }
+        None => return rsx! { p { {t!("web.loading", locale = locale).into_owned()} } },
+    };
+    rsx! {
+        article {
+            class: "legal",
+            h1 { {document.title(locale)} }
+            p {
+                class: "legal-version",
+                {
+                    t!(
+                        "web.legal.effective",
+                        locale = locale,
+                        version = rendered.version,
+                        date = rendered.effective.format("%Y-%m-%d")
+                    )
+                    .into_owned()
+                }
+            }
+            // the documents are only written in English
+            if locale != FALLBACK {
+                p { class: "legal-notice", {t!("web.legal.english_only", locale = locale).into_owned()} }
+            }
+            div { dangerous_inner_html: rendered.html }
+        }
+    }
+}
+
+/// The document with the bot's name and contact filled in from the configuration
+#[server]
+async fn fetch_legal_document(document: LegalDocument) -> Result<RenderedDocument, ServerFnError> {
+    use crate::state::GLOBAL_STATE;
+
+    let state = GLOBAL_STATE
+        .get()
+        .ok_or_else(|| ServerFnError::new("state not initialised"))?
+        .lock()
+        .await
+        .clone();
+    document
+        .render(&state)
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
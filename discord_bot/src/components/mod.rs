@@ -1,2 +1,6 @@
+mod language_switcher;
+pub use language_switcher::*;
+mod legal_page;
+pub use legal_page::*;
 mod navbar;
 pub use navbar::*;
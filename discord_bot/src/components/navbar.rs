@@ -1,4 +1,11 @@
-use {crate::app::Route, dioxus::prelude::*};
+use {
+    crate::{
+        app::Route,
+        components::{LanguageSwitcher, use_locale},
+    },
+    dioxus::prelude::*,
+    rust_i18n::t,
+};
 
 const NAVBAR_CSS: Asset = asset!("/assets/styling/navbar.css");
 
@@ -9,6 +16,7 @@ const NAVBAR_CSS: Asset = asset!("/assets/styling/navbar.css");
 /// routes will be rendered under the outlet inside this component
 #[component]
 pub fn Navbar() -> Element {
+    let locale = use_locale();
     rsx! {
         document::Link { rel: "stylesheet", href: NAVBAR_CSS }
 
@@ -16,20 +24,29 @@ pub fn Navbar() -> Element {
             id: "navbar",
             Link {
                 to: Route::Home {},
-                "Home"
+                {t!("web.nav.home", locale = locale).into_owned()}
             }
             Link {
                 to: Route::Logs {},
-                "Logs"
+                {t!("web.nav.logs", locale = locale).into_owned()}
+            }
+            Link {
+                to: Route::Playtime {},
+                {t!("web.nav.playtime", locale = locale).into_owned()}
+            }
+            Link {
+                to: Route::Population {},
+                {t!("web.nav.population", locale = locale).into_owned()}
             }
             Link {
                 to: Route::TermsOfService {},
-                "Terms of Service"
+                {t!("web.nav.terms_of_service", locale = locale).into_owned()}
             }
             Link {
                 to: Route::PrivacyPolicy {},
-                "Privacy Policy"
+                {t!("web.nav.privacy_policy", locale = locale).into_owned()}
             }
+            LanguageSwitcher {}
         }
 
         // The `Outlet` component is used to render the next component inside the layout. In this case, it will render either
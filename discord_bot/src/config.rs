@@ -0,0 +1,514 @@
+//! The bot's configuration, layered from a file, the environment and command line flags.
+//!
+//! Every setting has a key such as `DISCORD_TOKEN`, looked up in this order with the first match winning:
+//!
+//! 1. a `--discord-token <value>` or `--discord-token=<value>` flag
+//! 2. the `DISCORD_TOKEN` environment variable, or the file named by `DISCORD_TOKEN_FILE`
+//! 3. a systemd credential named `discord_token`, passed with `LoadCredential=`
+//! 4. `discord_token = "..."` in the TOML file given by `--config` or `DISCORD_BOT_CONFIG`
+//! 5. the default
+//!
+//! Empty values count as unset, so the blank entries in `sample.env` don't hide the file. Values read
+//! from files have surrounding whitespace trimmed, which lets secrets stay out of the environment. All
+//! problems found while loading are collected and reported together, rather than one per restart.
+
+use {
+    crate::{game_manager::GameServer, state::AppState},
+    std::{
+        collections::{HashMap, HashSet},
+        fmt::Display,
+        net::SocketAddr,
+        path::PathBuf,
+        str::FromStr,
+        time::Duration,
+    },
+    toml::Table,
+};
+
+/// Environment variable naming the config file, for when `--config` isn't given
+const CONFIG_ENV: &str = "DISCORD_BOT_CONFIG";
+
+/// Every problem found in the configuration
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The configuration sources, collecting problems as settings are read
+struct Layers {
+    path: Option<PathBuf>,
+    flags: HashMap<String, String>,
+    file: Table,
+    /// The environment variables that are valid UTF-8
+    env: HashMap<String, String>,
+    credentials: Option<PathBuf>,
+    /// Keys that were read, anything else in the flags or file is a typo
+    used: HashSet<String>,
+    problems: Vec<String>,
+}
+
+impl Layers {
+    /// Read the process's arguments and environment
+    fn load() -> Self {
+        let env = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Self::new(std::env::args().skip(1), env)
+    }
+
+    /// Parse `args`, the arguments after the program name, and read the config file they or `env` name
+    fn new(args: impl IntoIterator<Item = String>, env: HashMap<String, String>) -> Self {
+        let mut problems = Vec::new();
+        let mut flags = HashMap::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                problems.push(format!("unexpected argument `{arg}`"));
+                continue;
+            };
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (flag.to_owned(), args.next()),
+            };
+            match value {
+                Some(value) => {
+                    flags.insert(name, value);
+                }
+                None => problems.push(format!("`--{name}` needs a value")),
+            }
+        }
+        let path = flags
+            .remove("config")
+            .or_else(|| env.get(CONFIG_ENV).cloned())
+            .filter(|path| !path.is_empty());
+        let file =
+            path.as_ref()
+                .map_or_else(Table::new, |path| match std::fs::read_to_string(path) {
+                    Ok(contents) => contents.parse().unwrap_or_else(|e| {
+                        problems.push(format!("{path} is not valid TOML: {e}"));
+                        Table::new()
+                    }),
+                    Err(e) => {
+                        problems.push(format!("cannot read {path}: {e}"));
+                        Table::new()
+                    }
+                });
+        let credentials = env.get("CREDENTIALS_DIRECTORY").map(PathBuf::from);
+        Self {
+            path: path.map(PathBuf::from),
+            flags,
+            file,
+            env,
+            credentials,
+            used: HashSet::new(),
+            problems,
+        }
+    }
+
+    /// An environment variable, `None` if it is empty
+    fn var(&self, key: &str) -> Option<String> {
+        self.env.get(key).filter(|value| !value.is_empty()).cloned()
+    }
+
+    fn read_file(&mut self, what: &str, path: &std::path::Path) -> Option<String> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Some(contents.trim().to_owned()).filter(|value| !value.is_empty()),
+            Err(e) => {
+                self.problems
+                    .push(format!("cannot read {what} from {}: {e}", path.display()));
+                None
+            }
+        }
+    }
+
+    /// The value of `key` from the first layer that sets it
+    fn raw(&mut self, key: &str) -> Option<String> {
+        let name = key.to_lowercase();
+        self.used.insert(name.clone());
+        if let Some(value) = self.flags.get(&name.replace('_', "-")) {
+            return Some(value.clone()).filter(|value| !value.is_empty());
+        }
+        let file_key = format!("{key}_FILE");
+        let value = self.var(key);
+        let file = self.var(&file_key);
+        match (value, file) {
+            (Some(_), Some(_)) => {
+                self.problems
+                    .push(format!("only one of {key} and {file_key} may be set"));
+                return None;
+            }
+            (Some(value), None) => return Some(value),
+            (None, Some(path)) => return self.read_file(key, path.as_ref()),
+            (None, None) => {}
+        }
+        if let Some(credential) = self.credentials.as_ref().map(|dir| dir.join(&name))
+            && credential.is_file()
+        {
+            return self.read_file(key, &credential);
+        }
+        match self.file.get(&name)? {
+            toml::Value::String(value) => Some(value.clone()).filter(|value| !value.is_empty()),
+            value => Some(value.to_string()),
+        }
+    }
+
+    fn string(&mut self, key: &str, default: &str) -> String {
+        self.raw(key).unwrap_or_else(|| default.to_owned())
+    }
+
+    fn required(&mut self, key: &str) -> String {
+        self.raw(key).unwrap_or_else(|| {
+            self.problems.push(format!("{key} must be set"));
+            String::new()
+        })
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, default: T) -> T
+    where
+        T::Err: Display,
+    {
+        match self.raw(key) {
+            None => default,
+            Some(value) => value.parse().unwrap_or_else(|e| {
+                self.problems
+                    .push(format!("{key} `{value}` is invalid: {e}"));
+                default
+            }),
+        }
+    }
+
+    fn seconds(&mut self, key: &str, default: u64) -> Duration {
+        Duration::from_secs(self.parse(key, default))
+    }
+
+    /// A URL, kept as the string it was given as
+    fn url(&mut self, key: &str, default: &str) -> String {
+        let value = self.string(key, default);
+        if !value.is_empty()
+            && let Err(e) = url::Url::parse(&value)
+        {
+            self.problems
+                .push(format!("{key} `{value}` is not a URL: {e}"));
+        }
+        value
+    }
+
+    /// A Discord channel id, empty if unset
+    fn channel(&mut self, key: &str) -> String {
+        let value = self.string(key, "");
+        if !value.is_empty() && value.parse::<u64>().is_err() {
+            self.problems
+                .push(format!("{key} `{value}` is not a Discord channel id"));
+        }
+        value
+    }
+
+    fn finish(mut self) -> Result<(), ConfigError> {
+        for name in self.flags.keys() {
+            if !self.used.contains(&name.replace('-', "_")) {
+                self.problems.push(format!("unknown flag `--{name}`"));
+            }
+        }
+        for name in self.file.keys() {
+            if !self.used.contains(name) {
+                self.problems
+                    .push(format!("unknown setting `{name}` in config file"));
+            }
+        }
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError(self.problems))
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Config {
+    /// The config file, if one was given
+    pub file: Option<PathBuf>,
+    /// Settings shared with the web server and Discord handlers
+    pub state: AppState,
+    pub address: SocketAddr,
+    pub database_url: String,
+    pub game_manager_url: String,
+    pub game_manager_client_id: String,
+    pub game_manager_client_secret: String,
+    /// Channels chat is relayed between Discord and each game, empty to not relay
+    pub chat_channels: Vec<(GameServer, String)>,
+    /// How many messages a user may relay within the window
+    pub chat_bridge_rate_limit: usize,
+    pub chat_bridge_rate_window: Duration,
+    /// How many task failures within the window are tolerated before the bot exits
+    pub supervisor_crash_budget: usize,
+    pub supervisor_crash_window: Duration,
+    /// Language of announcements, and of replies to users whose language the bot doesn't speak
+    pub locale: String,
+}
+
+impl Config {
+    /// Read and validate the configuration
+    ///
+    /// # Errors
+    ///
+    /// Fails with every problem found, such as a missing token, an unparsable number or an unknown flag.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::from_layers(Layers::load())
+    }
+
+    fn from_layers(mut layers: Layers) -> Result<Self, ConfigError> {
+        let config = Self {
+            file: layers.path.clone(),
+            state: AppState {
+                announcement_channel_id: layers.channel("DISCORD_ANNOUNCEMENT_CHANNEL_ID"),
+                base_url: layers.url("BASE_URL", ""),
+                bot_name: layers.string("BOT_NAME", "Discord Bot"),
+                discord_client_id: layers.string("DISCORD_CLIENT_ID", ""),
+                discord_client_secret: layers.string("DISCORD_CLIENT_SECRET", ""),
+                discord_public_key: layers.string("DISCORD_PUBLIC_KEY", ""),
+                discord_token: layers.required("DISCORD_TOKEN"),
+                legal_contact: layers.string("LEGAL_CONTACT", ""),
+                user_agent: format!(
+                    "DiscordBot ({}, {})",
+                    env!("CARGO_PKG_REPOSITORY"),
+                    env!("CARGO_PKG_VERSION")
+                ),
+            },
+            address: layers.parse(
+                "ADDRESS",
+                dioxus_cli_config::fullstack_address_or_localhost(),
+            ),
+            database_url: layers.required("DATABASE_URL"),
+            game_manager_url: layers.url("GAME_MANAGER_URL", "http://localhost:8081"),
+            game_manager_client_id: layers.string("GAME_MANAGER_CLIENT_ID", ""),
+            game_manager_client_secret: layers.string("GAME_MANAGER_CLIENT_SECRET", ""),
+            chat_channels: vec![
+                (
+                    GameServer::MinecraftGeyser,
+                    layers.channel("MINECRAFT_GEYSER_CHAT_CHANNEL_ID"),
+                ),
+                (
+                    GameServer::MinecraftModded,
+                    layers.channel("MINECRAFT_MODDED_CHAT_CHANNEL_ID"),
+                ),
+                (
+                    GameServer::Terraria,
+                    layers.channel("TERRARIA_CHAT_CHANNEL_ID"),
+                ),
+            ],
+            chat_bridge_rate_limit: layers.parse("CHAT_BRIDGE_RATE_LIMIT", 5),
+            chat_bridge_rate_window: layers.seconds("CHAT_BRIDGE_RATE_WINDOW_SECONDS", 30),
+            supervisor_crash_budget: layers.parse("SUPERVISOR_CRASH_BUDGET", 5),
+            supervisor_crash_window: layers.seconds("SUPERVISOR_CRASH_WINDOW_SECONDS", 600),
+            locale: layers.string("LOCALE", crate::i18n::FALLBACK),
+        };
+        if config.chat_bridge_rate_window.is_zero() {
+            layers.problems.push(String::from(
+                "CHAT_BRIDGE_RATE_WINDOW_SECONDS must be above 0",
+            ));
+        }
+        let locales = rust_i18n::available_locales!();
+        if !locales.contains(&config.locale.as_str()) {
+            layers.problems.push(format!(
+                "LOCALE `{}` is not one of {}",
+                config.locale,
+                locales.join(", ")
+            ));
+        }
+        if !config.game_manager_client_id.is_empty() && config.game_manager_client_secret.is_empty()
+        {
+            layers.problems.push(String::from(
+                "GAME_MANAGER_CLIENT_SECRET must be set when GAME_MANAGER_CLIENT_ID is",
+            ));
+        }
+        layers.finish()?;
+        Ok(config)
+    }
+
+    /// Keep the settings that are only read at startup from the `running` configuration, returning the
+    /// keys of those this one would change
+    pub fn keep_startup_settings(&mut self, running: &Self) -> Vec<&'static str> {
+        fn keep<T: PartialEq + Clone>(
+            changed: &mut Vec<&'static str>,
+            key: &'static str,
+            new: &mut T,
+            old: &T,
+        ) {
+            if new != old {
+                changed.push(key);
+                new.clone_from(old);
+            }
+        }
+        let mut changed = Vec::new();
+        keep(&mut changed, CONFIG_ENV, &mut self.file, &running.file);
+        keep(&mut changed, "ADDRESS", &mut self.address, &running.address);
+        keep(
+            &mut changed,
+            "DATABASE_URL",
+            &mut self.database_url,
+            &running.database_url,
+        );
+        keep(
+            &mut changed,
+            "DISCORD_TOKEN",
+            &mut self.state.discord_token,
+            &running.state.discord_token,
+        );
+        keep(
+            &mut changed,
+            "SUPERVISOR_CRASH_BUDGET",
+            &mut self.supervisor_crash_budget,
+            &running.supervisor_crash_budget,
+        );
+        keep(
+            &mut changed,
+            "SUPERVISOR_CRASH_WINDOW_SECONDS",
+            &mut self.supervisor_crash_window,
+            &running.supervisor_crash_window,
+        );
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tempfile::TempDir};
+
+    fn layers(args: &[&str], env: &[(&str, &str)]) -> Layers {
+        Layers::new(
+            args.iter().map(ToString::to_string),
+            env.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    fn write(dir: &TempDir, name: &str, contents: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).expect("writing a file failed");
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn layers_take_precedence_in_order() {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let config = write(&dir, "config.toml", "bot_name = \"toml\"\n");
+        let secret = write(&dir, "secret", "  from file\n");
+        let credentials = dir.path().join("credentials");
+        std::fs::create_dir(&credentials).expect("creating the credentials directory failed");
+        std::fs::write(credentials.join("bot_name"), "credential\n")
+            .expect("writing the credential failed");
+        let credentials = credentials.to_string_lossy().into_owned();
+
+        let flag = ["--bot-name=flag"];
+        let env = [
+            ("DISCORD_BOT_CONFIG", config.as_str()),
+            ("CREDENTIALS_DIRECTORY", credentials.as_str()),
+            ("BOT_NAME", "env"),
+        ];
+        let env_file = [
+            ("DISCORD_BOT_CONFIG", config.as_str()),
+            ("CREDENTIALS_DIRECTORY", credentials.as_str()),
+            ("BOT_NAME_FILE", secret.as_str()),
+        ];
+        let blank_env = [
+            ("DISCORD_BOT_CONFIG", config.as_str()),
+            ("CREDENTIALS_DIRECTORY", credentials.as_str()),
+            ("BOT_NAME", ""),
+        ];
+        for (args, env, expected) in [
+            (&flag[..], &env[..], "flag"),
+            (&[][..], &env[..], "env"),
+            (&[][..], &env_file[..], "from file"),
+            (&[][..], &blank_env[..], "credential"),
+            (&[][..], &env[..1], "toml"),
+            (&[][..], &[][..], "default"),
+        ] {
+            let mut layers = layers(args, env);
+            assert_eq!(layers.string("BOT_NAME", "default"), expected);
+            assert!(layers.finish().is_ok(), "{expected}");
+        }
+    }
+
+    #[test]
+    fn a_value_and_a_file_conflict() {
+        let mut layers = layers(
+            &[],
+            &[
+                ("DISCORD_TOKEN", "token"),
+                ("DISCORD_TOKEN_FILE", "/run/secret"),
+            ],
+        );
+        assert_eq!(layers.raw("DISCORD_TOKEN"), None);
+        let Err(ConfigError(problems)) = layers.finish() else {
+            panic!("conflicting settings were accepted");
+        };
+        assert_eq!(
+            problems,
+            ["only one of DISCORD_TOKEN and DISCORD_TOKEN_FILE may be set"]
+        );
+    }
+
+    #[test]
+    fn unknown_flags_and_settings_are_problems() {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let config = write(&dir, "config.toml", "bot_name = \"a\"\nbot_nmae = \"b\"\n");
+        let mut layers = layers(
+            &["--config", &config, "--bot-nmae=c", "stray", "--locale"],
+            &[],
+        );
+        assert_eq!(layers.string("BOT_NAME", ""), "a");
+        layers.string("LOCALE", "");
+        let Err(ConfigError(problems)) = layers.finish() else {
+            panic!("unknown settings were accepted");
+        };
+        assert_eq!(
+            problems,
+            [
+                "unexpected argument `stray`",
+                "`--locale` needs a value",
+                "unknown flag `--bot-nmae`",
+                "unknown setting `bot_nmae` in config file",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_problem_is_reported_together() {
+        let Err(ConfigError(problems)) = Config::from_layers(layers(
+            &[
+                "--discord-announcement-channel-id=general",
+                "--chat-bridge-rate-window-seconds=0",
+                "--locale=xx",
+            ],
+            &[
+                ("GAME_MANAGER_CLIENT_ID", "discord_bot"),
+                ("SUPERVISOR_CRASH_BUDGET", "-1"),
+            ],
+        )) else {
+            panic!("an invalid configuration was accepted");
+        };
+        assert_eq!(
+            problems,
+            [
+                "DISCORD_ANNOUNCEMENT_CHANNEL_ID `general` is not a Discord channel id",
+                "DISCORD_TOKEN must be set",
+                "DATABASE_URL must be set",
+                "SUPERVISOR_CRASH_BUDGET `-1` is invalid: invalid digit found in string",
+                "CHAT_BRIDGE_RATE_WINDOW_SECONDS must be above 0",
+                "LOCALE `xx` is not one of de, en",
+                "GAME_MANAGER_CLIENT_SECRET must be set when GAME_MANAGER_CLIENT_ID is",
+            ]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Exporting and deleting what the bot stores about someone, as the Privacy Policy promises.
+//!
+//! People are identified by their Discord user id. Play sessions are keyed by in-game UUID, so they are
+//! found through the accounts the person has linked. What someone did as an administrator is exported
+//! but not deleted, since it belongs to the schedule, window or setting rather than to them, and entries
+//! that would reveal someone else, such as whose link they approved, are left out of exports.
+
+use {
+    crate::{
+        error::AppError,
+        game_manager::GameServer,
+        minecraft::unlink,
+        models::MinecraftLink,
+        queries::{
+            delete_play_sessions_for_player_ids, get_chat_bridge_channels,
+            get_maintenance_windows_created_by, get_minecraft_links_decided_by,
+            get_minecraft_links_for_user, get_play_sessions_for_player_ids, get_restart_schedules,
+        },
+    },
+    chrono::Utc,
+    serde_json::{Value, json},
+};
+
+/// What was deleted for someone
+pub struct Deletion {
+    pub links: usize,
+    pub sessions: usize,
+    /// Servers the unlinked accounts could not be removed from the whitelist of
+    pub whitelist_failures: Vec<GameServer>,
+}
+
+/// The ids play sessions of linked accounts may be recorded under, as `game_manager` may report UUIDs
+/// with or without hyphens
+fn player_ids(links: &[MinecraftLink]) -> Vec<String> {
+    links
+        .iter()
+        .flat_map(|link| {
+            [
+                link.minecraft_uuid.hyphenated().to_string(),
+                link.minecraft_uuid.simple().to_string(),
+            ]
+        })
+        .collect()
+}
+
+/// Everything stored about a Discord user, ready to be sent to them as JSON
+///
+/// # Errors
+///
+/// Fails if the database can't be queried.
+pub async fn export(
+    pool: deadpool_diesel::postgres::Pool,
+    discord_user_id: &str,
+) -> Result<Value, AppError> {
+    let links = get_minecraft_links_for_user(pool.clone(), discord_user_id.to_owned()).await?;
+    let sessions = get_play_sessions_for_player_ids(pool.clone(), player_ids(&links)).await?;
+    let decisions =
+        get_minecraft_links_decided_by(pool.clone(), discord_user_id.to_owned()).await?;
+    let windows =
+        get_maintenance_windows_created_by(pool.clone(), discord_user_id.to_owned()).await?;
+    let mut schedules = get_restart_schedules(pool.clone()).await?;
+    schedules.retain(|schedule| schedule.created_by == discord_user_id);
+    let mut channels = get_chat_bridge_channels(pool).await?;
+    channels.retain(|channel| channel.updated_by == discord_user_id);
+    Ok(json!({
+        "discord_user_id": discord_user_id,
+        "exported_at": Utc::now().to_rfc3339(),
+        "minecraft_links": links.iter().map(|link| json!({
+            "guild_id": link.guild_id,
+            "minecraft_uuid": link.minecraft_uuid.to_string(),
+            "minecraft_name": link.minecraft_name,
+            "platform": link.platform,
+            "status": link.status,
+            "requested_at": link.created_at.to_rfc3339(),
+        })).collect::<Vec<_>>(),
+        "play_sessions": sessions.iter().map(|session| json!({
+            "server": session.server,
+            "player_id": session.player_id,
+            "player_name": session.player_name,
+            "platform": session.platform,
+            "joined_at": session.joined_at.to_rfc3339(),
+            "left_at": session.left_at.map(|left_at| left_at.to_rfc3339()),
+        })).collect::<Vec<_>>(),
+        "administrator_actions": {
+            "link_decisions": decisions.iter().map(|link| json!({
+                "guild_id": link.guild_id,
+                "status": link.status,
+            })).collect::<Vec<_>>(),
+            "restart_schedules": schedules.iter().map(|schedule| json!({
+                "server": schedule.server,
+                "cron": schedule.cron,
+                "policy": schedule.policy,
+                "created_at": schedule.created_at.to_rfc3339(),
+            })).collect::<Vec<_>>(),
+            "maintenance_windows": windows.iter().map(|window| json!({
+                "server": window.server,
+                "starts_at": window.starts_at.to_rfc3339(),
+                "ends_at": window.ends_at.to_rfc3339(),
+                "reason": window.reason,
+            })).collect::<Vec<_>>(),
+            "chat_bridge_channels": channels.iter().map(|channel| json!({
+                "channel_id": channel.channel_id,
+                "enabled": channel.enabled,
+                "updated_at": channel.updated_at.to_rfc3339(),
+            })).collect::<Vec<_>>(),
+        },
+    }))
+}
+
+/// Delete a Discord user's Minecraft links and the play sessions of the linked accounts, taking the
+/// accounts off the whitelists
+///
+/// # Errors
+///
+/// Fails if the database can't be queried or `game_manager` isn't configured.
+pub async fn delete(
+    pool: deadpool_diesel::postgres::Pool,
+    discord_user_id: &str,
+) -> Result<Deletion, AppError> {
+    let links = get_minecraft_links_for_user(pool.clone(), discord_user_id.to_owned()).await?;
+    let sessions = delete_play_sessions_for_player_ids(pool.clone(), player_ids(&links)).await?;
+    let mut whitelist_failures = vec![];
+    for link in &links {
+        for server in unlink(pool.clone(), link).await? {
+            if !whitelist_failures.contains(&server) {
+                whitelist_failures.push(server);
+            }
+        }
+    }
+    tracing::info!(
+        "deleted {} links and {sessions} play sessions on request of {discord_user_id}",
+        links.len()
+    );
+    Ok(Deletion {
+        links: links.len(),
+        sessions,
+        whitelist_failures,
+    })
+}
@@ -1,63 +1,640 @@
 use crate::{
+    chat_bridge::{CHAT_BRIDGE, display_name, relay_to_game, sanitize_for_game},
+    data_requests,
     error::AppError,
-    models::{NewReactionMessage, NewSelfAssignableRole},
+    minecraft::{
+        GeyserLookup, LOOKUP_HTTP, LinkOutcome, LinkStatus, MojangLookup, approve_link, deny_link,
+        request_link, unlink,
+    },
+    models::{NewReactionMessage, NewRestartSchedule, NewSelfAssignableRole},
+    playtime::{PlayerPlaytime, format_duration, summarize, week_start},
     queries::{
-        create_reaction_message, create_self_assignable_role, delete_self_assignable_role, get_reaction_message, get_self_assignable_roles
+        create_reaction_message, create_restart_schedule, create_self_assignable_role,
+        delete_maintenance_window, delete_restart_schedule, delete_self_assignable_role,
+        get_minecraft_link, get_minecraft_links_with_status, get_play_sessions_for_player,
+        get_play_sessions_since, get_reaction_message, get_restart_schedules,
+        get_self_assignable_roles, get_upcoming_maintenance_windows,
     },
-    schema::self_assignable_roles,
+    scheduler::{add_maintenance, next_run, parse_cron, short_id},
 };
 use rust_i18n::t;
 use {
-    crate::state::{AppState, MessageType},
-    poise::serenity_prelude as serenity,
+    crate::{
+        game_manager::{
+            ApiError, ErrorCode, GAME_MANAGER, GameServer, Platform, Player, ServerAction,
+            WorldChanges,
+        },
+        health::HEALTH,
+        i18n::{choice, default_locale, join_list, locale, plural},
+        reload::reload,
+        restart::{RestartOutcome, RestartPolicy, online_players, restart_with_policy},
+        state::{AppState, MessageType, db_pool},
+    },
+    poise::{ChoiceParameter, serenity_prelude as serenity},
     std::sync::Arc,
     tokio::sync::Mutex,
 };
 
+/// Ask `game_manager` to act on a server's unit, replying once it has been done
+async fn control_server(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+    action: ServerAction,
+    message: &str,
+) -> Result<(), AppError> {
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    ctx.say(String::from(message)).await?;
+    match client.control(server, action).await {
+        Err(AppError::GameManager(e)) => {
+            tracing::warn!("{action:?} of {server} failed: {e}");
+            ctx.say(e.user_message(locale(ctx))).await?;
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Stop a server, telling the caller in their language first
+async fn stop_server(ctx: crate::state::Context<'_>, server: GameServer) -> Result<(), AppError> {
+    let message = t!(
+        "server.stopping",
+        locale = locale(ctx),
+        server = server.name()
+    );
+    control_server(ctx, server, ServerAction::Stop, &message).await
+}
+
+/// How long a `Wait for empty` restart waits by default, in minutes
+const DEFAULT_MAX_WAIT_MINUTES: u32 = 30;
+
+/// Restart a server with `policy`. Without a policy an empty server is restarted straight away, and if
+/// players are online the caller is asked to confirm a forced restart first. The restart itself runs in
+/// the background since a countdown or wait can outlast the interaction.
+async fn restart_server(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+    policy: Option<RestartPolicy>,
+    max_wait: Option<u32>,
+) -> Result<(), AppError> {
+    let locale = locale(ctx);
+    let players = match online_players(server).await {
+        Ok(players) => players,
+        Err(AppError::GameManager(e)) => {
+            ctx.say(e.user_message(locale)).await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let policy = match policy {
+        Some(policy) => policy,
+        None if players.is_empty() => RestartPolicy::Force,
+        None => {
+            if !confirm_restart(ctx, server, &players).await? {
+                return Ok(());
+            }
+            RestartPolicy::Force
+        }
+    };
+    let max_wait = std::time::Duration::from_secs(
+        u64::from(max_wait.unwrap_or(DEFAULT_MAX_WAIT_MINUTES)) * 60,
+    );
+    ctx.say(t!(
+        "restart.restarting",
+        locale = locale,
+        server = server.name(),
+        policy = choice(policy.name(), locale)
+    ))
+    .await?;
+    let http = ctx.serenity_context().http.clone();
+    let channel = ctx.channel_id();
+    let reason = t!(
+        "restart.requested_by",
+        locale = locale,
+        user = ctx.author().name
+    )
+    .into_owned();
+    tokio::spawn(async move {
+        let message = match restart_with_policy(server, policy, max_wait, &reason).await {
+            Ok(RestartOutcome::Restarted) => {
+                t!("restart.restarted", locale = locale, server = server.name()).into_owned()
+            }
+            Ok(RestartOutcome::Skipped(players)) => t!(
+                &plural("restart.skipped", players.len()),
+                locale = locale,
+                server = server.name(),
+                count = players.len()
+            )
+            .into_owned(),
+            Err(AppError::GameManager(e)) => e.user_message(locale),
+            Err(e) => {
+                tracing::error!("Restart of {server} failed: {e}");
+                t!("restart.failed", locale = locale, server = server.name()).into_owned()
+            }
+        };
+        if let Err(e) = channel.say(&http, message).await {
+            tracing::error!("Failed to report restart of {server}: {e}");
+        }
+    });
+    Ok(())
+}
+
+/// Ask the caller whether to restart a server with players on it, returning their answer
+async fn confirm_restart(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+    players: &[Player],
+) -> Result<bool, AppError> {
+    let locale = locale(ctx);
+    let names: Vec<_> = players.iter().map(Player::display_name).collect();
+    let prompt = t!(
+        &plural("restart.confirm", players.len()),
+        locale = locale,
+        count = players.len(),
+        server = server.name(),
+        names = join_list(&names, locale)
+    );
+    confirm(
+        ctx,
+        &prompt,
+        &t!("restart.confirm_label", locale = locale),
+        &t!("restart.action", locale = locale),
+    )
+    .await
+}
+
+/// Ask the invoking user to confirm `prompt` with a button, replacing the buttons with the outcome.
+/// `action` names what is confirmed in that outcome, e.g. `Restart confirmed.`
+async fn confirm(
+    ctx: crate::state::Context<'_>,
+    prompt: &str,
+    label: &str,
+    action: &str,
+) -> Result<bool, AppError> {
+    let locale = locale(ctx);
+    let confirm_id = format!("{}-confirm", ctx.id());
+    let cancel_id = format!("{}-cancel", ctx.id());
+    let reply = poise::CreateReply::default()
+        .content(prompt)
+        .components(vec![serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(&confirm_id)
+                .style(serenity::ButtonStyle::Danger)
+                .label(label),
+            serenity::CreateButton::new(&cancel_id)
+                .style(serenity::ButtonStyle::Secondary)
+                .label(t!("confirm.cancel", locale = locale)),
+        ])]);
+    let handle = ctx.send(reply).await?;
+    let ids = [confirm_id.clone(), cancel_id];
+    let press = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(std::time::Duration::from_secs(60))
+        .filter(move |press| ids.contains(&press.data.custom_id))
+        .await;
+    let confirmed = press
+        .as_ref()
+        .is_some_and(|press| press.data.custom_id == confirm_id);
+    let outcome = match (&press, confirmed) {
+        (None, _) => t!("confirm.timed_out", locale = locale, action = action),
+        (Some(_), false) => t!("confirm.cancelled", locale = locale, action = action),
+        (Some(_), true) => t!("confirm.confirmed", locale = locale, action = action),
+    };
+    if let Some(press) = press {
+        press
+            .create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+            .await?;
+    }
+    handle
+        .edit(
+            ctx,
+            poise::CreateReply::default()
+                .content(outcome)
+                .components(vec![]),
+        )
+        .await?;
+    Ok(confirmed)
+}
+
+/// Commands regular members may run over RCON, matched against the start of the command
+const MEMBER_RCON_COMMANDS: [&str; 5] = ["list", "say", "save-all", "seed", "whitelist list"];
+
+/// Whether a member without administrator permission may run `command`
+fn is_member_rcon_command(command: &str) -> bool {
+    let command = command.trim().trim_start_matches('/');
+    MEMBER_RCON_COMMANDS.iter().any(|allowed| {
+        command
+            .strip_prefix(allowed)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    })
+}
+
+/// Minecraft formats console output with `§` colour codes, which are noise in Discord
+fn strip_formatting_codes(output: &str) -> String {
+    let mut stripped = String::with_capacity(output.len());
+    let mut chars = output.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Run an RCON command on a Minecraft server, restricting non-admins to [`MEMBER_RCON_COMMANDS`]
+async fn run_rcon(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+    command: String,
+) -> Result<(), AppError> {
+    let is_admin = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions)
+        .is_some_and(serenity::Permissions::administrator);
+    let locale = locale(ctx);
+    if !is_admin && !is_member_rcon_command(&command) {
+        ctx.say(t!(
+            "rcon.not_allowed",
+            locale = locale,
+            commands = MEMBER_RCON_COMMANDS.join(", ")
+        ))
+        .await?;
+        return Ok(());
+    }
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    ctx.defer().await?;
+    match client.rcon(server, &command).await {
+        Ok(output) => {
+            let mut output = strip_formatting_codes(&output);
+            if output.trim().is_empty() {
+                output = t!("rcon.no_output", locale = locale).into_owned();
+            }
+            // stay well within Discord's 2000 character message limit
+            if let Some((index, _)) = output.char_indices().nth(1900) {
+                output.truncate(index);
+                output.push('…');
+            }
+            ctx.say(format!(
+                "```\n{}\n```",
+                output.replace("```", "`\u{200b}``")
+            ))
+            .await?;
+            Ok(())
+        }
+        Err(AppError::GameManager(e)) => {
+            tracing::warn!("rcon on {server} failed: {e}");
+            ctx.say(e.user_message(locale)).await?;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// poise needs commands to be async, even ones only grouping subcommands
+#[allow(clippy::unused_async)]
 #[poise::command(
     slash_command,
-    subcommands("minecraft_geyser_restart", "minecraft_geyser_stop")
+    subcommands(
+        "minecraft_geyser_rcon",
+        "minecraft_geyser_restart",
+        "minecraft_geyser_stop"
+    )
 )]
 pub async fn minecraft_geyser(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
     unreachable!()
 }
 
-#[poise::command(slash_command)]
-pub async fn minecraft_geyser_restart(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
-    ctx.say(String::from("Restarting Java/Bedrock Minecraft Server"))
-        .await?;
-    Ok(())
+#[poise::command(slash_command, rename = "rcon")]
+pub async fn minecraft_geyser_rcon(
+    ctx: crate::state::Context<'_>,
+    command: String,
+) -> Result<(), AppError> {
+    run_rcon(ctx, GameServer::MinecraftGeyser, command).await
 }
 
-#[poise::command(slash_command)]
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn minecraft_geyser_restart(
+    ctx: crate::state::Context<'_>,
+    policy: Option<RestartPolicy>,
+    max_wait: Option<u32>,
+) -> Result<(), AppError> {
+    restart_server(ctx, GameServer::MinecraftGeyser, policy, max_wait).await
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
 pub async fn minecraft_geyser_stop(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
-    ctx.say(String::from("Stopping Java/Bedrock Minecraft Server"))
-        .await?;
-    Ok(())
+    stop_server(ctx, GameServer::MinecraftGeyser).await
 }
 
+#[allow(clippy::unused_async)]
 #[poise::command(
     slash_command,
-    subcommands("minecraft_modded_restart", "minecraft_modded_stop")
+    subcommands(
+        "minecraft_modded_rcon",
+        "minecraft_modded_restart",
+        "minecraft_modded_stop"
+    )
 )]
 pub async fn minecraft_modded(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
     unreachable!()
 }
 
-#[poise::command(slash_command)]
-pub async fn minecraft_modded_restart(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
-    ctx.say(String::from("Restarting Modded Minecraft Server"))
-        .await?;
-    Ok(())
+#[poise::command(slash_command, rename = "rcon")]
+pub async fn minecraft_modded_rcon(
+    ctx: crate::state::Context<'_>,
+    command: String,
+) -> Result<(), AppError> {
+    run_rcon(ctx, GameServer::MinecraftModded, command).await
 }
 
-#[poise::command(slash_command)]
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn minecraft_modded_restart(
+    ctx: crate::state::Context<'_>,
+    policy: Option<RestartPolicy>,
+    max_wait: Option<u32>,
+) -> Result<(), AppError> {
+    restart_server(ctx, GameServer::MinecraftModded, policy, max_wait).await
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
 pub async fn minecraft_modded_stop(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
-    ctx.say(String::from("Stopping Modded Minecraft Server"))
+    stop_server(ctx, GameServer::MinecraftModded).await
+}
+
+#[allow(clippy::unused_async)]
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands(
+        "minecraft_link",
+        "minecraft_unlink",
+        "minecraft_pending",
+        "minecraft_approve",
+        "minecraft_deny"
+    )
+)]
+pub async fn minecraft(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    unreachable!()
+}
+
+/// Render a list of servers whitelisting failed on, if any
+fn whitelist_failures(failed: &[GameServer], locale: &str) -> String {
+    if failed.is_empty() {
+        String::new()
+    } else {
+        let servers = failed
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            " {}",
+            t!(
+                "minecraft.whitelist_failed",
+                locale = locale,
+                servers = servers
+            )
+        )
+    }
+}
+
+#[poise::command(slash_command, guild_only, rename = "link")]
+pub async fn minecraft_link(
+    ctx: crate::state::Context<'_>,
+    username: String,
+    platform: Option<Platform>,
+) -> Result<(), AppError> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| AppError::Other(String::from("not in a guild")))?;
+    ctx.defer_ephemeral().await?;
+    let platform = platform.unwrap_or(Platform::Java);
+    let guild_id = u64::from(guild_id).to_string();
+    let user_id = u64::from(ctx.author().id).to_string();
+    let http = LOOKUP_HTTP.clone();
+    let outcome = match platform {
+        Platform::Java => {
+            let lookup = MojangLookup { http };
+            request_link(
+                &lookup,
+                db_pool()?,
+                guild_id,
+                user_id,
+                username.trim(),
+                platform,
+            )
+            .await?
+        }
+        Platform::Bedrock => {
+            let lookup = GeyserLookup { http };
+            request_link(
+                &lookup,
+                db_pool()?,
+                guild_id,
+                user_id,
+                username.trim(),
+                platform,
+            )
+            .await?
+        }
+    };
+    let locale = locale(ctx);
+    let reply = match outcome {
+        LinkOutcome::Requested(profile) => t!(
+            "minecraft.link.requested",
+            locale = locale,
+            name = profile.name,
+            platform = profile.platform
+        ),
+        LinkOutcome::InvalidUsername => {
+            t!(
+                "minecraft.link.invalid_username",
+                locale = locale,
+                username = username
+            )
+        }
+        LinkOutcome::UnknownUsername => t!(
+            "minecraft.link.unknown_username",
+            locale = locale,
+            platform = platform,
+            username = username
+        ),
+        LinkOutcome::AlreadyLinked(name, status) => {
+            let key = format!("minecraft.status.{}", status.to_lowercase());
+            t!(
+                "minecraft.link.already_linked",
+                locale = locale,
+                name = name,
+                status = t!(&key, locale = locale)
+            )
+        }
+        LinkOutcome::TakenBy(user_id) => {
+            t!("minecraft.link.taken", locale = locale, user = user_id)
+        }
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "unlink")]
+pub async fn minecraft_unlink(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| AppError::Other(String::from("not in a guild")))?;
+    ctx.defer_ephemeral().await?;
+    let link = get_minecraft_link(
+        db_pool()?,
+        u64::from(guild_id).to_string(),
+        u64::from(ctx.author().id).to_string(),
+    )
+    .await?;
+    let locale = locale(ctx);
+    let Some(link) = link else {
+        ctx.say(t!("minecraft.unlink.none", locale = locale))
+            .await?;
+        return Ok(());
+    };
+    let failed = unlink(db_pool()?, &link).await?;
+    ctx.say(format!(
+        "{}{}",
+        t!(
+            "minecraft.unlink.done",
+            locale = locale,
+            name = link.minecraft_name
+        ),
+        whitelist_failures(&failed, locale)
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "pending",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn minecraft_pending(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| AppError::Other(String::from("not in a guild")))?;
+    let links = get_minecraft_links_with_status(
+        db_pool()?,
+        u64::from(guild_id).to_string(),
+        LinkStatus::Pending.to_string(),
+    )
+    .await?;
+    let reply = if links.is_empty() {
+        t!("minecraft.pending.none", locale = locale(ctx)).into_owned()
+    } else {
+        links
+            .iter()
+            .map(|link| {
+                format!(
+                    "<@{}> → `{}` ({})",
+                    link.discord_user_id, link.minecraft_name, link.platform
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(reply)
+            .ephemeral(true)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Find the pending link of `user`, replying and returning `None` if there is none
+async fn pending_link(
+    ctx: crate::state::Context<'_>,
+    user: &serenity::User,
+) -> Result<Option<crate::models::MinecraftLink>, AppError> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| AppError::Other(String::from("not in a guild")))?;
+    let link = get_minecraft_link(
+        db_pool()?,
+        u64::from(guild_id).to_string(),
+        u64::from(user.id).to_string(),
+    )
+    .await?
+    .filter(|link| link.status == LinkStatus::Pending.to_string());
+    if link.is_none() {
+        ctx.say(t!(
+            "minecraft.pending.not_found",
+            locale = locale(ctx),
+            user = user.name
+        ))
         .await?;
+    }
+    Ok(link)
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "approve",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn minecraft_approve(
+    ctx: crate::state::Context<'_>,
+    user: serenity::User,
+) -> Result<(), AppError> {
+    ctx.defer().await?;
+    let Some(link) = pending_link(ctx, &user).await? else {
+        return Ok(());
+    };
+    let failed = approve_link(db_pool()?, &link, u64::from(ctx.author().id).to_string()).await?;
+    let locale = locale(ctx);
+    ctx.say(format!(
+        "{}{}",
+        t!(
+            "minecraft.approve.done",
+            locale = locale,
+            user = link.discord_user_id,
+            name = link.minecraft_name
+        ),
+        whitelist_failures(&failed, locale)
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "deny",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn minecraft_deny(
+    ctx: crate::state::Context<'_>,
+    user: serenity::User,
+) -> Result<(), AppError> {
+    let Some(link) = pending_link(ctx, &user).await? else {
+        return Ok(());
+    };
+    deny_link(db_pool()?, &link, u64::from(ctx.author().id).to_string()).await?;
+    ctx.say(t!(
+        "minecraft.deny.done",
+        locale = locale(ctx),
+        user = link.discord_user_id,
+        name = link.minecraft_name
+    ))
+    .await?;
     Ok(())
 }
 
+#[allow(clippy::unused_async)]
 #[poise::command(
     slash_command,
     subcommands("terraria_broadcast_message", "terraria_restart", "terraria_stop")
@@ -66,28 +643,729 @@ pub async fn terraria(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
     unreachable!()
 }
 
-#[poise::command(slash_command)]
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
 pub async fn terraria_broadcast_message(
     ctx: crate::state::Context<'_>,
-    #[description = "The message to broadcast"] message: String,
+    message: String,
+) -> Result<(), AppError> {
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    let member = ctx.author_member().await;
+    let author = sanitize_for_game(display_name(
+        member.as_ref().and_then(|m| m.nick.as_deref()),
+        ctx.author(),
+    ));
+    let message = sanitize_for_game(&message);
+    let reply = match client
+        .broadcast(GameServer::Terraria, &format!("<{author}> {message}"))
+        .await
+    {
+        Ok(()) => t!(
+            "terraria.broadcast",
+            locale = locale(ctx),
+            message = message
+        )
+        .into_owned(),
+        Err(AppError::GameManager(e)) => e.user_message(locale(ctx)),
+        Err(e) => return Err(e),
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "chat_bridge",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn chat_bridge(ctx: crate::state::Context<'_>, enabled: bool) -> Result<(), AppError> {
+    let locale = locale(ctx);
+    let Some(bridge) = CHAT_BRIDGE.get() else {
+        ctx.say(t!("chat_bridge.not_configured", locale = locale))
+            .await?;
+        return Ok(());
+    };
+    let channel = ctx.channel_id();
+    let Some(server) = bridge.bridged_server(channel) else {
+        ctx.say(t!("chat_bridge.not_bridged", locale = locale))
+            .await?;
+        return Ok(());
+    };
+    bridge
+        .set_enabled(
+            db_pool()?,
+            channel,
+            enabled,
+            u64::from(ctx.author().id).to_string(),
+        )
+        .await?;
+    let key = if enabled {
+        "chat_bridge.enabled"
+    } else {
+        "chat_bridge.disabled"
+    };
+    ctx.say(t!(key, locale = locale, server = server.name()))
+        .await?;
+    Ok(())
+}
+
+#[allow(clippy::unused_async)]
+#[poise::command(slash_command, guild_only, subcommands("config_reload"))]
+pub async fn config(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    unreachable!()
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "reload",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn config_reload(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    ctx.defer_ephemeral().await?;
+    let locale = locale(ctx);
+    let reply = match reload().await {
+        Ok(reloaded) => {
+            let key = if reloaded.changed {
+                "config.reloaded"
+            } else {
+                "config.unchanged"
+            };
+            let mut reply = t!(key, locale = locale).into_owned();
+            if !reloaded.restart_required.is_empty() {
+                let keys = reloaded
+                    .restart_required
+                    .iter()
+                    .map(|key| format!("`{key}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                reply.push(' ');
+                reply.push_str(&t!("config.restart_required", locale = locale, keys = keys));
+            }
+            reply
+        }
+        Err(AppError::Config(e)) => t!("config.invalid", locale = locale, error = e).into_owned(),
+        Err(e) => return Err(e),
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn terraria_restart(
+    ctx: crate::state::Context<'_>,
+    policy: Option<RestartPolicy>,
+    max_wait: Option<u32>,
+) -> Result<(), AppError> {
+    restart_server(ctx, GameServer::Terraria, policy, max_wait).await
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn terraria_stop(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    stop_server(ctx, GameServer::Terraria).await
+}
+
+#[allow(clippy::unused_async)]
+#[poise::command(slash_command, subcommands("privacy_export", "privacy_delete"))]
+pub async fn privacy(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    unreachable!()
+}
+
+#[poise::command(slash_command, rename = "export")]
+pub async fn privacy_export(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    ctx.defer_ephemeral().await?;
+    let data = data_requests::export(db_pool()?, &u64::from(ctx.author().id).to_string()).await?;
+    let attachment =
+        serenity::CreateAttachment::bytes(serde_json::to_vec_pretty(&data)?, "data.json");
+    ctx.send(
+        poise::CreateReply::default()
+            .content(t!("privacy.export.done", locale = locale(ctx)))
+            .attachment(attachment)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "delete")]
+pub async fn privacy_delete(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    let locale = locale(ctx);
+    let confirmed = confirm(
+        ctx,
+        &t!("privacy.delete.confirm", locale = locale),
+        &t!("privacy.delete.confirm_label", locale = locale),
+        &t!("privacy.delete.action", locale = locale),
+    )
+    .await?;
+    if !confirmed {
+        return Ok(());
+    }
+    let deleted =
+        data_requests::delete(db_pool()?, &u64::from(ctx.author().id).to_string()).await?;
+    let reply = if deleted.links == 0 && deleted.sessions == 0 {
+        t!("privacy.delete.nothing", locale = locale).into_owned()
+    } else {
+        let links = t!(
+            &plural("privacy.delete.links", deleted.links),
+            locale = locale,
+            count = deleted.links
+        )
+        .into_owned();
+        let sessions = t!(
+            &plural("privacy.delete.sessions", deleted.sessions),
+            locale = locale,
+            count = deleted.sessions
+        )
+        .into_owned();
+        format!(
+            "{}{}",
+            t!(
+                "privacy.delete.done",
+                locale = locale,
+                links = links,
+                sessions = sessions
+            ),
+            whitelist_failures(&deleted.whitelist_failures, locale)
+        )
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[allow(clippy::unused_async)]
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands(
+        "schedule_add",
+        "schedule_list",
+        "schedule_remove",
+        "schedule_maintenance"
+    )
+)]
+pub async fn schedule(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    unreachable!()
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "add",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn schedule_add(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+    cron: String,
+    policy: Option<RestartPolicy>,
+    max_wait: Option<u32>,
 ) -> Result<(), AppError> {
+    let locale = locale(ctx);
+    let cron = cron.trim().to_owned();
+    if let Err(e) = parse_cron(&cron) {
+        ctx.say(t!(
+            "schedule.invalid_cron",
+            locale = locale,
+            cron = cron,
+            error = e
+        ))
+        .await?;
+        return Ok(());
+    }
+    let schedule = create_restart_schedule(
+        db_pool()?,
+        NewRestartSchedule {
+            server: server.id().to_owned(),
+            cron,
+            created_by: u64::from(ctx.author().id).to_string(),
+            policy: policy.unwrap_or(RestartPolicy::Force).to_string(),
+            max_wait_minutes: i32::try_from(max_wait.unwrap_or(DEFAULT_MAX_WAIT_MINUTES))
+                .unwrap_or(i32::MAX),
+        },
+    )
+    .await?;
+    let next = next_run(&schedule).map_or_else(String::new, |next| {
+        format!(
+            " {}",
+            t!(
+                "schedule.next",
+                locale = locale,
+                timestamp = next.timestamp()
+            )
+        )
+    });
     ctx.say(format!(
-        "Broadcasting message: `{}` to Terraria Server",
-        message
+        "{}{next}",
+        t!(
+            "schedule.added",
+            locale = locale,
+            id = short_id(schedule.id),
+            server = server.name()
+        )
     ))
     .await?;
     Ok(())
 }
 
-#[poise::command(slash_command)]
-pub async fn terraria_restart(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
-    ctx.say(String::from("Restarting Terraria Server")).await?;
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "list",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn schedule_list(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    let locale = locale(ctx);
+    let server_name =
+        |id: &str| GameServer::from_id(id).map_or_else(|| id.to_owned(), |s| s.name().to_owned());
+    let schedules = get_restart_schedules(db_pool()?)
+        .await?
+        .iter()
+        .map(|schedule| {
+            let next = next_run(schedule).map_or_else(
+                || t!("schedule.never", locale = locale).into_owned(),
+                |next| format!("<t:{}:R>", next.timestamp()),
+            );
+            let policy = schedule
+                .policy
+                .parse::<RestartPolicy>()
+                .map_or_else(|_| schedule.policy.clone(), |p| choice(p.name(), locale));
+            t!(
+                "schedule.schedule",
+                locale = locale,
+                id = short_id(schedule.id),
+                server = server_name(&schedule.server),
+                cron = schedule.cron,
+                policy = policy,
+                next = next
+            )
+            .into_owned()
+        })
+        .collect::<Vec<_>>();
+    let windows = get_upcoming_maintenance_windows(db_pool()?, chrono::Utc::now())
+        .await?
+        .iter()
+        .map(|window| {
+            t!(
+                "schedule.window",
+                locale = locale,
+                id = short_id(window.id),
+                server = server_name(&window.server),
+                starts = window.starts_at.timestamp(),
+                ends = window.ends_at.timestamp(),
+                reason = window.reason
+            )
+            .into_owned()
+        })
+        .collect::<Vec<_>>();
+    let section = |title: &str, lines: Vec<String>| {
+        if lines.is_empty() {
+            format!(
+                "**{}**\n{}",
+                t!(title, locale = locale),
+                t!("schedule.none", locale = locale)
+            )
+        } else {
+            format!("**{}**\n{}", t!(title, locale = locale), lines.join("\n"))
+        }
+    };
+    ctx.say(format!(
+        "{}\n{}",
+        section("schedule.schedules", schedules),
+        section("schedule.windows", windows)
+    ))
+    .await?;
     Ok(())
 }
 
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "remove",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn schedule_remove(ctx: crate::state::Context<'_>, id: String) -> Result<(), AppError> {
+    let id = id.trim().to_lowercase();
+    let schedule = get_restart_schedules(db_pool()?)
+        .await?
+        .into_iter()
+        .find(|schedule| short_id(schedule.id) == id);
+    let window = get_upcoming_maintenance_windows(db_pool()?, chrono::Utc::now())
+        .await?
+        .into_iter()
+        .find(|window| short_id(window.id) == id);
+    let locale = locale(ctx);
+    let reply = if let Some(schedule) = schedule {
+        delete_restart_schedule(db_pool()?, schedule.id).await?;
+        t!("schedule.removed_schedule", locale = locale, id = id)
+    } else if let Some(window) = window {
+        delete_maintenance_window(db_pool()?, window.id).await?;
+        t!("schedule.removed_window", locale = locale, id = id)
+    } else {
+        t!("schedule.not_found", locale = locale, id = id)
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "maintenance",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn schedule_maintenance(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+    #[min = 1] duration: u32,
+    starts_in: Option<u32>,
+    reason: Option<String>,
+) -> Result<(), AppError> {
+    let locale = locale(ctx);
+    let starts_at =
+        chrono::Utc::now() + chrono::Duration::minutes(i64::from(starts_in.unwrap_or_default()));
+    let ends_at = starts_at + chrono::Duration::minutes(i64::from(duration));
+    let window = add_maintenance(
+        server,
+        starts_at,
+        ends_at,
+        reason.unwrap_or_else(|| t!("schedule.maintenance_reason", locale = locale).into_owned()),
+        u64::from(ctx.author().id).to_string(),
+    )
+    .await?;
+    ctx.say(t!(
+        "schedule.maintenance_added",
+        locale = locale,
+        id = short_id(window.id),
+        server = server.name(),
+        starts = starts_at.timestamp(),
+        ends = ends_at.timestamp()
+    ))
+    .await?;
+    Ok(())
+}
+
+#[allow(clippy::unused_async)]
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("backup_create", "backup_list", "backup_restore")
+)]
+pub async fn backup(_ctx: crate::state::Context<'_>) -> Result<(), AppError> {
+    unreachable!()
+}
+
+/// Backups shown by `/backup list`
+const BACKUP_LIST_SIZE: usize = 10;
+
+/// A byte count in the largest unit that keeps it above one, e.g. `1.5 GiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_millis(millis: u64) -> String {
+    format!("{:.1}s", millis as f64 / 1000.0)
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "create",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn backup_create(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+) -> Result<(), AppError> {
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    // archiving a world takes longer than Discord waits for a reply
+    ctx.defer().await?;
+    let locale = locale(ctx);
+    let reply = match client.create_backup(server).await {
+        Ok(backup) => t!(
+            "backup.created",
+            locale = locale,
+            server = server.name(),
+            id = backup.id,
+            size = format_size(backup.size_bytes),
+            duration = backup.duration_ms.map_or_else(
+                || t!("backup.unknown_duration", locale = locale).into_owned(),
+                format_millis
+            )
+        )
+        .into_owned(),
+        Err(AppError::GameManager(e)) => {
+            tracing::warn!("Backup of {server} failed: {e}");
+            e.user_message(locale)
+        }
+        Err(e) => return Err(e),
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "list",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn backup_list(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+) -> Result<(), AppError> {
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    let locale = locale(ctx);
+    let backups = match client.backups(server).await {
+        Ok(backups) => backups,
+        Err(AppError::GameManager(e)) => {
+            ctx.say(e.user_message(locale)).await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let reply = if backups.is_empty() {
+        t!("backup.none", locale = locale, server = server.name()).into_owned()
+    } else {
+        let total: u64 = backups.iter().map(|b| b.size_bytes).sum();
+        let lines = backups
+            .iter()
+            .take(BACKUP_LIST_SIZE)
+            .map(|b| {
+                let key = if b.pre_restore {
+                    "backup.entry_pre_restore"
+                } else {
+                    "backup.entry"
+                };
+                t!(
+                    key,
+                    locale = locale,
+                    id = b.id,
+                    created = b.created_at.timestamp(),
+                    size = format_size(b.size_bytes)
+                )
+                .into_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "{}\n{lines}",
+            t!(
+                "backup.list",
+                locale = locale,
+                server = server.name(),
+                count = backups.len(),
+                size = format_size(total)
+            )
+        )
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// What a restore did or would do to a world, e.g. `12 files added, 3 removed and 40 changed`
+fn describe_changes(changes: &WorldChanges, locale: &str) -> String {
+    t!(
+        &plural("backup.changes", changes.added),
+        locale = locale,
+        added = changes.added,
+        removed = changes.removed,
+        changed = changes.changed,
+        unchanged = changes.unchanged,
+        current = format_size(changes.current_bytes),
+        restored = format_size(changes.restored_bytes)
+    )
+    .into_owned()
+}
+
+/// Why a restore was refused or failed. An invalid backup is explained rather than just rejected.
+fn restore_error(server: GameServer, e: &ApiError, locale: &str) -> String {
+    tracing::warn!("Restore of {server} failed: {e}");
+    if e.code == ErrorCode::InvalidRequest {
+        format!("{} {}", e.user_message(locale), e.message)
+    } else {
+        e.user_message(locale)
+    }
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "restore",
+    required_permissions = "ADMINISTRATOR"
+)]
+pub async fn backup_restore(
+    ctx: crate::state::Context<'_>,
+    server: GameServer,
+    backup: String,
+    dry_run: Option<bool>,
+) -> Result<(), AppError> {
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    let backup = backup.trim();
+    ctx.defer().await?;
+    let locale = locale(ctx);
+    let plan = match client.restore_backup(server, backup, true).await {
+        Ok(plan) => plan,
+        Err(AppError::GameManager(e)) => {
+            ctx.say(restore_error(server, &e, locale)).await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let key = if plan.was_running {
+        "backup.will_stop"
+    } else {
+        "backup.will_start"
+    };
+    let summary = t!(
+        "backup.plan",
+        locale = locale,
+        server = server.name(),
+        id = plan.backup.id,
+        created = plan.backup.created_at.timestamp(),
+        changes = describe_changes(&plan.changes, locale),
+        stop = t!(key, locale = locale, server = server.name())
+    );
+    if dry_run.unwrap_or(false) {
+        ctx.say(format!(
+            "{}\n{summary}",
+            t!("backup.dry_run", locale = locale)
+        ))
+        .await?;
+        return Ok(());
+    }
+    let action = t!("backup.restore_action", locale = locale);
+    let prompt = format!("{summary}\n{}", t!("backup.confirm", locale = locale));
+    if !confirm(ctx, &prompt, &action, &action).await? {
+        return Ok(());
+    }
+    let reply = match client.restore_backup(server, backup, false).await {
+        Ok(restored) => {
+            let previous = restored.pre_restore.map_or_else(
+                || t!("backup.no_previous_world", locale = locale),
+                |b| t!("backup.previous_world", locale = locale, id = b.id),
+            );
+            t!(
+                "backup.restored",
+                locale = locale,
+                server = server.name(),
+                id = restored.backup.id,
+                duration = format_millis(restored.duration_ms),
+                changes = describe_changes(&restored.changes, locale),
+                previous = previous
+            )
+            .into_owned()
+        }
+        Err(AppError::GameManager(e)) => restore_error(server, &e, locale),
+        Err(e) => return Err(e),
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Number of players shown on the weekly leaderboard
+const LEADERBOARD_SIZE: usize = 10;
+
+/// A player's playtime on each server, as `/playtime <player>` replies with it
+fn player_playtime(entry: &PlayerPlaytime, locale: &str) -> String {
+    let servers = entry
+        .servers
+        .iter()
+        .map(|s| {
+            let name =
+                GameServer::from_id(&s.server).map_or(s.server.as_str(), |server| server.name());
+            format!("- {name}: {}", format_duration(s.total_seconds))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let seen = entry.last_seen.map_or_else(
+        || t!("playtime.online_now", locale = locale),
+        |seen| {
+            t!(
+                "playtime.last_seen",
+                locale = locale,
+                timestamp = seen.timestamp()
+            )
+        },
+    );
+    format!(
+        "{}\n{servers}",
+        t!(
+            "playtime.summary",
+            locale = locale,
+            name = entry.name,
+            total = format_duration(entry.total_seconds),
+            week = format_duration(entry.week_seconds),
+            seen = seen
+        )
+    )
+}
+
 #[poise::command(slash_command)]
-pub async fn terraria_stop(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
-    ctx.say(String::from("Stopping Terraria Server")).await?;
+pub async fn playtime(
+    ctx: crate::state::Context<'_>,
+    player: Option<String>,
+) -> Result<(), AppError> {
+    let locale = locale(ctx);
+    let now = chrono::Utc::now();
+    let week_start = week_start(now);
+    let reply = if let Some(player) = player {
+        let sessions = get_play_sessions_for_player(db_pool()?, player.trim().to_owned()).await?;
+        summarize(&sessions, now, week_start).first().map_or_else(
+            || t!("playtime.none", locale = locale, player = player).into_owned(),
+            |entry| player_playtime(entry, locale),
+        )
+    } else {
+        let sessions = get_play_sessions_since(db_pool()?, Some(week_start)).await?;
+        let mut players = summarize(&sessions, now, week_start);
+        players.retain(|p| p.week_seconds > 0);
+        players.sort_by_key(|p| std::cmp::Reverse(p.week_seconds));
+        if players.is_empty() {
+            t!("playtime.nobody", locale = locale).into_owned()
+        } else {
+            let lines = players
+                .iter()
+                .take(LEADERBOARD_SIZE)
+                .enumerate()
+                .map(|(i, p)| {
+                    format!(
+                        "{}. **{}**: {}",
+                        i + 1,
+                        p.name,
+                        format_duration(p.week_seconds)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{}\n{lines}", t!("playtime.leaderboard", locale = locale))
+        }
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(reply)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
     Ok(())
 }
 
@@ -95,14 +1373,18 @@ pub async fn terraria_stop(ctx: crate::state::Context<'_>) -> Result<(), AppErro
 pub async fn game_roles(ctx: crate::state::Context<'_>) -> Result<(), AppError> {
     if let Some(guild) = ctx.partial_guild().await {
         let self_assignable_roles =
-            get_self_assignable_roles(pool, u64::from(guild.id).to_string()).await?;
+            get_self_assignable_roles(db_pool()?, u64::from(guild.id).to_string()).await?;
         let message = self_assignable_roles
             .iter()
             .map(|sar| format!("{} = <@&{}>", sar.emoji, sar.role_id))
             .collect::<Vec<_>>()
             .join("\n");
         let sent_message = ctx
-            .say(format!("{}\n{}", t!("roles.intro"), message,))
+            .say(format!(
+                "{}\n{}",
+                t!("roles.intro", locale = locale(ctx)),
+                message,
+            ))
             .await?;
         let sent_message_id = sent_message.message().await?.id;
         let new_message = NewReactionMessage {
@@ -110,127 +1392,192 @@ pub async fn game_roles(ctx: crate::state::Context<'_>) -> Result<(), AppError>
             message_id: u64::from(sent_message_id).to_string(),
             message_type: MessageType::RoleAssigner.to_string(),
         };
-        let new_message = create_reaction_message(pool, new_message).await?;
+        create_reaction_message(db_pool()?, new_message).await?;
     }
     Ok(())
 }
 
-#[poise::command(slash_command)]
+#[poise::command(slash_command, guild_only)]
 pub async fn register_self_assignable_role(
     ctx: crate::state::Context<'_>,
-    #[description = "Pick a role"] role: serenity::RoleId,
-    #[description = "Reaction emoji"] emoji: String,
+    role: serenity::RoleId,
+    emoji: String,
 ) -> Result<(), AppError> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| AppError::Other(String::from("not in a guild")))?;
     let new_role = NewSelfAssignableRole {
         emoji: emoji.clone(),
-        guild_id: u64::from(ctx.guild_id().unwrap()).to_string(),
+        guild_id: u64::from(guild_id).to_string(),
         role_id: u64::from(role).to_string(),
     };
-    let new_role = create_self_assignable_role(pool, new_role).await?;
-    ctx.say(format!(
-        "Registered self-assignable role: <@&{}> with emoji {}",
-        role, emoji
+    create_self_assignable_role(db_pool()?, new_role).await?;
+    ctx.say(t!(
+        "roles.registered",
+        locale = locale(ctx),
+        role = role,
+        emoji = emoji
     ))
     .await?;
     Ok(())
 }
 
-#[poise::command(slash_command)]
+#[poise::command(slash_command, guild_only)]
 pub async fn deregister_self_assignable_role(
     ctx: crate::state::Context<'_>,
-    #[description = "Pick a role"] role: serenity::RoleId,
+    role: serenity::RoleId,
 ) -> Result<(), AppError> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| AppError::Other(String::from("not in a guild")))?;
     delete_self_assignable_role(
-        pool,
-        u64::from(ctx.guild_id().unwrap()).to_string(),
+        db_pool()?,
+        u64::from(guild_id).to_string(),
         u64::from(role).to_string(),
     )
     .await?;
-    ctx.say(format!("Deregistered self-assignable role: <@&{}>", role))
+    ctx.say(t!("roles.deregistered", locale = locale(ctx), role = role))
         .await?;
     Ok(())
 }
 
+/// The self-assignable role a reaction stands for, if it was added to or removed from a role assigner
+/// message posted by `/game_roles`
+async fn reaction_role(
+    reaction: &serenity::Reaction,
+) -> Result<Option<serenity::RoleId>, AppError> {
+    let (Some(guild_id), serenity::ReactionType::Unicode(emoji)) =
+        (reaction.guild_id, &reaction.emoji)
+    else {
+        return Ok(None);
+    };
+    let guild_id = u64::from(guild_id).to_string();
+    let message = get_reaction_message(
+        db_pool()?,
+        guild_id.clone(),
+        u64::from(reaction.message_id).to_string(),
+    )
+    .await?;
+    if message.is_none_or(|message| message.message_type != MessageType::RoleAssigner.to_string()) {
+        return Ok(None);
+    }
+    let roles = get_self_assignable_roles(db_pool()?, guild_id).await?;
+    Ok(roles
+        .into_iter()
+        .find(|role| role.emoji == *emoji)
+        .and_then(|role| role.role_id.parse().ok())
+        .map(serenity::RoleId::new))
+}
+
+/// Every slash command the bot registers
+#[must_use]
+pub fn commands() -> Vec<poise::Command<Arc<Mutex<AppState>>, AppError>> {
+    vec![
+        backup(),
+        chat_bridge(),
+        config(),
+        minecraft(),
+        minecraft_geyser(),
+        minecraft_modded(),
+        playtime(),
+        privacy(),
+        schedule(),
+        terraria(),
+        game_roles(),
+        register_self_assignable_role(),
+        deregister_self_assignable_role(),
+    ]
+}
+
+/// Handle the gateway events the bot reacts to outside of commands
+///
+/// # Errors
+///
+/// Fails if a reaction's role can't be looked up or assigned.
 pub async fn event_handler(
     ctx: &serenity::Context,
     event: &serenity::FullEvent,
     _framework: poise::FrameworkContext<'_, Arc<Mutex<AppState>>, AppError>,
-    data: &Arc<Mutex<AppState>>,
+    _data: &Arc<Mutex<AppState>>,
 ) -> Result<(), AppError> {
     match event {
+        serenity::FullEvent::Ready { .. } | serenity::FullEvent::Resume { .. } => {
+            HEALTH.set_gateway_connected(true);
+        }
+        serenity::FullEvent::ShardStageUpdate { event } => {
+            HEALTH.set_gateway_connected(event.new == serenity::ConnectionStage::Connected);
+        }
+        serenity::FullEvent::Message { new_message } => {
+            if let Err(e) = relay_to_game(ctx, new_message).await {
+                tracing::warn!("Relaying message {} to game failed: {e}", new_message.id);
+            }
+        }
         serenity::FullEvent::ReactionAdd { add_reaction } => {
-            let message = get_reaction_message(pool, add_reaction.guild_id, add_reaction.message_id).await?;
-            let data = data.lock().await.clone();
-            match data.message_ids.get(&u64::from(add_reaction.message_id)) {
-                Some(&MessageType::RoleAssigner) => {
-                    if let serenity::ReactionType::Unicode(emoji) = add_reaction.clone().emoji {
-                        if let Some(role_id) = data.self_assignable_roles.get(&emoji) {
-                            if let Some(user_id) = add_reaction.user_id {
-                                if let Some(guild_id) = add_reaction.guild_id {
-                                    add_reaction
-                                        .channel_id
-                                        .say(
-                                            &ctx.http,
-                                            &format!(
-                                                "You have been assigned to the role: <@&{}>",
-                                                role_id
-                                            ),
-                                        )
-                                        .await?;
-                                    let member = guild_id.member(&ctx.http, user_id).await?;
-                                    // TODO: this never resolves, the following trace is never printed
-                                    member.add_role(&ctx.http, *role_id).await?;
-                                    tracing::info!(
-                                        "Added Role ID: {:?} to user: {:?}",
-                                        role_id,
-                                        add_reaction.user_id
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {}
+            if let (Some(role_id), Some(user_id), Some(guild_id)) = (
+                reaction_role(add_reaction).await?,
+                add_reaction.user_id,
+                add_reaction.guild_id,
+            ) {
+                add_reaction
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        t!("roles.assigned", locale = default_locale(), role = role_id),
+                    )
+                    .await?;
+                let member = guild_id.member(&ctx.http, user_id).await?;
+                // TODO: this never resolves, the following trace is never printed
+                member.add_role(&ctx.http, role_id).await?;
+                tracing::info!("Added Role ID: {:?} to user: {:?}", role_id, user_id);
             }
         }
         serenity::FullEvent::ReactionRemove { removed_reaction } => {
-            let data = data.lock().await.clone();
-            match data
-                .message_ids
-                .get(&u64::from(removed_reaction.message_id))
-            {
-                Some(&MessageType::RoleAssigner) => {
-                    if let serenity::ReactionType::Unicode(emoji) = removed_reaction.clone().emoji {
-                        if let Some(role_id) = data.self_assignable_roles.get(&emoji) {
-                            if let Some(user_id) = removed_reaction.user_id {
-                                if let Some(guild_id) = removed_reaction.guild_id {
-                                    removed_reaction
-                                        .channel_id
-                                        .say(
-                                            &ctx.http,
-                                            &format!(
-                                                "You have been removed from the role: <@&{}>",
-                                                role_id
-                                            ),
-                                        )
-                                        .await?;
-                                    let member = guild_id.member(&ctx.http, user_id).await?;
-                                    // TODO this never resolves, the following trace is never printed
-                                    member.remove_role(&ctx.http, *role_id).await?;
-                                    tracing::info!(
-                                        "Removed Role ID: {:?} from user: {:?}",
-                                        role_id,
-                                        removed_reaction.user_id
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {}
+            if let (Some(role_id), Some(user_id), Some(guild_id)) = (
+                reaction_role(removed_reaction).await?,
+                removed_reaction.user_id,
+                removed_reaction.guild_id,
+            ) {
+                removed_reaction
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        t!("roles.removed", locale = default_locale(), role = role_id),
+                    )
+                    .await?;
+                let member = guild_id.member(&ctx.http, user_id).await?;
+                // TODO this never resolves, the following trace is never printed
+                member.remove_role(&ctx.http, role_id).await?;
+                tracing::info!("Removed Role ID: {:?} from user: {:?}", role_id, user_id);
             }
         }
         _ => {}
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_may_only_run_allowed_rcon_commands() {
+        for (command, allowed) in [
+            ("list", true),
+            ("/list", true),
+            ("  say hello there ", true),
+            ("save-all flush", true),
+            ("seed", true),
+            ("whitelist list", true),
+            ("whitelist add Notch", false),
+            ("whitelist remove Notch", false),
+            ("whitelist", false),
+            ("listen", false),
+            ("sayhello", false),
+            ("op Notch", false),
+            ("stop", false),
+            ("", false),
+        ] {
+            assert_eq!(is_member_rcon_command(command), allowed, "{command:?}");
+        }
+    }
+}
@@ -1,15 +1,24 @@
 #[derive(Debug)]
 pub enum AppError {
     Anyhow(anyhow::Error),
+    #[cfg(feature = "server")]
+    Config(crate::config::ConfigError),
+    #[cfg(feature = "server")]
+    Database(diesel::result::Error),
     EnvVar(std::env::VarError),
+    #[cfg(feature = "server")]
+    GameManager(crate::game_manager::ApiError),
     Io(std::io::Error),
     Json(serde_json::Error),
     Other(String),
     #[cfg(feature = "server")]
     Parse(url::ParseError),
+    #[cfg(feature = "server")]
+    Pool(String),
     Request(reqwest::Error),
+    /// Boxed, as it is several times the size of the other variants
     #[cfg(feature = "server")]
-    Serenity(serenity::Error),
+    Serenity(Box<serenity::Error>),
     UTF8(std::str::Utf8Error),
 }
 
@@ -17,15 +26,19 @@ pub enum AppError {
 impl axum::response::IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         match self {
-            AppError::Anyhow(e) => tracing::error!("Anyhow error: {e:}"),
-            AppError::EnvVar(e) => tracing::error!("Environment variable error: {e}"),
-            AppError::Io(e) => tracing::error!("IO error: {e:}"),
-            AppError::Json(e) => tracing::error!("JSON error: {e:}"),
-            AppError::Other(e) => tracing::error!("Other error: {e:}"),
-            AppError::Parse(e) => tracing::error!("Parse error: {e:}"),
-            AppError::Request(e) => tracing::error!("Request error: {e:}"),
-            AppError::Serenity(e) => tracing::error!("Serenity error: {e:}"),
-            AppError::UTF8(e) => tracing::error!("UTF-8 error: {e:}"),
+            Self::Anyhow(e) => tracing::error!("Anyhow error: {e:}"),
+            Self::Config(e) => tracing::error!("Configuration error: {e:}"),
+            Self::Database(e) => tracing::error!("Database error: {e:}"),
+            Self::EnvVar(e) => tracing::error!("Environment variable error: {e}"),
+            Self::GameManager(e) => tracing::error!("Game manager error: {e}"),
+            Self::Io(e) => tracing::error!("IO error: {e:}"),
+            Self::Json(e) => tracing::error!("JSON error: {e:}"),
+            Self::Other(e) => tracing::error!("Other error: {e:}"),
+            Self::Parse(e) => tracing::error!("Parse error: {e:}"),
+            Self::Pool(e) => tracing::error!("Connection pool error: {e:}"),
+            Self::Request(e) => tracing::error!("Request error: {e:}"),
+            Self::Serenity(e) => tracing::error!("Serenity error: {e:}"),
+            Self::UTF8(e) => tracing::error!("UTF-8 error: {e:}"),
         }
 
         (
@@ -40,12 +53,20 @@ impl std::fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Anyhow(e) => write!(f, "Anyhow error: {e}"),
+            #[cfg(feature = "server")]
+            Self::Config(e) => write!(f, "{e}"),
+            #[cfg(feature = "server")]
+            Self::Database(e) => write!(f, "Database error: {e}"),
             Self::EnvVar(e) => write!(f, "Environment variable error: {e}"),
+            #[cfg(feature = "server")]
+            Self::GameManager(e) => write!(f, "Game manager error: {e}"),
             Self::Io(e) => write!(f, "IO error: {e}"),
             Self::Json(e) => write!(f, "JSON error: {e}"),
             Self::Other(e) => write!(f, "Other error: {e}"),
             #[cfg(feature = "server")]
             Self::Parse(e) => write!(f, "Parse error: {e}"),
+            #[cfg(feature = "server")]
+            Self::Pool(e) => write!(f, "Connection pool error: {e}"),
             Self::Request(e) => write!(f, "Request error: {e}"),
             #[cfg(feature = "server")]
             Self::Serenity(e) => write!(f, "Serenity error: {e}"),
@@ -62,6 +83,34 @@ impl From<anyhow::Error> for AppError {
     }
 }
 
+#[cfg(feature = "server")]
+impl From<crate::config::ConfigError> for AppError {
+    fn from(err: crate::config::ConfigError) -> Self {
+        Self::Config(err)
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<diesel::result::Error> for AppError {
+    fn from(err: diesel::result::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<deadpool_diesel::PoolError> for AppError {
+    fn from(err: deadpool_diesel::PoolError) -> Self {
+        Self::Pool(err.to_string())
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<deadpool_diesel::InteractError> for AppError {
+    fn from(err: deadpool_diesel::InteractError) -> Self {
+        Self::Pool(err.to_string())
+    }
+}
+
 impl From<std::env::VarError> for AppError {
     fn from(err: std::env::VarError) -> Self {
         Self::EnvVar(err)
@@ -96,7 +145,7 @@ impl From<reqwest::Error> for AppError {
 #[cfg(feature = "server")]
 impl From<serenity::Error> for AppError {
     fn from(err: serenity::Error) -> Self {
-        Self::Serenity(err)
+        Self::Serenity(Box::new(err))
     }
 }
 
@@ -0,0 +1,863 @@
+//! Client for the `game_manager` API, which runs on the machine hosting the game servers.
+//!
+//! The client authenticates with the client-credentials exchange on `/api/authorize`, keeps the returned
+//! access token until shortly before it expires and then renews it with the refresh token, falling back to
+//! a fresh exchange if the refresh is rejected.
+
+use {
+    crate::error::AppError,
+    reqwest::{Method, StatusCode},
+    rust_i18n::t,
+    serde::{Deserialize, Serialize, de::DeserializeOwned},
+    std::{
+        sync::{OnceLock, PoisonError, RwLock},
+        time::{Duration, Instant},
+    },
+    tokio::sync::Mutex,
+    url::Url,
+};
+
+pub static GAME_MANAGER: OnceLock<GameManagerClient> = OnceLock::new();
+
+pub fn set_game_manager_client(client: GameManagerClient) {
+    let _ = GAME_MANAGER.set(client);
+}
+
+/// Renew the access token this long before `game_manager` would reject it
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+/// Creating or restoring a backup archives a whole world, so wait far longer than for other requests
+const BACKUP_TIMEOUT: Duration = Duration::from_mins(30);
+
+/// The game servers managed by `game_manager`
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, poise::ChoiceParameter,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum GameServer {
+    #[name = "Java/Bedrock Minecraft"]
+    MinecraftGeyser,
+    #[name = "Modded Minecraft"]
+    MinecraftModded,
+    #[name = "Terraria"]
+    Terraria,
+}
+
+impl GameServer {
+    pub const ALL: [Self; 3] = [Self::MinecraftGeyser, Self::MinecraftModded, Self::Terraria];
+
+    /// The id `game_manager` uses for this server in `/api/servers/{id}`
+    #[must_use]
+    pub const fn id(self) -> &'static str {
+        match self {
+            Self::MinecraftGeyser => "minecraft_geyser",
+            Self::MinecraftModded => "minecraft_modded",
+            Self::Terraria => "terraria",
+        }
+    }
+
+    /// The server with this `game_manager` id, as stored in the database
+    #[must_use]
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|server| server.id() == id)
+    }
+}
+
+impl std::fmt::Display for GameServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// Which Minecraft client a player joined with
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, poise::ChoiceParameter,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    Java,
+    Bedrock,
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Player {
+    /// The name without any Floodgate prefix
+    pub name: String,
+    /// `None` for games other than Minecraft
+    pub platform: Option<Platform>,
+    pub uuid: Option<String>,
+    /// The Xbox user id of a Bedrock player
+    pub xuid: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServerStatus {
+    pub id: GameServer,
+    pub online: bool,
+    pub version: Option<String>,
+    pub players: Vec<Player>,
+    pub max_players: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub author: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChatBody {
+    pub cursor: Option<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// The archive name without its extension, e.g. `terraria-20261019T040000Z`
+    pub id: String,
+    pub server: GameServer,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub size_bytes: u64,
+    /// Taken automatically of the world a restore replaced
+    pub pre_restore: bool,
+    /// How long creating the backup took, only set in the response to creating it
+    pub duration_ms: Option<u64>,
+}
+
+/// How restoring a backup changes the files of a world
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorldChanges {
+    pub added: usize,
+    pub removed: usize,
+    /// Files in both the world and the backup whose size differs
+    pub changed: usize,
+    pub unchanged: usize,
+    pub current_bytes: u64,
+    pub restored_bytes: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub backup: BackupInfo,
+    pub dry_run: bool,
+    /// Whether the server was running, and so was or would be stopped for the restore
+    pub was_running: bool,
+    pub changes: WorldChanges,
+    /// The archive of the replaced world, none for dry runs or when there was no world
+    pub pre_restore: Option<BackupInfo>,
+    pub duration_ms: u64,
+}
+
+/// Control actions `game_manager` can perform on a server's unit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerAction {
+    Restart,
+    Start,
+    Stop,
+}
+
+impl ServerAction {
+    const fn path(self) -> &'static str {
+        match self {
+            Self::Restart => "restart",
+            Self::Start => "start",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+/// The machine readable `code` of a `game_manager` error response
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    UpstreamUnreachable,
+    UpstreamTimeout,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    InvalidRequest,
+    Conflict,
+    #[serde(other)]
+    Internal,
+}
+
+/// A non-success response from `game_manager`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: u16,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    /// A reply suitable for showing to the Discord user who triggered the request, in `locale`
+    #[must_use]
+    pub fn user_message(&self, locale: &str) -> String {
+        let key = match self.code {
+            ErrorCode::UpstreamUnreachable => "game_manager.upstream_unreachable",
+            ErrorCode::UpstreamTimeout => "game_manager.upstream_timeout",
+            ErrorCode::Unauthorized => "game_manager.unauthorized",
+            ErrorCode::Forbidden => "game_manager.forbidden",
+            ErrorCode::NotFound => "game_manager.not_found",
+            ErrorCode::InvalidRequest => "game_manager.invalid_request",
+            ErrorCode::Conflict => "game_manager.conflict",
+            ErrorCode::Internal => "game_manager.internal",
+        };
+        t!(key, locale = locale).into_owned()
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "game_manager responded {} ({:?}): {}",
+            self.status, self.code, self.message
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: ErrorCode,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct AuthPayload<'a> {
+    client_id: &'a str,
+    client_secret: &'a str,
+}
+
+#[derive(Serialize)]
+struct RefreshPayload<'a> {
+    refresh_token: &'a str,
+}
+
+#[derive(Deserialize)]
+struct AuthBody {
+    access_token: String,
+    expires_in: u64,
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct RconBody {
+    output: String,
+}
+
+struct Tokens {
+    access_token: String,
+    refresh_token: String,
+    expires_at: Instant,
+}
+
+impl From<AuthBody> for Tokens {
+    fn from(body: AuthBody) -> Self {
+        Self {
+            access_token: body.access_token,
+            refresh_token: body.refresh_token,
+            expires_at: Instant::now() + Duration::from_secs(body.expires_in),
+        }
+    }
+}
+
+/// Where `game_manager` is and how the client authenticates with it
+#[derive(PartialEq, Eq)]
+struct Endpoint {
+    base_url: Url,
+    client_id: String,
+    client_secret: String,
+}
+
+pub struct GameManagerClient {
+    endpoint: RwLock<Endpoint>,
+    http: reqwest::Client,
+    max_retries: u32,
+    tokens: Mutex<Option<Tokens>>,
+}
+
+impl GameManagerClient {
+    /// # Errors
+    ///
+    /// Fails if `base_url` is not a valid URL or the HTTP client cannot be built.
+    pub fn new(
+        base_url: &str,
+        client_id: String,
+        client_secret: String,
+        user_agent: &str,
+    ) -> Result<Self, AppError> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent(user_agent)
+            .build()?;
+        Ok(Self {
+            endpoint: RwLock::new(Endpoint {
+                base_url: Url::parse(base_url)?,
+                client_id,
+                client_secret,
+            }),
+            http,
+            max_retries: 3,
+            tokens: Mutex::new(None),
+        })
+    }
+
+    /// Talk to another `game_manager` or authenticate differently from now on. Tokens issued for the old
+    /// endpoint are dropped if anything changed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `base_url` is not a valid URL, leaving the client as it was.
+    pub async fn reconfigure(
+        &self,
+        base_url: &str,
+        client_id: String,
+        client_secret: String,
+    ) -> Result<(), AppError> {
+        let endpoint = Endpoint {
+            base_url: Url::parse(base_url)?,
+            client_id,
+            client_secret,
+        };
+        // hold the tokens so no request authenticates against the old endpoint in between
+        let mut tokens = self.tokens.lock().await;
+        let mut current = self
+            .endpoint
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *current != endpoint {
+            *current = endpoint;
+            *tokens = None;
+        }
+        drop(current);
+        drop(tokens);
+        Ok(())
+    }
+
+    /// Use a preconfigured HTTP client, e.g. one with a different timeout
+    #[must_use]
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// How often a request is retried after a connection error, timeout or 5xx response
+    #[must_use]
+    pub const fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached or rejects the request.
+    pub async fn status(&self, server: GameServer) -> Result<ServerStatus, AppError> {
+        self.request(
+            Method::GET,
+            self.url(&format!("api/servers/{server}/status"))?,
+        )
+        .await
+    }
+
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached or rejects the request.
+    pub async fn players(&self, server: GameServer) -> Result<Vec<Player>, AppError> {
+        self.request(
+            Method::GET,
+            self.url(&format!("api/servers/{server}/players"))?,
+        )
+        .await
+    }
+
+    /// Journal lines for the server's unit between two RFC 3339 timestamps
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached or rejects the request.
+    pub async fn logs(
+        &self,
+        server: GameServer,
+        since: &str,
+        until: &str,
+    ) -> Result<String, AppError> {
+        let mut url = self.url(&format!("api/servers/{server}/logs"))?;
+        url.query_pairs_mut()
+            .append_pair("since", since)
+            .append_pair("until", until);
+        Ok(self.send(Method::GET, url, None).await?.text().await?)
+    }
+
+    /// Chat messages logged since `cursor`. Without a cursor nothing is returned except the cursor to
+    /// start reading from.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached or rejects the request.
+    pub async fn chat(
+        &self,
+        server: GameServer,
+        cursor: Option<&str>,
+    ) -> Result<ChatBody, AppError> {
+        let mut url = self.url(&format!("api/servers/{server}/chat"))?;
+        if let Some(cursor) = cursor {
+            url.query_pairs_mut().append_pair("cursor", cursor);
+        }
+        self.request(Method::GET, url).await
+    }
+
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached or rejects the request.
+    pub async fn control(&self, server: GameServer, action: ServerAction) -> Result<(), AppError> {
+        let url = self.url(&format!("api/servers/{server}/{}", action.path()))?;
+        self.send(Method::POST, url, None).await.map(|_| ())
+    }
+
+    /// Run a console command over RCON and return its output
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached, the server has no RCON configured or the command fails.
+    pub async fn rcon(&self, server: GameServer, command: &str) -> Result<String, AppError> {
+        let url = self.url(&format!("api/servers/{server}/rcon"))?;
+        let body = serde_json::json!({ "command": command });
+        let response: RconBody = self
+            .send(Method::POST, url, Some(&body))
+            .await?
+            .json()
+            .await?;
+        Ok(response.output)
+    }
+
+    /// Show a message to everyone on a server
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached or the server does not accept the message.
+    pub async fn broadcast(&self, server: GameServer, message: &str) -> Result<(), AppError> {
+        let url = self.url(&format!("api/servers/{server}/broadcast"))?;
+        let body = serde_json::json!({ "message": message });
+        self.send(Method::POST, url, Some(&body)).await.map(|_| ())
+    }
+
+    /// Backups of a server, newest first
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached or rejects the request.
+    pub async fn backups(&self, server: GameServer) -> Result<Vec<BackupInfo>, AppError> {
+        self.request(
+            Method::GET,
+            self.url(&format!("api/servers/{server}/backups"))?,
+        )
+        .await
+    }
+
+    /// Save the server's world and archive it
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached, is already backing up the server or the backup fails.
+    pub async fn create_backup(&self, server: GameServer) -> Result<BackupInfo, AppError> {
+        let url = self.url(&format!("api/servers/{server}/backups"))?;
+        Ok(self
+            .send_with_timeout(Method::POST, url, None, Some(BACKUP_TIMEOUT))
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Replace the server's world with a backup, stopping the server meanwhile. A dry run only checks the
+    /// backup and reports how the world would change.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached, does not know the backup, finds it invalid or the restore
+    /// fails.
+    pub async fn restore_backup(
+        &self,
+        server: GameServer,
+        backup: &str,
+        dry_run: bool,
+    ) -> Result<RestoreReport, AppError> {
+        let mut url = self.url(&format!("api/servers/{server}/backups"))?;
+        url.path_segments_mut()
+            .map_err(|()| AppError::Other(String::from("game_manager URL cannot have a path")))?
+            .extend([backup, "restore"]);
+        if dry_run {
+            url.query_pairs_mut().append_pair("dry_run", "true");
+        }
+        Ok(self
+            .send_with_timeout(Method::POST, url, None, Some(BACKUP_TIMEOUT))
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Check that `game_manager` answers, without authenticating
+    ///
+    /// # Errors
+    ///
+    /// Fails if `game_manager` cannot be reached within `timeout` or does not answer successfully.
+    pub async fn ping(&self, timeout: Duration) -> Result<(), AppError> {
+        let response = self
+            .http
+            .get(self.url(".well-known/jwks.json")?)
+            .timeout(timeout)
+            .send()
+            .await?;
+        Self::check(response).await.map(|_| ())
+    }
+
+    fn url(&self, path: &str) -> Result<Url, AppError> {
+        Ok(self
+            .endpoint
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .base_url
+            .join(path)?)
+    }
+
+    async fn request<T: DeserializeOwned>(&self, method: Method, url: Url) -> Result<T, AppError> {
+        Ok(self.send(method, url, None).await?.json().await?)
+    }
+
+    /// Send an authenticated request, retrying transient failures with exponential backoff and
+    /// re-authenticating once if the token is rejected. Only GET requests are retried after they reached
+    /// `game_manager`, as anything else may already have taken effect; a failed connection is retried for
+    /// every method.
+    async fn send(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<&serde_json::Value>,
+    ) -> Result<reqwest::Response, AppError> {
+        self.send_with_timeout(method, url, body, None).await
+    }
+
+    /// [`Self::send`] with a timeout other than the HTTP client's. A request that timed out may still be
+    /// running on `game_manager`, so with a custom timeout timeouts are not retried.
+    async fn send_with_timeout(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<&serde_json::Value>,
+        timeout: Option<Duration>,
+    ) -> Result<reqwest::Response, AppError> {
+        let idempotent = method == Method::GET;
+        let mut attempt = 0;
+        let mut reauthenticated = false;
+        loop {
+            let token = self.access_token().await?;
+            let mut request = self
+                .http
+                .request(method.clone(), url.clone())
+                .bearer_auth(token);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            if let Some(timeout) = timeout {
+                request = request.timeout(timeout);
+            }
+            let result = request.send().await;
+            let retryable = match &result {
+                Ok(response)
+                    if response.status() == StatusCode::UNAUTHORIZED && !reauthenticated =>
+                {
+                    reauthenticated = true;
+                    *self.tokens.lock().await = None;
+                    continue;
+                }
+                Ok(response) => idempotent && response.status().is_server_error(),
+                Err(e) => e.is_connect() || (idempotent && e.is_timeout() && timeout.is_none()),
+            };
+            if retryable && attempt < self.max_retries {
+                let backoff = Duration::from_millis(250 * 2_u64.pow(attempt));
+                tracing::debug!("Retrying {method} {url} in {backoff:?}");
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                continue;
+            }
+            return Self::check(result?).await;
+        }
+    }
+
+    async fn check(response: reqwest::Response) -> Result<reqwest::Response, AppError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let (code, message) = match response.json::<ErrorBody>().await {
+            Ok(body) => (body.code, body.error),
+            Err(_) => (
+                ErrorCode::Internal,
+                status.canonical_reason().unwrap_or_default().to_string(),
+            ),
+        };
+        Err(AppError::GameManager(ApiError {
+            status: status.as_u16(),
+            code,
+            message,
+        }))
+    }
+
+    /// The cached access token, renewed with the refresh token or a new credentials exchange when it is
+    /// about to expire.
+    async fn access_token(&self) -> Result<String, AppError> {
+        let mut tokens = self.tokens.lock().await;
+        if let Some(current) = tokens.as_ref() {
+            if current.expires_at > Instant::now() + EXPIRY_MARGIN {
+                return Ok(current.access_token.clone());
+            }
+            match self.refresh(&current.refresh_token).await {
+                Ok(refreshed) => {
+                    let access_token = refreshed.access_token.clone();
+                    *tokens = Some(refreshed);
+                    return Ok(access_token);
+                }
+                Err(e) => tracing::debug!("Refreshing game_manager token failed: {e}"),
+            }
+        }
+        let authorized = self.authorize().await?;
+        let access_token = authorized.access_token.clone();
+        *tokens = Some(authorized);
+        drop(tokens);
+        Ok(access_token)
+    }
+
+    async fn authorize(&self) -> Result<Tokens, AppError> {
+        let (client_id, client_secret) = {
+            let endpoint = self.endpoint.read().unwrap_or_else(PoisonError::into_inner);
+            (endpoint.client_id.clone(), endpoint.client_secret.clone())
+        };
+        let response = self
+            .http
+            .post(self.url("api/authorize")?)
+            .json(&AuthPayload {
+                client_id: &client_id,
+                client_secret: &client_secret,
+            })
+            .send()
+            .await?;
+        Ok(Self::check(response)
+            .await?
+            .json::<AuthBody>()
+            .await?
+            .into())
+    }
+
+    async fn refresh(&self, refresh_token: &str) -> Result<Tokens, AppError> {
+        let response = self
+            .http
+            .post(self.url("api/token/refresh")?)
+            .json(&RefreshPayload { refresh_token })
+            .send()
+            .await?;
+        Ok(Self::check(response)
+            .await?
+            .json::<AuthBody>()
+            .await?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        axum::Router,
+        std::sync::{
+            Arc, Once,
+            atomic::{AtomicUsize, Ordering},
+        },
+        tokio::net::TcpListener,
+    };
+
+    const CLIENT_ID: &str = "discord_bot";
+    const CLIENT_SECRET: &str = "secret";
+    /// Nothing listens here, so connecting fails straight away
+    const UNREACHABLE: &str = "127.0.0.1:1";
+
+    /// Serve `router` on a free local port, returning its base URL
+    async fn serve(router: Router) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("binding a local port failed");
+        let address = listener.local_addr().expect("listener has no address");
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        format!("http://{address}/")
+    }
+
+    /// A `TShock` answering every request with a 500, and how many requests it got
+    async fn failing_tshock() -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let router = Router::new().fallback(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { axum::http::StatusCode::INTERNAL_SERVER_ERROR }
+        });
+        (serve(router).await.trim_end_matches('/').to_owned(), hits)
+    }
+
+    /// A `game_manager` granting the bot `scopes`, whose game servers can't be reached
+    async fn game_manager(tshock_base_url: &str, scopes: &str) -> String {
+        static KEYS: Once = Once::new();
+        KEYS.call_once(|| {
+            // the keys are kept in memory and never due for rotation, so their directory can go
+            let dir = tempfile::TempDir::new().expect("creating a temporary directory failed");
+            let keys = game_manager::keys::KeyStore::load(
+                dir.path().to_path_buf(),
+                Duration::from_hours(24),
+                Duration::from_hours(1),
+                None,
+            )
+            .expect("creating signing keys failed");
+            let _ = game_manager::KEYS.set(keys);
+            let _ =
+                game_manager::auth::REVOCATIONS.set(game_manager::auth::Revocations::in_memory());
+        });
+        let state = game_manager::AppState {
+            minecraft_geyser_address: UNREACHABLE.to_owned(),
+            floodgate_prefix: String::from("."),
+            minecraft_geyser_rcon_address: UNREACHABLE.to_owned(),
+            minecraft_modded_address: UNREACHABLE.to_owned(),
+            minecraft_modded_rcon_address: UNREACHABLE.to_owned(),
+            terraria_address: UNREACHABLE.to_owned(),
+            tshock_base_url: tshock_base_url.to_owned(),
+            issuer: String::from("game_manager"),
+            audience: String::from("discord_bot"),
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: CLIENT_SECRET.to_owned(),
+            client_scopes: scopes.to_owned(),
+            ..game_manager::AppState::default()
+        };
+        serve(game_manager::router(Arc::new(Mutex::new(state)))).await
+    }
+
+    fn client(base_url: &str, client_secret: &str) -> GameManagerClient {
+        GameManagerClient::new(
+            base_url,
+            CLIENT_ID.to_owned(),
+            client_secret.to_owned(),
+            "tests",
+        )
+        .expect("building the client failed")
+        .with_max_retries(2)
+    }
+
+    fn api_error(result: Result<impl std::fmt::Debug, AppError>) -> ApiError {
+        match result {
+            Err(AppError::GameManager(e)) => e,
+            other => panic!("expected a game_manager error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn reauthenticates_when_the_token_is_rejected() {
+        let base_url = game_manager(&format!("http://{UNREACHABLE}"), "servers:read").await;
+        let client = client(&base_url, CLIENT_SECRET);
+        client
+            .status(GameServer::Terraria)
+            .await
+            .expect("first request failed");
+        if let Some(tokens) = client.tokens.lock().await.as_mut() {
+            tokens.access_token = String::from("not a token");
+        }
+        let status = client
+            .status(GameServer::Terraria)
+            .await
+            .expect("request with a rejected token failed");
+        assert!(!status.online);
+        let tokens = client.tokens.lock().await;
+        assert_ne!(
+            tokens.as_ref().map(|t| t.access_token.as_str()),
+            Some("not a token")
+        );
+    }
+
+    #[tokio::test]
+    async fn refreshes_an_expiring_token() {
+        let base_url = game_manager(&format!("http://{UNREACHABLE}"), "servers:read").await;
+        let client = client(&base_url, CLIENT_SECRET);
+        client
+            .status(GameServer::Terraria)
+            .await
+            .expect("first request failed");
+        let mut tokens = client.tokens.lock().await;
+        let current = tokens.as_mut().expect("no tokens after a request");
+        current.expires_at = Instant::now();
+        let refresh_token = current.refresh_token.clone();
+        drop(tokens);
+        client
+            .status(GameServer::Terraria)
+            .await
+            .expect("request after refreshing failed");
+        // the refresh token was used rather than the credentials, so it can't be used again
+        assert!(client.refresh(&refresh_token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn retries_failed_gets_but_not_posts() {
+        let (tshock, hits) = failing_tshock().await;
+        let base_url = game_manager(&tshock, "servers:read servers:broadcast").await;
+        let client = client(&base_url, CLIENT_SECRET);
+
+        let error = api_error(client.status(GameServer::Terraria).await);
+        assert_eq!((error.status, error.code), (500, ErrorCode::Internal));
+        assert_eq!(
+            hits.load(Ordering::SeqCst),
+            3,
+            "a GET is tried once and retried twice"
+        );
+
+        let error = api_error(client.broadcast(GameServer::Terraria, "hello").await);
+        assert_eq!((error.status, error.code), (500, ErrorCode::Internal));
+        assert_eq!(
+            hits.load(Ordering::SeqCst),
+            4,
+            "a POST may have taken effect, so it isn't retried"
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_error_codes() {
+        let base_url = game_manager(&format!("http://{UNREACHABLE}"), "servers:broadcast").await;
+        let client = client(&base_url, CLIENT_SECRET);
+        let cases = [
+            (
+                client.status(GameServer::Terraria).await.map(|_| ()),
+                403,
+                ErrorCode::Forbidden,
+            ),
+            (
+                client.broadcast(GameServer::Terraria, " ").await,
+                400,
+                ErrorCode::InvalidRequest,
+            ),
+            (
+                client.broadcast(GameServer::Terraria, "hello").await,
+                502,
+                ErrorCode::UpstreamUnreachable,
+            ),
+            (
+                self::client(&base_url, "wrong")
+                    .status(GameServer::Terraria)
+                    .await
+                    .map(|_| ()),
+                401,
+                ErrorCode::Unauthorized,
+            ),
+        ];
+        for (result, status, code) in cases {
+            let error = api_error(result);
+            assert_eq!((error.status, error.code), (status, code), "{error}");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Liveness and readiness of the bot's subsystems, served as JSON on `/healthz` and `/readyz`.
+//!
+//! The web server, the Discord gateway and the interval task run as separate Tokio tasks, so the web
+//! server answering says nothing about the others. Each task reports in to [`HEALTH`] instead.
+//!
+//! `/healthz` only looks at the bot's own tasks: it fails when a supervised task is not running, the
+//! gateway is disconnected or the interval task has stopped ticking, which calls for a restart.
+//! `/readyz` also checks the database and `game_manager`, failing when the database is unusable.
+//! `game_manager` being unreachable or polls failing only degrade readiness, since the bot still serves
+//! Discord and the website without them.
+
+use {
+    crate::{
+        error::AppError,
+        game_manager::GAME_MANAGER,
+        queries::ping,
+        state::db_pool,
+        supervisor::{TaskState, TaskStatus},
+    },
+    axum::{Json, http::StatusCode},
+    chrono::{DateTime, Utc},
+    serde::Serialize,
+    std::{
+        collections::BTreeMap,
+        sync::{
+            Mutex, PoisonError,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::{Duration, Instant},
+    },
+};
+
+pub static HEALTH: Health = Health::new();
+
+/// The interval task ticks every few seconds, a tick older than this means it is stuck or gone
+const STALE_AFTER: Duration = Duration::from_mins(5);
+/// How long each readiness check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Health {
+    gateway_connected: AtomicBool,
+    last_tick: Mutex<Option<DateTime<Utc>>>,
+    last_poll: Mutex<Option<DateTime<Utc>>>,
+    tasks: Mutex<BTreeMap<&'static str, TaskStatus>>,
+}
+
+impl Health {
+    const fn new() -> Self {
+        Self {
+            gateway_connected: AtomicBool::new(false),
+            last_tick: Mutex::new(None),
+            last_poll: Mutex::new(None),
+            tasks: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn set_gateway_connected(&self, connected: bool) {
+        self.gateway_connected.store(connected, Ordering::Relaxed);
+    }
+
+    /// Record that the interval task started a tick
+    pub fn record_tick(&self) {
+        *self
+            .last_tick
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Utc::now());
+    }
+
+    /// Record that `game_manager` answered a status poll
+    pub fn record_poll(&self) {
+        *self
+            .last_poll
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Utc::now());
+    }
+
+    /// Record the state of a supervised task
+    pub fn set_task(
+        &self,
+        name: &'static str,
+        state: TaskState,
+        restarts: u32,
+        last_error: Option<String>,
+    ) {
+        self.tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                name,
+                TaskStatus {
+                    state,
+                    restarts,
+                    last_error,
+                    since: Utc::now(),
+                },
+            );
+    }
+
+    fn tasks(&self) -> BTreeMap<&'static str, TaskStatus> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Whether everything the liveness check looks at is fine
+    fn alive(&self, now: DateTime<Utc>) -> bool {
+        self.gateway_connected()
+            && Report::fresh(self.last_tick(), now)
+            && self
+                .tasks()
+                .values()
+                .all(|task| task.state == TaskState::Running)
+    }
+
+    fn gateway_connected(&self) -> bool {
+        self.gateway_connected.load(Ordering::Relaxed)
+    }
+
+    fn last_tick(&self) -> Option<DateTime<Utc>> {
+        *self
+            .last_tick
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn last_poll(&self) -> Option<DateTime<Utc>> {
+        *self
+            .last_poll
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    /// Serving, but something the bot can do without is failing
+    Degraded,
+    Unavailable,
+}
+
+/// The outcome of checking a dependency
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct Check {
+    pub ok: bool,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+impl Check {
+    async fn run<F: Future<Output = Result<(), AppError>>>(check: F) -> Self {
+        let started = Instant::now();
+        let result = tokio::time::timeout(CHECK_TIMEOUT, check)
+            .await
+            .unwrap_or_else(|_| Err(AppError::Other(String::from("timed out"))));
+        Self {
+            ok: result.is_ok(),
+            latency_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct Report {
+    pub status: Status,
+    pub gateway_connected: bool,
+    /// When the interval task last ticked
+    pub last_tick: Option<DateTime<Utc>>,
+    /// When `game_manager` last answered a status poll
+    pub last_poll: Option<DateTime<Utc>>,
+    /// The supervised tasks by name
+    pub tasks: BTreeMap<&'static str, TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<Check>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_manager: Option<Check>,
+}
+
+impl Report {
+    fn fresh(at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        at.is_some_and(|at| (now - at).to_std().unwrap_or_default() < STALE_AFTER)
+    }
+
+    fn respond(self) -> (StatusCode, Json<Self>) {
+        let code = if self.status == Status::Unavailable {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::OK
+        };
+        (code, Json(self))
+    }
+}
+
+/// The `/healthz` route, whether the bot's own tasks are alive
+#[allow(clippy::unused_async)] // axum handlers have to be async
+pub async fn healthz() -> (StatusCode, Json<Report>) {
+    let status = if HEALTH.alive(Utc::now()) {
+        Status::Ok
+    } else {
+        Status::Unavailable
+    };
+    Report {
+        status,
+        gateway_connected: HEALTH.gateway_connected(),
+        last_tick: HEALTH.last_tick(),
+        last_poll: HEALTH.last_poll(),
+        tasks: HEALTH.tasks(),
+        database: None,
+        game_manager: None,
+    }
+    .respond()
+}
+
+/// The `/readyz` route, whether the bot and what it depends on can serve requests
+pub async fn readyz() -> (StatusCode, Json<Report>) {
+    let now = Utc::now();
+    let last_poll = HEALTH.last_poll();
+    let (database, game_manager) = tokio::join!(
+        Check::run(async { ping(db_pool()?).await }),
+        Check::run(async {
+            GAME_MANAGER
+                .get()
+                .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?
+                .ping(CHECK_TIMEOUT)
+                .await
+        }),
+    );
+    let status = if !database.ok || !HEALTH.alive(now) {
+        Status::Unavailable
+    } else if !game_manager.ok || !Report::fresh(last_poll, now) {
+        Status::Degraded
+    } else {
+        Status::Ok
+    };
+    Report {
+        status,
+        gateway_connected: HEALTH.gateway_connected(),
+        last_tick: HEALTH.last_tick(),
+        last_poll,
+        tasks: HEALTH.tasks(),
+        database: Some(database),
+        game_manager: Some(game_manager),
+    }
+    .respond()
+}
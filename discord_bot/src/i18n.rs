@@ -0,0 +1,238 @@
+//! Choosing the language to respond in.
+//!
+//! Texts live in `locales/<locale>.yml`, looked up with [`rust_i18n::t!`]. A key missing from a locale
+//! falls back to English. Announcements and anything else not sent in reply to someone use the locale
+//! set with `LOCALE`. The web frontend picks its own from a cookie or the browser's `Accept-Language`, see
+//! [`crate::components::LanguageSwitcher`].
+
+use rust_i18n::t;
+
+/// The locale the texts are written in first, and the one used when nothing else matches
+pub const FALLBACK: &str = "en";
+
+/// The first of `candidates` the bot has texts for, such as `de` for `de-AT`. Candidates are tags like
+/// `en-US`, as Discord and browsers send them, and are tried in order.
+#[must_use]
+pub fn resolve<'a>(candidates: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    let available = rust_i18n::available_locales!();
+    candidates.into_iter().find_map(|candidate| {
+        let language = candidate.split(['-', '_']).next().unwrap_or(candidate);
+        available
+            .iter()
+            .find(|locale| locale.eq_ignore_ascii_case(candidate))
+            .or_else(|| {
+                available
+                    .iter()
+                    .find(|locale| locale.eq_ignore_ascii_case(language))
+            })
+            .copied()
+    })
+}
+
+/// The locale best matching an `Accept-Language` header such as `de-AT,de;q=0.9,en;q=0.5`
+#[must_use]
+pub fn negotiate(accept_language: &str) -> Option<&'static str> {
+    let mut languages: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+            let quality = parts
+                .find_map(|part| part.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse().ok())?;
+            Some((tag, quality)).filter(|(_, quality)| *quality > 0.0)
+        })
+        .collect();
+    // stable, so equally preferred languages keep the browser's order
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+    resolve(languages.into_iter().map(|(tag, _)| tag))
+}
+
+/// The locale set with `LOCALE`
+#[must_use]
+pub fn default_locale() -> &'static str {
+    resolve([&*rust_i18n::locale()]).unwrap_or(FALLBACK)
+}
+
+/// The key for `count` of something, `<key>.one` or `<key>.other`. That is enough for the languages so
+/// far, which have one singular form.
+#[must_use]
+pub fn plural(key: &str, count: usize) -> String {
+    format!("{key}.{}", if count == 1 { "one" } else { "other" })
+}
+
+/// The key of a command choice by its name, e.g. `choices.force_with_countdown` for `Force with countdown`
+fn choice_key(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("choices.{name}")
+}
+
+/// A command choice such as a restart policy, by its name, in `locale`
+#[must_use]
+pub fn choice(name: &str, locale: &str) -> String {
+    let key = choice_key(name);
+    t!(&key, locale = locale).into_owned()
+}
+
+/// `one, two, and three` in `locale`
+#[must_use]
+pub fn join_list(items: &[String], locale: &str) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [first, last] => t!("list.two", locale = locale, first = first, last = last).into_owned(),
+        [rest @ .., last] => t!(
+            "list.many",
+            locale = locale,
+            rest = rest.join(", "),
+            last = last
+        )
+        .into_owned(),
+    }
+}
+
+/// The locale to reply in: the invoking user's if the bot speaks it, else the guild's, else `LOCALE`
+#[cfg(feature = "server")]
+#[must_use]
+pub fn locale(ctx: crate::state::Context<'_>) -> &'static str {
+    let guild = match ctx {
+        poise::Context::Application(ctx) => ctx.interaction.guild_locale.as_deref(),
+        poise::Context::Prefix(_) => None,
+    };
+    resolve(ctx.locale().into_iter().chain(guild)).unwrap_or_else(default_locale)
+}
+
+/// Discord's code for a locale, which for some languages includes the region
+#[cfg(feature = "server")]
+fn discord_locale(locale: &str) -> &str {
+    match locale {
+        "es" => "es-ES",
+        "pt" => "pt-BR",
+        "sv" => "sv-SE",
+        "zh" => "zh-CN",
+        _ => locale,
+    }
+}
+
+/// Fill in the names and descriptions of `commands`, their parameters and choices from the locale files,
+/// so Discord shows them in each user's language. Call before registering the commands.
+#[cfg(feature = "server")]
+pub fn localize_commands<U, E>(commands: &mut [poise::Command<U, E>]) {
+    for command in commands {
+        localize_command(command, "commands");
+    }
+}
+
+#[cfg(feature = "server")]
+fn localize_command<U, E>(command: &mut poise::Command<U, E>, parent: &str) {
+    let key = format!("{parent}.{}", command.name);
+    let locales: Vec<_> = rust_i18n::available_locales!()
+        .into_iter()
+        .filter(|locale| *locale != FALLBACK)
+        .collect();
+    let text = |key: &str, locale: &str| t!(key, locale = locale).into_owned();
+    command.description = Some(text(&format!("{key}.description"), FALLBACK));
+    for locale in &locales {
+        let code = discord_locale(locale).to_owned();
+        command
+            .name_localizations
+            .insert(code.clone(), text(&format!("{key}.name"), locale));
+        command
+            .description_localizations
+            .insert(code, text(&format!("{key}.description"), locale));
+    }
+    for parameter in &mut command.parameters {
+        let key = format!("{key}.{}", parameter.name);
+        parameter.description = Some(text(&format!("{key}.description"), FALLBACK));
+        for locale in &locales {
+            let code = discord_locale(locale).to_owned();
+            parameter
+                .name_localizations
+                .insert(code.clone(), text(&format!("{key}.name"), locale));
+            parameter
+                .description_localizations
+                .insert(code.clone(), text(&format!("{key}.description"), locale));
+            for choice in &mut parameter.choices {
+                choice
+                    .localizations
+                    .insert(code.clone(), text(&choice_key(&choice.name), locale));
+            }
+        }
+    }
+    for subcommand in &mut command.subcommands {
+        localize_command(subcommand, &key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::BTreeSet};
+
+    /// The keys in `locales/<locale>.yml`, which holds one flat `dotted.key: "text"` per line
+    fn keys(locale: &str) -> BTreeSet<String> {
+        let path = format!("{}/locales/{locale}.yml", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("reading {path} failed: {e}"))
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(':').map(|(key, _)| key.trim().to_owned()))
+            .filter(|key| key != "_version")
+            .collect()
+    }
+
+    #[test]
+    fn locales_have_the_same_keys() {
+        let fallback = keys(FALLBACK);
+        for locale in rust_i18n::available_locales!() {
+            let keys = keys(locale);
+            let missing: Vec<_> = fallback.difference(&keys).collect();
+            let extra: Vec<_> = keys.difference(&fallback).collect();
+            assert!(missing.is_empty(), "{locale} is missing {missing:?}");
+            assert!(
+                extra.is_empty(),
+                "{locale} has keys {FALLBACK} lacks: {extra:?}"
+            );
+        }
+    }
+
+    /// A missing key comes back as `<locale>.<key>`, which Discord rejects as a command name
+    #[cfg(feature = "server")]
+    #[test]
+    fn every_command_is_localized() {
+        fn check<U, E>(command: &poise::Command<U, E>) {
+            let is_key = |text: &str| text.contains("commands.") || text.contains("choices.");
+            let mut texts: Vec<&String> = command.description.iter().collect();
+            texts.extend(command.name_localizations.values());
+            texts.extend(command.description_localizations.values());
+            for parameter in &command.parameters {
+                texts.extend(parameter.description.iter());
+                texts.extend(parameter.name_localizations.values());
+                texts.extend(parameter.description_localizations.values());
+                for choice in &parameter.choices {
+                    texts.extend(choice.localizations.values());
+                }
+            }
+            for text in texts {
+                assert!(
+                    !is_key(text),
+                    "/{} has no text for {text}",
+                    command.qualified_name
+                );
+            }
+            command.subcommands.iter().for_each(check);
+        }
+
+        let mut commands = crate::discord::commands();
+        localize_commands(&mut commands);
+        commands.iter().for_each(check);
+    }
+}
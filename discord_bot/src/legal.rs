@@ -0,0 +1,146 @@
+//! The Terms of Service and Privacy Policy, which Discord requires verified apps to link to.
+//!
+//! Both are Markdown files in `assets/legal`, starting with a front matter block giving the document's
+//! `version` and the date it takes `effective`. Placeholders like `{{bot_name}}` are filled in from the
+//! configuration when a page is rendered, so other deployments don't have to edit them:
+//!
+//! - `{{bot_name}}`: `BOT_NAME`
+//! - `{{contact}}`: `LEGAL_CONTACT`, an email address, URL or any other text
+//! - `{{base_url}}`: `BASE_URL`, for links between the pages
+//!
+//! Bump the version and effective date whenever a document changes meaningfully.
+
+#[cfg(feature = "server")]
+use crate::{error::AppError, state::AppState};
+use {
+    chrono::NaiveDate,
+    rust_i18n::t,
+    serde::{Deserialize, Serialize},
+};
+
+/// Who to contact when `LEGAL_CONTACT` isn't set
+#[cfg(feature = "server")]
+const DEFAULT_CONTACT: &str = "the administrators of the Discord server you use the bot in";
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LegalDocument {
+    TermsOfService,
+    PrivacyPolicy,
+}
+
+/// A document ready to show, with its placeholders filled in
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RenderedDocument {
+    pub version: u32,
+    pub effective: NaiveDate,
+    pub html: String,
+}
+
+impl LegalDocument {
+    /// The document's title in `locale`
+    #[must_use]
+    pub fn title(self, locale: &str) -> String {
+        let key = match self {
+            Self::TermsOfService => "web.nav.terms_of_service",
+            Self::PrivacyPolicy => "web.nav.privacy_policy",
+        };
+        t!(key, locale = locale).into_owned()
+    }
+}
+
+#[cfg(feature = "server")]
+impl LegalDocument {
+    const fn source(self) -> &'static str {
+        match self {
+            Self::TermsOfService => include_str!("../assets/legal/terms_of_service.md"),
+            Self::PrivacyPolicy => include_str!("../assets/legal/privacy_policy.md"),
+        }
+    }
+
+    /// Fill in the document's placeholders from `state` and render it to HTML
+    ///
+    /// # Errors
+    ///
+    /// Fails if the front matter is missing or invalid, or a placeholder is unknown or unclosed.
+    pub fn render(self, state: &AppState) -> Result<RenderedDocument, AppError> {
+        let invalid = |problem: &str| AppError::Other(format!("{self:?} {problem}"));
+        let (front_matter, body) = self
+            .source()
+            .strip_prefix("---\n")
+            .and_then(|rest| rest.split_once("\n---\n"))
+            .ok_or_else(|| invalid("has no front matter"))?;
+        let field = |name: &str| {
+            front_matter
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(key, _)| key.trim() == name)
+                .map(|(_, value)| value.trim())
+                .ok_or_else(|| invalid(&format!("has no `{name}` in its front matter")))
+        };
+        let version = field("version")?
+            .parse()
+            .map_err(|e| invalid(&format!("has an invalid version: {e}")))?;
+        let effective = NaiveDate::parse_from_str(field("effective")?, "%Y-%m-%d")
+            .map_err(|e| invalid(&format!("has an invalid effective date: {e}")))?;
+        let contact = contact(&state.legal_contact);
+        let body = fill(
+            body,
+            &[
+                ("bot_name", state.bot_name.as_str()),
+                ("contact", contact.as_str()),
+                ("base_url", state.base_url.trim_end_matches('/')),
+            ],
+        )
+        .map_err(|placeholder| {
+            invalid(&format!(
+                "has an unknown or unclosed placeholder `{placeholder}`"
+            ))
+        })?;
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(&body));
+        Ok(RenderedDocument {
+            version,
+            effective,
+            html,
+        })
+    }
+}
+
+/// `LEGAL_CONTACT` as Markdown, turning email addresses and URLs into links
+#[cfg(feature = "server")]
+fn contact(contact: &str) -> String {
+    let is_email = contact.contains('@') && !contact.contains([':', ' ', '<', '>']);
+    let is_url = url::Url::parse(contact)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "mailto"));
+    if contact.is_empty() {
+        String::from(DEFAULT_CONTACT)
+    } else if is_email || is_url {
+        format!("<{contact}>")
+    } else {
+        contact.to_owned()
+    }
+}
+
+/// Replace each `{{name}}` in `template` with its value, returning the first placeholder that can't be
+/// filled in
+#[cfg(feature = "server")]
+fn fill(template: &str, values: &[(&str, &str)]) -> Result<String, String> {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            return Err(after.lines().next().unwrap_or_default().to_owned());
+        };
+        let name = after[..end].trim();
+        let (_, value) = values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .ok_or_else(|| name.to_owned())?;
+        filled.push_str(value);
+        rest = &after[end + 2..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
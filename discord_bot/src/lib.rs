@@ -1,15 +1,45 @@
 pub mod app;
+#[cfg(feature = "server")]
+pub mod chat_bridge;
 pub mod components;
 #[cfg(feature = "server")]
+pub mod config;
+#[cfg(feature = "server")]
+pub mod data_requests;
+#[cfg(feature = "server")]
 pub mod discord;
 pub mod error;
 #[cfg(feature = "server")]
+pub mod game_manager;
+#[cfg(feature = "server")]
+pub mod health;
+pub mod i18n;
+pub mod legal;
+#[cfg(feature = "server")]
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod minecraft;
+#[cfg(feature = "server")]
 pub mod models;
 #[cfg(feature = "server")]
+pub mod players;
+pub mod playtime;
+#[cfg(feature = "server")]
+pub mod poller;
+pub mod population;
+#[cfg(feature = "server")]
 pub mod queries;
 #[cfg(feature = "server")]
+pub mod reload;
+#[cfg(feature = "server")]
+pub mod restart;
+#[cfg(feature = "server")]
+pub mod scheduler;
+#[cfg(feature = "server")]
 pub mod schema;
 pub mod state;
+#[cfg(feature = "server")]
+pub mod supervisor;
 pub mod views;
 
-rust_i18n::i18n!();
+rust_i18n::i18n!("locales", fallback = "en");
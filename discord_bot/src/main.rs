@@ -3,16 +3,26 @@ use {
     axum::{
         Router,
         http::{HeaderValue, header},
+        routing::get,
     },
     diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations},
     dioxus::server::{DioxusRouterExt, ServeConfig},
     discord_bot::{
+        chat_bridge::{CHAT_BRIDGE, ChatBridge, RateLimiter, set_chat_bridge},
+        config::Config,
         discord,
         error::AppError,
-        state::{AppState, set_global_state},
+        game_manager::{GameManagerClient, set_game_manager_client},
+        health::{self, HEALTH},
+        i18n, metrics,
+        poller::Poller,
+        reload::{self, set_config},
+        scheduler::Scheduler,
+        state::{AppState, db_pool, set_db_pool, set_global_state},
+        supervisor::Supervisor,
     },
     poise::serenity_prelude as serenity,
-    std::{env::var, sync::Arc},
+    std::sync::Arc,
     tokio::sync::{Mutex, watch},
     tower_http::{
         LatencyUnit, ServiceBuilderExt,
@@ -50,72 +60,160 @@ async fn main() -> Result<(), discord_bot::error::AppError> {
     //     .with(tracing_subscriber::fmt::layer().without_time())
     //     .init();
 
-    let db_url = std::env::var("DATABASE_URL").unwrap();
+    metrics::install()?;
 
-    // set up connection pool
-    let manager = deadpool_diesel::postgres::Manager::new(db_url, deadpool_diesel::Runtime::Tokio1);
-    let pool = deadpool_diesel::postgres::Pool::builder(manager)
-        .build()
-        .unwrap();
+    // main reports errors with their Debug output, so log the list of problems readably first
+    let config = Config::load().inspect_err(|e| tracing::error!("{e}"))?;
 
-    // run the migrations on server startup
-    {
-        let conn = pool.get().await.unwrap();
-        conn.interact(|conn| conn.run_pending_migrations(MIGRATIONS).map(|_| ()))
-            .await
-            .unwrap()
-            .unwrap();
-    }
+    // Announcements, and replies to users whose language the bot doesn't speak, use the configured locale
+    rust_i18n::set_locale(&config.locale);
+
+    set_db_pool(connect_database(config.database_url.clone()).await?);
 
     // Build shared state
-    let shared_state = Arc::new(Mutex::new(AppState {
-        base_url: var("BASE_URL").unwrap_or_default(),
-        discord_client_id: var("DISCORD_CLIENT_ID").unwrap_or_default(),
-        discord_client_secret: var("DISCORD_CLIENT_SECRET").unwrap_or_default(),
-        discord_public_key: var("DISCORD_PUBLIC_KEY").unwrap_or_default(),
-        discord_token: var("DISCORD_TOKEN").unwrap_or_default(),
-        user_agent: format!(
-            "DiscordBot ({}, {})",
-            env!("CARGO_PKG_REPOSITORY"),
-            env!("CARGO_PKG_VERSION")
-        ),
-        ..Default::default()
-    }));
+    let shared_state = Arc::new(Mutex::new(config.state.clone()));
 
     // Set global state for Dioxus server functions
     set_global_state(shared_state.clone());
 
-    // Shutdown signal channel we can use to shut down both tasks gracefully if desired.
+    // Client for the game_manager API on the game host
+    set_game_manager_client(GameManagerClient::new(
+        &config.game_manager_url,
+        config.game_manager_client_id.clone(),
+        config.game_manager_client_secret.clone(),
+        &config.state.user_agent,
+    )?);
+
+    // Channels chat is relayed between Discord and each game
+    set_chat_bridge(ChatBridge::new(
+        &config.chat_channels,
+        RateLimiter::new(
+            config.chat_bridge_rate_limit,
+            config.chat_bridge_rate_window,
+        ),
+    ));
+    if let Some(bridge) = CHAT_BRIDGE.get() {
+        bridge.load_toggles(db_pool()?).await?;
+    }
+
+    // Keep the running configuration to compare reloads against
+    set_config(config.clone());
+
+    // Shutdown signal channel we can use to shut down every task gracefully.
     // We'll send a value when ctrl-c is received.
     let (shutdown_tx, shutdown_rx) = watch::channel::<()>(());
 
+    // Restart tasks that fail, giving up on the whole process once too many fail too quickly
+    let (supervisor, mut fatal_rx) = Supervisor::new(
+        shutdown_rx,
+        config.supervisor_crash_budget,
+        config.supervisor_crash_window,
+    );
+
     // ------------- Interval -------------
-    let shutdown_rx_for_interval = shutdown_rx.clone();
-    let interval_handle = tokio::spawn(async move {
-        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
-        let mut shutdown_rx = shutdown_rx_for_interval.clone();
-        // Start the interval with graceful shutdown
-        tokio::select! {
-            _ = async {
-                loop {
-                    interval.tick().await;
-                    tracing::debug!("Tracking Tick");
-                    // TODO: call game_manager to get the current game state
+    let http = Arc::new(serenity::Http::new(&config.state.discord_token));
+    let interval_handle = supervisor.spawn("interval", move |mut shutdown_rx| {
+        // a restarted task starts over, as it would after restarting the bot
+        let mut poller = Poller::new(http.clone());
+        let mut scheduler = Scheduler::new(http.clone());
+        async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+            // Start the interval with graceful shutdown
+            tokio::select! {
+                _ = async {
+                    loop {
+                        interval.tick().await;
+                        tracing::debug!("Tracking Tick");
+                        HEALTH.record_tick();
+                        if let Err(e) = poller.tick().await {
+                            tracing::error!("Polling game_manager failed: {e}");
+                        }
+                        if let Err(e) = scheduler.tick().await {
+                            tracing::error!("Running restart schedules failed: {e}");
+                        }
+                    }
+                } => {}
+                _ = shutdown_rx.changed() => {
+                    tracing::info!("Interval task received shutdown signal");
                 }
-            } => {
-                tracing::error!("Interval task exited first");
-            }
-            _ = async {
-                let _ = shutdown_rx.changed().await;
-            } => {
-                tracing::info!("Interval task received shutdown signal");
             }
+            Ok(())
         }
     });
 
     // ------------- Axum -------------
-    let app_state_for_axum = shared_state.clone();
+    let router = router(shared_state.clone());
+
+    let address = config.address;
+    // Serve the Dioxus fullstack app, binding again if the server has to be restarted
+    let axum_handle = supervisor.spawn("axum", move |mut shutdown_rx| {
+        let router = router.clone();
+        async move {
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            tracing::trace!("Listening on {address}");
+            axum::serve(listener, router)
+                .with_graceful_shutdown(async move {
+                    // Wait for the shutdown notification
+                    let _ = shutdown_rx.changed().await;
+                })
+                .await?;
+            Ok(())
+        }
+    });
+
+    // ------------- Poise -------------
+    let poise_handle = supervisor.spawn("poise", move |shutdown_rx| {
+        run_discord(shared_state.clone(), shutdown_rx)
+    });
+
+    // ------------- Config -------------
+    // Reload the configuration when its file changes or on SIGHUP
+    let config_handle = supervisor.spawn("config", reload::watch);
+
+    // ------------- Shutdown Signal -------------
+    // Wait for Ctrl+C or terminate signal, or for the supervisor to give up, and then trigger graceful shutdown
+    let fatal = tokio::select! {
+        () = shutdown_signal() => None,
+        reason = fatal_rx.recv() => reason,
+    };
+    match &fatal {
+        None => tracing::info!("Shutdown signal received, shutting down..."),
+        Some(reason) => tracing::error!("Shutting down after repeated failures: {reason}"),
+    }
+    let _ = shutdown_tx.send(());
+
+    // Wait for all tasks to exit
+    let _ = axum_handle.await;
+    let _ = poise_handle.await;
+    let _ = config_handle.await;
+    let _ = interval_handle.await;
+
+    tracing::info!("Shutdown complete");
+    // exit non-zero so systemd restarts the bot
+    fatal.map_or(Ok(()), |reason| Err(AppError::Other(reason)))
+}
+
+/// Set up the connection pool and run the migrations on server startup
+#[cfg(feature = "server")]
+async fn connect_database(
+    database_url: String,
+) -> Result<deadpool_diesel::postgres::Pool, AppError> {
+    let manager =
+        deadpool_diesel::postgres::Manager::new(database_url, deadpool_diesel::Runtime::Tokio1);
+    let pool = deadpool_diesel::postgres::Pool::builder(manager)
+        .build()
+        .map_err(|e| AppError::Pool(e.to_string()))?;
+    let conn = pool.get().await?;
+    conn.interact(|conn| conn.run_pending_migrations(MIGRATIONS).map(|_| ()))
+        .await?
+        .map_err(|e| AppError::Other(format!("failed to run migrations: {e}")))?;
+    drop(conn);
+    Ok(pool)
+}
 
+/// The web app and the health and metrics routes, behind the logging, timeout and compression layers
+#[cfg(feature = "server")]
+fn router(shared_state: Arc<Mutex<AppState>>) -> Router {
     let sensitive_headers: std::sync::Arc<[_]> = vec![header::AUTHORIZATION, header::COOKIE].into();
     // Build our middleware stack
     let middleware = tower::ServiceBuilder::new()
@@ -125,7 +223,7 @@ async fn main() -> Result<(), discord_bot::error::AppError> {
         .layer(
             TraceLayer::new_for_http()
                 .on_body_chunk(|chunk: &axum::body::Bytes, latency: std::time::Duration, _: &tracing::Span| {
-                    tracing::trace!(size_bytes = chunk.len(), latency = ?latency, "sending body chunk")
+                    tracing::trace!(size_bytes = chunk.len(), latency = ?latency, "sending body chunk");
                 })
                 .make_span_with(DefaultMakeSpan::new().include_headers(true))
                 .on_response(DefaultOnResponse::new().include_headers(true).latency_unit(LatencyUnit::Micros)),
@@ -142,110 +240,97 @@ async fn main() -> Result<(), discord_bot::error::AppError> {
         );
 
     // we want a deep integration of axum, dioxus, and state management, so we need to reimplement the dioxus axum wrapper, dioxus::server::router
-    let router = Router::new()
+    Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/metrics", get(metrics::render))
+        .route("/readyz", get(health::readyz))
         .layer(middleware)
-        .with_state(app_state_for_axum)
-        .serve_dioxus_application(ServeConfig::new(), discord_bot::app::App);
-
-    let address = dioxus_cli_config::fullstack_address_or_localhost();
-    let listener = tokio::net::TcpListener::bind(address).await?;
-    let mut shutdown_rx_for_axum = shutdown_rx.clone();
-    let server = axum::serve(listener, router).with_graceful_shutdown(async move {
-        // Wait for the shutdown notification
-        let _ = shutdown_rx_for_axum.changed().await;
-    });
+        .with_state(shared_state)
+        .serve_dioxus_application(ServeConfig::new(), discord_bot::app::App)
+        // Added last so the Dioxus routes are measured too
+        .layer(axum::middleware::from_fn(metrics::track_http))
+}
 
-    // Spawn the Dioxus fullstack server as a background task
-    let axum_handle = tokio::spawn(async move {
-        tracing::trace!("Listening on {address}");
-        if let Err(e) = server.await {
-            tracing::error!("Dioxus server error: {}", e);
-        }
-    });
+/// Connect to Discord and handle events until shutdown. Fails if the client cannot be built or the
+/// connection is lost for good.
+#[cfg(feature = "server")]
+async fn run_discord(
+    shared_state: Arc<Mutex<AppState>>,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Result<(), AppError> {
+    let token = shared_state.lock().await.discord_token.clone();
+    let intents = serenity::GatewayIntents::non_privileged()
+        | serenity::GatewayIntents::GUILD_MEMBERS
+        | serenity::GatewayIntents::MESSAGE_CONTENT;
 
-    // ------------- Poise -------------
-    let shared_state_for_poise = shared_state.clone();
-    let shutdown_rx_for_poise = shutdown_rx.clone();
-    let poise_task = tokio::spawn(async move {
-        let token = std::env::var("DISCORD_TOKEN").expect("Expected DISCORD_TOKEN in environment");
-        let intents = serenity::GatewayIntents::non_privileged()
-            | serenity::GatewayIntents::GUILD_MEMBERS
-            | serenity::GatewayIntents::MESSAGE_CONTENT;
-
-        let shared_state_for_setup = shared_state_for_poise.clone();
-        let framework = poise::Framework::builder()
-            .options(poise::FrameworkOptions {
-                commands: vec![
-                    discord::minecraft_geyser(),
-                    discord::minecraft_modded(),
-                    discord::terraria(),
-                    discord::game_roles(),
-                    discord::register_self_assignable_role(),
-                    discord::deregister_self_assignable_role(),
-                ],
-                event_handler: |ctx, event, framework, data| {
-                    Box::pin(discord::event_handler(ctx, event, framework, data))
-                },
-                ..Default::default()
-            })
-            .setup(move |ctx, ready, framework| {
+    let shared_state_for_setup = shared_state.clone();
+    let mut commands = discord::commands();
+    // Command names and descriptions are registered in every locale, Discord picks the user's
+    i18n::localize_commands(&mut commands);
+    let framework = poise::Framework::builder()
+        .options(poise::FrameworkOptions {
+            commands,
+            event_handler: |ctx, event, framework, data| {
+                Box::pin(discord::event_handler(ctx, event, framework, data))
+            },
+            pre_command: |ctx| {
+                Box::pin(async move { metrics::command_invoked(&ctx.command().qualified_name) })
+            },
+            on_error: |error| {
                 Box::pin(async move {
-                    tracing::info!("Logged in as {}", ready.user.name);
+                    if let Some(ctx) = error.ctx() {
+                        metrics::command_failed(&ctx.command().qualified_name);
+                    }
+                    poise::builtins::on_error(error)
+                        .await
+                        .unwrap_or_else(|e| tracing::error!("Failed to report error: {e}"));
+                })
+            },
+            ..Default::default()
+        })
+        .setup(move |ctx, ready, framework| {
+            Box::pin(async move {
+                tracing::info!("Logged in as {}", ready.user.name);
 
-                    // Register slash commands
-                    poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                // Register slash commands
+                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
 
-                    // set the activity of the bot
-                    ctx.set_activity(Some(serenity::ActivityData::custom("Under Development")));
+                // set the activity of the bot
+                ctx.set_activity(Some(serenity::ActivityData::custom("Under Development")));
 
-                    Ok(shared_state_for_setup)
-                })
+                Ok(shared_state_for_setup)
             })
-            .build();
+        })
+        .build();
 
-        tracing::info!("Starting Poise bot with shared state");
+    tracing::info!("Starting Poise bot with shared state");
 
-        // Create Serenity client with the framework
-        let mut client = serenity::ClientBuilder::new(token, intents)
-            .framework(framework)
-            .await?;
+    // Create Serenity client with the framework
+    let mut client = serenity::ClientBuilder::new(token, intents)
+        .framework(framework)
+        .await?;
+    // aborted on return, as a restarted client brings its own
+    let latency = tokio::spawn(metrics::sample_gateway_latency(
+        client.shard_manager.clone(),
+    ));
 
-        // Start the framework with graceful shutdown
-        let mut shutdown_rx = shutdown_rx_for_poise.clone();
-        tokio::select! {
-            result = client.start() => {
-                if let Err(e) = result {
-                    tracing::error!("Serenity client error: {}", e);
-                }
-            }
-            _ = async {
-                let _ = shutdown_rx.changed().await;
-            } => {
-                tracing::info!("Poise bot received shutdown signal");
-            }
+    // Start the framework with graceful shutdown
+    let result = tokio::select! {
+        result = client.start() => result.map_err(AppError::from),
+        _ = shutdown_rx.changed() => {
+            tracing::info!("Poise bot received shutdown signal");
+            Ok(())
         }
+    };
 
-        tracing::info!("Poise bot exiting");
-        Ok::<(), AppError>(())
-    });
-
-    // ------------- Shutdown Signal -------------
-    // Wait for Ctrl+C or terminate signal and then trigger graceful shutdown
-    shutdown_signal().await;
-    tracing::info!("Shutdown signal received, shutting down...");
-    let _ = shutdown_tx.send(());
-
-    // Wait for all tasks to exit
-    let _ = axum_handle.await;
-    let _ = poise_task.await;
-    let _ = interval_handle.await;
-
-    tracing::info!("Shutdown complete");
-    Ok(())
+    latency.abort();
+    HEALTH.set_gateway_connected(false);
+    tracing::info!("Poise bot exiting");
+    result
 }
 
 #[cfg(feature = "server")]
-pub async fn shutdown_signal() {
+async fn shutdown_signal() {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -260,7 +345,7 @@ pub async fn shutdown_signal() {
     };
 
     tokio::select! {
-        _ = ctrl_c => {},
-        _ = terminate => {},
+        () = ctrl_c => {},
+        () = terminate => {},
     }
 }
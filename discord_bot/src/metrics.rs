@@ -0,0 +1,154 @@
+//! Prometheus metrics, served as text on `/metrics`.
+//!
+//! Metrics are recorded through the `metrics` facade anywhere in the bot and rendered by the recorder
+//! installed with [`install`]. Labels are kept to values with a small, fixed set, such as route templates
+//! rather than request paths, so scrapes stay small.
+
+use {
+    crate::error::AppError,
+    axum::{
+        extract::{MatchedPath, Request},
+        http::header,
+        middleware::Next,
+        response::{IntoResponse, Response},
+    },
+    metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram},
+    metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle},
+    poise::serenity_prelude as serenity,
+    std::{
+        sync::{Arc, OnceLock},
+        time::{Duration, Instant},
+    },
+};
+
+static METRICS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Histogram buckets for request latencies, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// How often histograms are compacted and the gateway latency is sampled
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Install the Prometheus recorder and describe every metric the bot records. Must be called once,
+/// inside the Tokio runtime, before anything is recorded.
+///
+/// # Errors
+///
+/// Fails if a recorder is already installed.
+pub fn install() -> Result<(), AppError> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix(String::from("_duration_seconds")),
+            &LATENCY_BUCKETS,
+        )
+        .and_then(PrometheusBuilder::install_recorder)
+        .map_err(|e| AppError::Other(format!("failed to install metrics recorder: {e}")))?;
+    describe_counter!(
+        "http_requests_total",
+        "HTTP requests by method, route and status"
+    );
+    describe_histogram!(
+        "http_request_duration_seconds",
+        metrics::Unit::Seconds,
+        "HTTP request latency by method and route"
+    );
+    describe_counter!(
+        "discord_commands_total",
+        "Slash command invocations by command"
+    );
+    describe_counter!(
+        "discord_command_errors_total",
+        "Slash commands that failed, by command"
+    );
+    describe_gauge!(
+        "discord_gateway_latency_seconds",
+        metrics::Unit::Seconds,
+        "Latency of the last gateway heartbeat by shard"
+    );
+    describe_gauge!(
+        "game_server_up",
+        "Whether a game server answered its last status query"
+    );
+    describe_gauge!("game_server_players", "Players online on a game server");
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+    let _ = METRICS.set(handle);
+    Ok(())
+}
+
+/// The `/metrics` route
+#[allow(clippy::unused_async)] // axum handlers have to be async
+pub async fn render() -> Response {
+    METRICS.get().map_or_else(
+        || {
+            (
+                axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                "metrics are not enabled",
+            )
+                .into_response()
+        },
+        |handle| {
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                handle.render(),
+            )
+                .into_response()
+        },
+    )
+}
+
+/// Middleware counting requests and recording their latency. Requests that match no route, which
+/// includes every page rendered by Dioxus, share one label so arbitrary paths cannot grow the output.
+pub async fn track_http(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| String::from("other"), |path| path.as_str().to_owned());
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let status = response.status().as_u16().to_string();
+    histogram!("http_request_duration_seconds", "method" => method.clone(), "route" => route.clone())
+        .record(started.elapsed().as_secs_f64());
+    counter!("http_requests_total", "method" => method, "route" => route, "status" => status)
+        .increment(1);
+    response
+}
+
+/// Count a slash command invocation, for `FrameworkOptions::pre_command`
+pub fn command_invoked(command: &str) {
+    counter!("discord_commands_total", "command" => command.to_owned()).increment(1);
+}
+
+/// Count a failed slash command, for `FrameworkOptions::on_error`
+pub fn command_failed(command: &str) {
+    counter!("discord_command_errors_total", "command" => command.to_owned()).increment(1);
+}
+
+/// Record whether a game server is up and how many players it has
+#[allow(clippy::cast_precision_loss)]
+pub fn record_server(server: &str, online: bool, players: usize) {
+    gauge!("game_server_up", "server" => server.to_owned()).set(if online { 1.0 } else { 0.0 });
+    gauge!("game_server_players", "server" => server.to_owned()).set(players as f64);
+}
+
+/// Sample the heartbeat latency of every shard, for spawning alongside the Serenity client
+pub async fn sample_gateway_latency(shard_manager: Arc<serenity::ShardManager>) {
+    let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        for (id, runner) in shard_manager.runners.lock().await.iter() {
+            if let Some(latency) = runner.latency {
+                gauge!("discord_gateway_latency_seconds", "shard" => id.to_string())
+                    .set(latency.as_secs_f64());
+            }
+        }
+    }
+}
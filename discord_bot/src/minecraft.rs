@@ -0,0 +1,473 @@
+//! Linking Discord accounts to Minecraft accounts and keeping the server whitelists in sync.
+
+use {
+    crate::{
+        error::AppError,
+        game_manager::{GAME_MANAGER, GameServer, Platform},
+        models::{MinecraftLink, NewMinecraftLink},
+        queries::{
+            create_minecraft_link, delete_minecraft_link, get_minecraft_link,
+            get_minecraft_link_by_uuid, set_minecraft_link_status,
+        },
+    },
+    serde::Deserialize,
+    std::{sync::LazyLock, time::Duration},
+    uuid::Uuid,
+};
+
+/// How long the Mojang and `GeyserMC` APIs may take to answer
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client for the profile lookups, shared so its connections are reused
+pub static LOOKUP_HTTP: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    // building only fails without a TLS backend, which the default client would lack just the same
+    reqwest::Client::builder()
+        .timeout(LOOKUP_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+
+/// The Minecraft servers a linked account is whitelisted on. Bedrock players can only reach the geyser
+/// server, and are whitelisted through Floodgate since they have no Java profile.
+#[must_use]
+pub const fn whitelisted_servers(platform: Platform) -> &'static [(GameServer, &'static str)] {
+    match platform {
+        Platform::Java => &[
+            (GameServer::MinecraftGeyser, "whitelist"),
+            (GameServer::MinecraftModded, "whitelist"),
+        ],
+        Platform::Bedrock => &[(GameServer::MinecraftGeyser, "fwhitelist")],
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl std::fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinecraftProfile {
+    /// For Bedrock players the UUID Floodgate assigns, derived from the XUID
+    pub uuid: Uuid,
+    pub name: String,
+    pub platform: Platform,
+}
+
+/// Resolves a Minecraft username to its profile. Implemented against the Mojang and `GeyserMC` APIs in
+/// production and replaceable with a stub in tests.
+pub trait ProfileLookup: Sync {
+    fn lookup(
+        &self,
+        username: &str,
+    ) -> impl Future<Output = Result<Option<MinecraftProfile>, AppError>> + Send;
+}
+
+/// Looks up Java edition profiles with the Mojang API
+///
+/// ref: <https://minecraft.wiki/w/Mojang_API#Query_player's_UUID>
+pub struct MojangLookup {
+    pub http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct MojangProfile {
+    id: String,
+    name: String,
+}
+
+impl ProfileLookup for MojangLookup {
+    async fn lookup(&self, username: &str) -> Result<Option<MinecraftProfile>, AppError> {
+        let response = self
+            .http
+            .get(format!(
+                "https://api.mojang.com/users/profiles/minecraft/{username}"
+            ))
+            .send()
+            .await?;
+        if matches!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::NO_CONTENT
+        ) {
+            return Ok(None);
+        }
+        let profile = response.error_for_status()?.json::<MojangProfile>().await?;
+        let uuid = Uuid::parse_str(&profile.id)
+            .map_err(|e| AppError::Other(format!("Mojang returned an invalid uuid: {e}")))?;
+        Ok(Some(MinecraftProfile {
+            uuid,
+            name: profile.name,
+            platform: Platform::Java,
+        }))
+    }
+}
+
+/// Looks up Bedrock gamertags with the `GeyserMC` global API
+///
+/// ref: <https://api.geysermc.org/docs>
+pub struct GeyserLookup {
+    pub http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct GeyserXuid {
+    xuid: u64,
+}
+
+impl ProfileLookup for GeyserLookup {
+    async fn lookup(&self, gamertag: &str) -> Result<Option<MinecraftProfile>, AppError> {
+        let response = self
+            .http
+            .get(format!("https://api.geysermc.org/v2/xbox/xuid/{gamertag}"))
+            .send()
+            .await?;
+        if matches!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::BAD_REQUEST
+        ) {
+            return Ok(None);
+        }
+        let GeyserXuid { xuid } = response.error_for_status()?.json().await?;
+        Ok(Some(MinecraftProfile {
+            // Floodgate's UUID for a Bedrock player has the XUID as its low 64 bits
+            uuid: Uuid::from_u64_pair(0, xuid),
+            name: gamertag.to_owned(),
+            platform: Platform::Bedrock,
+        }))
+    }
+}
+
+/// Java usernames are 3 to 16 characters of letters, digits and underscores. Xbox gamertags are up to
+/// 16 characters of letters, digits and spaces.
+#[must_use]
+pub fn is_valid_username(username: &str, platform: Platform) -> bool {
+    match platform {
+        Platform::Java => {
+            (3..=16).contains(&username.len())
+                && username
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        Platform::Bedrock => {
+            (1..=16).contains(&username.len())
+                && username
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == ' ')
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LinkOutcome {
+    /// A link request was created and is waiting for an admin
+    Requested(MinecraftProfile),
+    InvalidUsername,
+    UnknownUsername,
+    /// The Discord user already has a link, in any state
+    AlreadyLinked(String, String),
+    /// The Minecraft account is linked to someone else
+    TakenBy(String),
+}
+
+/// Record a pending link between a Discord user and a Minecraft account
+///
+/// # Errors
+///
+/// Fails if the lookup or the database fails.
+pub async fn request_link(
+    lookup: &impl ProfileLookup,
+    pool: deadpool_diesel::postgres::Pool,
+    guild_id: String,
+    discord_user_id: String,
+    username: &str,
+    platform: Platform,
+) -> Result<LinkOutcome, AppError> {
+    if !is_valid_username(username, platform) {
+        return Ok(LinkOutcome::InvalidUsername);
+    }
+    if let Some(existing) =
+        get_minecraft_link(pool.clone(), guild_id.clone(), discord_user_id.clone()).await?
+    {
+        return Ok(LinkOutcome::AlreadyLinked(
+            existing.minecraft_name,
+            existing.status,
+        ));
+    }
+    let Some(profile) = lookup.lookup(username).await? else {
+        return Ok(LinkOutcome::UnknownUsername);
+    };
+    if let Some(existing) = get_minecraft_link_by_uuid(pool.clone(), profile.uuid).await? {
+        return Ok(LinkOutcome::TakenBy(existing.discord_user_id));
+    }
+    create_minecraft_link(
+        pool,
+        NewMinecraftLink {
+            guild_id,
+            discord_user_id,
+            minecraft_uuid: profile.uuid,
+            minecraft_name: profile.name.clone(),
+            status: LinkStatus::Pending.to_string(),
+            platform: profile.platform.to_string(),
+        },
+    )
+    .await?;
+    Ok(LinkOutcome::Requested(profile))
+}
+
+impl MinecraftLink {
+    #[must_use]
+    pub fn platform(&self) -> Platform {
+        if self.platform == Platform::Bedrock.to_string() {
+            Platform::Bedrock
+        } else {
+            Platform::Java
+        }
+    }
+}
+
+/// A player name as an RCON argument. Gamertags may contain spaces, which would otherwise split them
+/// into several arguments, but never quotes.
+fn rcon_name(name: &str) -> String {
+    if name.contains(' ') {
+        format!("\"{name}\"")
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Add or remove a linked player on every server they are whitelisted on, returning the servers it
+/// failed on
+async fn whitelist(action: &str, link: &MinecraftLink) -> Result<Vec<GameServer>, AppError> {
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+    let mut failed = vec![];
+    for &(server, command) in whitelisted_servers(link.platform()) {
+        let command = format!("{command} {action} {}", rcon_name(&link.minecraft_name));
+        if let Err(e) = client.rcon(server, &command).await {
+            tracing::warn!("{command} on {server} failed: {e}");
+            failed.push(server);
+        }
+    }
+    Ok(failed)
+}
+
+/// Approve a pending link and whitelist the player, returning the servers whitelisting failed on
+///
+/// # Errors
+///
+/// Fails if the database fails or `game_manager` is not configured.
+pub async fn approve_link(
+    pool: deadpool_diesel::postgres::Pool,
+    link: &MinecraftLink,
+    decided_by: String,
+) -> Result<Vec<GameServer>, AppError> {
+    set_minecraft_link_status(pool, link.id, LinkStatus::Approved.to_string(), decided_by).await?;
+    whitelist("add", link).await
+}
+
+/// Deny a pending link. The row is kept so the same request is not made again.
+///
+/// # Errors
+///
+/// Fails if the database fails.
+pub async fn deny_link(
+    pool: deadpool_diesel::postgres::Pool,
+    link: &MinecraftLink,
+    decided_by: String,
+) -> Result<(), AppError> {
+    set_minecraft_link_status(pool, link.id, LinkStatus::Denied.to_string(), decided_by).await?;
+    Ok(())
+}
+
+/// Remove a link, taking the player off the whitelists if it had been approved. Returns the servers
+/// removal failed on.
+///
+/// # Errors
+///
+/// Fails if the database fails or `game_manager` is not configured.
+pub async fn unlink(
+    pool: deadpool_diesel::postgres::Pool,
+    link: &MinecraftLink,
+) -> Result<Vec<GameServer>, AppError> {
+    delete_minecraft_link(pool, link.id).await?;
+    if link.status == LinkStatus::Approved.to_string() {
+        whitelist("remove", link).await
+    } else {
+        Ok(vec![])
+    }
+}
+
+/// The link tests need a PostgreSQL database to record links in, run them with
+/// `DATABASE_URL=postgres://… cargo test --features server -- --ignored`
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        deadpool_diesel::postgres::{Manager, Pool},
+        diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations},
+        tokio::sync::OnceCell,
+    };
+
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/");
+
+    /// Answers from a fixed list of profiles instead of asking Mojang or `GeyserMC`
+    struct StubLookup(Vec<MinecraftProfile>);
+
+    impl ProfileLookup for StubLookup {
+        fn lookup(
+            &self,
+            username: &str,
+        ) -> impl Future<Output = Result<Option<MinecraftProfile>, AppError>> + Send {
+            std::future::ready(Ok(self
+                .0
+                .iter()
+                .find(|profile| profile.name.eq_ignore_ascii_case(username))
+                .cloned()))
+        }
+    }
+
+    async fn pool() -> Pool {
+        static MIGRATED: OnceCell<()> = OnceCell::const_new();
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let pool = Pool::builder(Manager::new(url, deadpool_diesel::Runtime::Tokio1))
+            .build()
+            .expect("building the pool failed");
+        MIGRATED
+            .get_or_init(|| async {
+                let conn = pool.get().await.expect("connecting failed");
+                conn.interact(|conn| conn.run_pending_migrations(MIGRATIONS).map(|_| ()))
+                    .await
+                    .expect("running the migrations panicked")
+                    .expect("running the migrations failed");
+            })
+            .await;
+        pool
+    }
+
+    /// A guild or user id no other test uses
+    fn unique_id() -> String {
+        Uuid::new_v4().as_u64_pair().0.to_string()
+    }
+
+    fn java_profile(name: &str) -> MinecraftProfile {
+        MinecraftProfile {
+            uuid: Uuid::new_v4(),
+            name: name.to_owned(),
+            platform: Platform::Java,
+        }
+    }
+
+    #[test]
+    fn names_with_spaces_are_quoted() {
+        assert_eq!(rcon_name("Steve_1"), "Steve_1");
+        assert_eq!(rcon_name("Steve Plays"), "\"Steve Plays\"");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a PostgreSQL database in DATABASE_URL"]
+    async fn unknown_username_is_not_linked() {
+        let (pool, guild, user) = (pool().await, unique_id(), unique_id());
+        let lookup = StubLookup(vec![]);
+        let outcome = request_link(
+            &lookup,
+            pool.clone(),
+            guild.clone(),
+            user.clone(),
+            "Nobody",
+            Platform::Java,
+        )
+        .await
+        .expect("requesting the link failed");
+        assert_eq!(outcome, LinkOutcome::UnknownUsername);
+        let link = get_minecraft_link(pool, guild, user)
+            .await
+            .expect("query failed");
+        assert!(link.is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a PostgreSQL database in DATABASE_URL"]
+    async fn account_linked_to_someone_else_is_taken() {
+        let (pool, guild) = (pool().await, unique_id());
+        let (first, second) = (unique_id(), unique_id());
+        let profile = java_profile("Taken_Name");
+        let lookup = StubLookup(vec![profile.clone()]);
+        let outcome = request_link(
+            &lookup,
+            pool.clone(),
+            guild.clone(),
+            first.clone(),
+            "Taken_Name",
+            Platform::Java,
+        )
+        .await
+        .expect("requesting the first link failed");
+        assert_eq!(outcome, LinkOutcome::Requested(profile.clone()));
+        // usernames are case-insensitive, the UUID is what matters
+        let outcome = request_link(
+            &lookup,
+            pool.clone(),
+            guild.clone(),
+            second.clone(),
+            "taken_name",
+            Platform::Java,
+        )
+        .await
+        .expect("requesting the second link failed");
+        assert_eq!(outcome, LinkOutcome::TakenBy(first.clone()));
+        let link = get_minecraft_link(pool.clone(), guild.clone(), second)
+            .await
+            .expect("query failed");
+        assert!(link.is_none());
+        if let Some(link) = get_minecraft_link(pool.clone(), guild, first)
+            .await
+            .expect("query failed")
+        {
+            delete_minecraft_link(pool, link.id)
+                .await
+                .expect("cleaning up failed");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a PostgreSQL database in DATABASE_URL"]
+    async fn bedrock_gamertag_is_linked_by_its_floodgate_uuid() {
+        let (pool, guild, user) = (pool().await, unique_id(), unique_id());
+        let xuid = Uuid::new_v4().as_u64_pair().1;
+        let profile = MinecraftProfile {
+            uuid: Uuid::from_u64_pair(0, xuid),
+            name: String::from("Steve Plays"),
+            platform: Platform::Bedrock,
+        };
+        let lookup = StubLookup(vec![profile.clone()]);
+        let outcome = request_link(
+            &lookup,
+            pool.clone(),
+            guild.clone(),
+            user.clone(),
+            "Steve Plays",
+            Platform::Bedrock,
+        )
+        .await
+        .expect("requesting the link failed");
+        assert_eq!(outcome, LinkOutcome::Requested(profile.clone()));
+        let link = get_minecraft_link(pool.clone(), guild, user)
+            .await
+            .expect("query failed")
+            .expect("no link was recorded");
+        assert_eq!(link.minecraft_uuid, profile.uuid);
+        assert_eq!(link.platform(), Platform::Bedrock);
+        assert_eq!(link.status, LinkStatus::Pending.to_string());
+        delete_minecraft_link(pool, link.id)
+            .await
+            .expect("cleaning up failed");
+    }
+}
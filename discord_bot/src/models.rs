@@ -1,4 +1,8 @@
-use crate::schema::{reaction_messages, self_assignable_roles};
+use crate::schema::{
+    chat_bridge_channels, maintenance_windows, minecraft_links, play_sessions, population_samples,
+    reaction_messages, restart_schedules, self_assignable_roles,
+};
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use uuid::Uuid;
 
@@ -33,3 +37,131 @@ pub struct NewReactionMessage {
     pub message_id: String,
     pub message_type: String,
 }
+
+#[derive(HasQuery, Clone, Debug)]
+pub struct MinecraftLink {
+    pub id: Uuid,
+    pub guild_id: String,
+    pub discord_user_id: String,
+    pub minecraft_uuid: Uuid,
+    pub minecraft_name: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub decided_by: Option<String>,
+    pub platform: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = minecraft_links)]
+pub struct NewMinecraftLink {
+    pub guild_id: String,
+    pub discord_user_id: String,
+    pub minecraft_uuid: Uuid,
+    pub minecraft_name: String,
+    pub status: String,
+    pub platform: String,
+}
+
+#[derive(HasQuery, Clone, Debug)]
+pub struct ChatBridgeChannel {
+    pub channel_id: String,
+    pub enabled: bool,
+    pub updated_by: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = chat_bridge_channels)]
+pub struct NewChatBridgeChannel {
+    pub channel_id: String,
+    pub enabled: bool,
+    pub updated_by: String,
+}
+
+#[derive(HasQuery, Clone, Debug)]
+pub struct PlaySession {
+    pub id: Uuid,
+    pub server: String,
+    /// The player's UUID where the server reports one, otherwise their name
+    pub player_id: String,
+    pub player_name: String,
+    pub platform: Option<String>,
+    pub joined_at: DateTime<Utc>,
+    pub left_at: Option<DateTime<Utc>>,
+    /// The last poll the player was seen online in, where a session left open by a crash ends
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = play_sessions)]
+pub struct NewPlaySession {
+    pub server: String,
+    pub player_id: String,
+    pub player_name: String,
+    pub platform: Option<String>,
+    pub joined_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[derive(HasQuery, Clone, Debug)]
+pub struct PopulationSample {
+    pub id: i64,
+    pub server: String,
+    pub sampled_at: DateTime<Utc>,
+    pub online: bool,
+    pub players: i32,
+    pub max_players: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = population_samples)]
+pub struct NewPopulationSample {
+    pub server: String,
+    pub online: bool,
+    pub players: i32,
+    pub max_players: Option<i32>,
+}
+
+#[derive(HasQuery, Clone, Debug)]
+pub struct RestartSchedule {
+    pub id: Uuid,
+    pub server: String,
+    /// Five field cron expression, evaluated in UTC
+    pub cron: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub policy: String,
+    /// How long a `WaitForEmpty` restart waits before it is forced
+    pub max_wait_minutes: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = restart_schedules)]
+pub struct NewRestartSchedule {
+    pub server: String,
+    pub cron: String,
+    pub created_by: String,
+    pub policy: String,
+    pub max_wait_minutes: i32,
+}
+
+#[derive(HasQuery, Clone, Debug)]
+pub struct MaintenanceWindow {
+    pub id: Uuid,
+    pub server: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub reason: String,
+    pub created_by: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = maintenance_windows)]
+pub struct NewMaintenanceWindow {
+    pub server: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub reason: String,
+    pub created_by: String,
+}
@@ -0,0 +1,171 @@
+use {
+    crate::{
+        game_manager::{Platform, Player},
+        i18n::{default_locale, join_list, plural},
+    },
+    rust_i18n::t,
+};
+
+impl Player {
+    /// Two entries are the same player if their profile UUIDs match, or their names do when the server
+    /// does not report UUIDs.
+    #[must_use]
+    pub fn is_same_player(&self, other: &Self) -> bool {
+        match (&self.uuid, &other.uuid) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.name == other.name,
+        }
+    }
+
+    /// The name shown in Discord, marking Bedrock players so they can be told apart from a Java player
+    /// with the same name
+    #[must_use]
+    pub fn display_name(&self) -> String {
+        match self.platform {
+            Some(Platform::Bedrock) => format!("{} (Bedrock)", self.name),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+/// take two lists of players and return the difference between them.
+///
+/// the first tuple is the list of players who have disconnected. the second tuple is of players who have joined.
+/// the third is of players who were online before and still are.
+#[must_use]
+pub fn get_player_diff(
+    before: &[Player],
+    after: &[Player],
+) -> (Vec<Player>, Vec<Player>, Vec<Player>) {
+    let disconnected = before
+        .iter()
+        .filter(|&player| !after.iter().any(|p| p.is_same_player(player)))
+        .cloned()
+        .collect();
+    let mut joined = vec![];
+    let mut remaining_online = vec![];
+    for player in after {
+        if before.iter().any(|p| p.is_same_player(player)) {
+            remaining_online.push(player.clone());
+        } else {
+            joined.push(player.clone());
+        }
+    }
+
+    (disconnected, joined, remaining_online)
+}
+
+fn join_names(players: &[Player]) -> String {
+    let names: Vec<_> = players.iter().map(Player::display_name).collect();
+    join_list(&names, default_locale())
+}
+
+/// Announce who joined and left, in the locale set with `LOCALE`
+#[must_use]
+pub fn get_player_changes(before: &[Player], after: &[Player]) -> Option<String> {
+    let (disconnected, joined, remaining) = get_player_diff(before, after);
+    if disconnected.is_empty() && joined.is_empty() {
+        return None;
+    }
+    let describe = |key: &str, players: &[Player]| {
+        t!(&plural(key, players.len()), names = join_names(players)).into_owned()
+    };
+    // player1, player2, and player3 have joined. player4, player5, and player6 have disconnected
+    Some(
+        [
+            if joined.is_empty() {
+                String::new()
+            } else {
+                describe("players.joined", &joined)
+            },
+            if disconnected.is_empty() {
+                String::new()
+            } else {
+                describe("players.disconnected", &disconnected)
+            },
+            if !remaining.is_empty() {
+                describe("players.online", &remaining)
+            } else if !disconnected.is_empty() && joined.is_empty() {
+                t!("players.nobody_online").into_owned()
+            } else {
+                String::new()
+            },
+        ]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, platform: Option<Platform>, uuid: Option<&str>) -> Player {
+        Player {
+            name: name.to_owned(),
+            platform,
+            uuid: uuid.map(String::from),
+            xuid: None,
+        }
+    }
+
+    fn java(name: &str, uuid: &str) -> Player {
+        player(name, Some(Platform::Java), Some(uuid))
+    }
+
+    fn bedrock(name: &str, uuid: &str) -> Player {
+        player(name, Some(Platform::Bedrock), Some(uuid))
+    }
+
+    #[test]
+    fn diffs_players_by_uuid() {
+        let steve = java("Steve", "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        let java_alex = java("Alex", "ec561538-f3fd-461d-aff5-086b22154bce");
+        // a Bedrock player may share a Java player's name once Floodgate's prefix is stripped
+        let bedrock_alex = bedrock("Alex", "00000000-0000-0000-0009-01f5b3e0c2a5");
+
+        let (left, joined, online) = get_player_diff(
+            &[steve.clone(), java_alex.clone()],
+            &[steve.clone(), bedrock_alex.clone()],
+        );
+        assert_eq!(left, [java_alex]);
+        assert_eq!(joined, [bedrock_alex]);
+        assert_eq!(online, std::slice::from_ref(&steve));
+
+        // a renamed player is the same player
+        let renamed = java("Herobrine", "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        let (left, joined, online) = get_player_diff(&[steve], std::slice::from_ref(&renamed));
+        assert_eq!(left, []);
+        assert_eq!(joined, []);
+        assert_eq!(online, [renamed]);
+    }
+
+    #[test]
+    fn diffs_players_without_uuids_by_name() {
+        let guide = player("Steve the Guide", None, None);
+        let merchant = player("Merchant", None, None);
+        let (left, joined, online) = get_player_diff(
+            std::slice::from_ref(&guide),
+            std::slice::from_ref(&merchant),
+        );
+        assert_eq!(left, std::slice::from_ref(&guide));
+        assert_eq!(joined, [merchant]);
+        assert_eq!(online, []);
+
+        let (left, joined, online) = get_player_diff(&[], &[]);
+        assert_eq!((left, joined, online), (vec![], vec![], vec![]));
+        assert_eq!(
+            get_player_changes(std::slice::from_ref(&guide), std::slice::from_ref(&guide)),
+            None
+        );
+    }
+
+    #[test]
+    fn marks_bedrock_players_in_discord() {
+        assert_eq!(bedrock("Alex", "").display_name(), "Alex (Bedrock)");
+        assert_eq!(java("Alex", "").display_name(), "Alex");
+        assert_eq!(player("Alex", None, None).display_name(), "Alex");
+    }
+}
@@ -0,0 +1,197 @@
+//! Play sessions recorded from the joins and leaves the poller sees, and the totals derived from them.
+
+#[cfg(feature = "server")]
+use {
+    crate::{
+        error::AppError,
+        game_manager::{GameServer, Player},
+        models::{NewPlaySession, PlaySession},
+        queries::{
+            close_play_sessions, close_play_sessions_at_last_seen, create_play_sessions,
+            get_open_play_sessions, touch_play_sessions,
+        },
+        state::db_pool,
+    },
+    std::collections::HashMap,
+};
+use {
+    chrono::{DateTime, Utc},
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServerPlaytime {
+    /// `game_manager` id of the server
+    pub server: String,
+    pub total_seconds: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlayerPlaytime {
+    /// The name the player was last seen with
+    pub name: String,
+    pub total_seconds: i64,
+    /// Time played since the start of the leaderboard week
+    pub week_seconds: i64,
+    /// When the player last left, `None` while they are online
+    pub last_seen: Option<DateTime<Utc>>,
+    pub online: bool,
+    pub servers: Vec<ServerPlaytime>,
+}
+
+/// Format a duration as e.g. `3h 12m`, or `45m` under an hour
+#[must_use]
+pub fn format_duration(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+/// Total playtime per player across servers, most played first. Sessions still open count up to `now`,
+/// and only the part of a session after `week_start` counts towards the week.
+#[cfg(feature = "server")]
+#[must_use]
+pub fn summarize(
+    sessions: &[PlaySession],
+    now: DateTime<Utc>,
+    week_start: DateTime<Utc>,
+) -> Vec<PlayerPlaytime> {
+    let mut players: HashMap<&str, PlayerPlaytime> = HashMap::new();
+    for session in sessions {
+        let end = session.left_at.unwrap_or(now);
+        let seconds = (end - session.joined_at).num_seconds().max(0);
+        let week_seconds = (end - session.joined_at.max(week_start))
+            .num_seconds()
+            .max(0);
+        let player = players
+            .entry(session.player_id.as_str())
+            .or_insert_with(|| PlayerPlaytime {
+                name: session.player_name.clone(),
+                total_seconds: 0,
+                week_seconds: 0,
+                last_seen: None,
+                online: false,
+                servers: vec![],
+            });
+        // sessions are ordered by join time, so the latest name wins
+        player.name.clone_from(&session.player_name);
+        player.total_seconds += seconds;
+        player.week_seconds += week_seconds;
+        player.online |= session.left_at.is_none();
+        player.last_seen = player.last_seen.max(session.left_at);
+        match player
+            .servers
+            .iter_mut()
+            .find(|s| s.server == session.server)
+        {
+            Some(server) => server.total_seconds += seconds,
+            None => player.servers.push(ServerPlaytime {
+                server: session.server.clone(),
+                total_seconds: seconds,
+            }),
+        }
+    }
+    let mut players: Vec<_> = players
+        .into_values()
+        .map(|mut player| {
+            if player.online {
+                player.last_seen = None;
+            }
+            player
+                .servers
+                .sort_by_key(|s| std::cmp::Reverse(s.total_seconds));
+            player
+        })
+        .collect();
+    players.sort_by_key(|p| std::cmp::Reverse(p.total_seconds));
+    players
+}
+
+/// Monday 00:00 UTC of the current week
+#[cfg(feature = "server")]
+#[must_use]
+pub fn week_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    use chrono::{Datelike, Duration, NaiveTime};
+
+    let monday = now.date_naive() - Duration::days(i64::from(now.weekday().num_days_from_monday()));
+    monday.and_time(NaiveTime::MIN).and_utc()
+}
+
+#[cfg(feature = "server")]
+impl Player {
+    /// Key sessions by UUID so renamed players keep their history
+    #[must_use]
+    pub fn player_id(&self) -> String {
+        self.uuid.clone().unwrap_or_else(|| self.name.clone())
+    }
+}
+
+#[cfg(feature = "server")]
+fn new_session(server: GameServer, player: &Player, joined_at: DateTime<Utc>) -> NewPlaySession {
+    NewPlaySession {
+        server: server.id().to_owned(),
+        player_id: player.player_id(),
+        player_name: player.name.clone(),
+        platform: player.platform.map(|p| p.to_string()),
+        joined_at,
+        last_seen_at: joined_at,
+    }
+}
+
+/// Open sessions for players who joined, close those of players who left and note that everyone else
+/// is still online
+///
+/// # Errors
+///
+/// Fails if the database fails.
+#[cfg(feature = "server")]
+pub async fn record_sessions(
+    server: GameServer,
+    joined: &[Player],
+    left: &[Player],
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    if !left.is_empty() {
+        let ids = left.iter().map(Player::player_id).collect();
+        close_play_sessions(db_pool()?, server.id().to_owned(), ids, now).await?;
+    }
+    if !joined.is_empty() {
+        let sessions = joined.iter().map(|p| new_session(server, p, now)).collect();
+        create_play_sessions(db_pool()?, sessions).await?;
+    }
+    touch_play_sessions(db_pool()?, server.id().to_owned(), now).await
+}
+
+/// Bring the sessions of a server in line with who is online on the first poll after the bot starts.
+///
+/// Sessions left open by a previous run are closed at the last poll that saw the player if they have
+/// gone, and kept if they are still online; players without an open session get one.
+///
+/// # Errors
+///
+/// Fails if the database fails.
+#[cfg(feature = "server")]
+pub async fn reconcile_sessions(server: GameServer, online: &[Player]) -> Result<(), AppError> {
+    let open = get_open_play_sessions(db_pool()?, server.id().to_owned()).await?;
+    let gone: Vec<String> = open
+        .iter()
+        .filter(|session| !online.iter().any(|p| p.player_id() == session.player_id))
+        .map(|session| session.player_id.clone())
+        .collect();
+    let joined: Vec<Player> = online
+        .iter()
+        .filter(|p| {
+            !open
+                .iter()
+                .any(|session| session.player_id == p.player_id())
+        })
+        .cloned()
+        .collect();
+    if !gone.is_empty() {
+        tracing::info!("Closing {} dangling {server} sessions", gone.len());
+        close_play_sessions_at_last_seen(db_pool()?, server.id().to_owned(), gone).await?;
+    }
+    record_sessions(server, &joined, &[]).await
+}
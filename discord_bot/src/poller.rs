@@ -0,0 +1,201 @@
+//! Periodically polls `game_manager` for the state of every game server and announces changes in Discord,
+//! and relays game chat to bridged channels.
+
+use {
+    crate::{
+        chat_bridge::{CHAT_BRIDGE, relay_to_discord},
+        error::AppError,
+        game_manager::{GAME_MANAGER, GameServer, Player, ServerStatus},
+        health::HEALTH,
+        metrics,
+        players::{get_player_changes, get_player_diff},
+        playtime::{reconcile_sessions, record_sessions},
+        population::{PRUNE_INTERVAL, SAMPLE_INTERVAL, prune_samples, record_sample},
+        scheduler::in_maintenance,
+        state::announcement_channel,
+    },
+    poise::{ChoiceParameter, serenity_prelude as serenity},
+    rust_i18n::t,
+    std::{
+        collections::HashMap,
+        sync::{Arc, LazyLock, Mutex, PoisonError},
+        time::Instant,
+    },
+};
+
+/// The status of each server on the last tick, absent while it can't be polled. The website shows these
+/// rather than asking `game_manager` on every page view.
+static STATUSES: LazyLock<Mutex<HashMap<GameServer, ServerStatus>>> = LazyLock::new(Mutex::default);
+
+/// A server's status as of the last tick, `None` if it has not been polled or polling it failed
+#[must_use]
+pub fn cached_status(server: GameServer) -> Option<ServerStatus> {
+    STATUSES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&server)
+        .cloned()
+}
+
+fn cache_status(server: GameServer, status: Option<ServerStatus>) {
+    let mut statuses = STATUSES.lock().unwrap_or_else(PoisonError::into_inner);
+    match status {
+        Some(status) => statuses.insert(server, status),
+        None => statuses.remove(&server),
+    };
+}
+
+pub struct Poller {
+    http: Arc<serenity::Http>,
+    /// Refreshed from the global state every tick
+    announcement_channel: Option<serenity::ChannelId>,
+    /// Players seen on the previous tick, absent until a server has been polled once
+    last_players: HashMap<GameServer, Vec<Player>>,
+    /// Where each bridged server's chat was last read up to
+    chat_cursors: HashMap<GameServer, String>,
+    /// When each server's population was last recorded
+    last_sample: HashMap<GameServer, Instant>,
+    /// When old population samples were last deleted
+    last_prune: Option<Instant>,
+    /// Whether each server was up on the previous tick
+    last_online: HashMap<GameServer, bool>,
+}
+
+impl Poller {
+    #[must_use]
+    pub fn new(http: Arc<serenity::Http>) -> Self {
+        Self {
+            http,
+            announcement_channel: None,
+            last_players: HashMap::new(),
+            chat_cursors: HashMap::new(),
+            last_sample: HashMap::new(),
+            last_prune: None,
+            last_online: HashMap::new(),
+        }
+    }
+
+    /// Poll every server once, announcing players who joined or left since the last tick
+    ///
+    /// # Errors
+    ///
+    /// Fails if the `game_manager` client is not configured. Failures polling a single server are logged.
+    pub async fn tick(&mut self) -> Result<(), AppError> {
+        self.announcement_channel = announcement_channel().await;
+        let client = GAME_MANAGER
+            .get()
+            .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+        for server in GameServer::ALL {
+            match client.status(server).await {
+                Ok(status) => {
+                    HEALTH.record_poll();
+                    cache_status(server, Some(status.clone()));
+                    self.update(status).await;
+                }
+                Err(e) => {
+                    tracing::debug!("Polling {server} failed: {e}");
+                    cache_status(server, None);
+                }
+            }
+            if let Some(channel) = CHAT_BRIDGE.get().and_then(|bridge| bridge.channel(server))
+                && let Err(e) = self.relay_chat(server, channel).await
+            {
+                tracing::debug!("Relaying {server} chat failed: {e}");
+            }
+        }
+        if self
+            .last_prune
+            .is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL)
+        {
+            self.last_prune = Some(Instant::now());
+            if let Err(e) = prune_samples().await {
+                tracing::error!("Failed to delete old population samples: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn relay_chat(
+        &mut self,
+        server: GameServer,
+        channel: serenity::ChannelId,
+    ) -> Result<(), AppError> {
+        let client = GAME_MANAGER
+            .get()
+            .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+        let chat = client
+            .chat(server, self.chat_cursors.get(&server).map(String::as_str))
+            .await?;
+        if let Some(cursor) = chat.cursor {
+            self.chat_cursors.insert(server, cursor);
+        }
+        relay_to_discord(&self.http, channel, &chat.messages).await
+    }
+
+    async fn update(&mut self, status: ServerStatus) {
+        metrics::record_server(status.id.id(), status.online, status.players.len());
+        if let Some(was_online) = self.last_online.insert(status.id, status.online)
+            && was_online != status.online
+        {
+            self.announce_state(status.id, status.online).await;
+        }
+        if self
+            .last_sample
+            .get(&status.id)
+            .is_none_or(|t| t.elapsed() >= SAMPLE_INTERVAL)
+        {
+            self.last_sample.insert(status.id, Instant::now());
+            if let Err(e) = record_sample(&status).await {
+                tracing::error!("Failed to record {} population: {e}", status.id);
+            }
+        }
+        let previous = self.last_players.insert(status.id, status.players.clone());
+        // nothing to compare against on the first poll after a (re)start
+        let Some(previous) = previous else {
+            if let Err(e) = reconcile_sessions(status.id, &status.players).await {
+                tracing::error!("Failed to reconcile {} play sessions: {e}", status.id);
+            }
+            return;
+        };
+        let (left, joined, _) = get_player_diff(&previous, &status.players);
+        if let Err(e) = record_sessions(status.id, &joined, &left).await {
+            tracing::error!("Failed to record {} play sessions: {e}", status.id);
+        }
+        let Some(changes) = get_player_changes(&previous, &status.players) else {
+            return;
+        };
+        tracing::info!("{}: {changes}", status.id);
+        self.announce(format!("**{}**: {changes}", status.id.name()))
+            .await;
+    }
+
+    /// Announce a server going down or coming back, unless it is in a maintenance window
+    async fn announce_state(&self, server: GameServer, online: bool) {
+        match in_maintenance(server, chrono::Utc::now()).await {
+            Ok(true) => {
+                tracing::info!(
+                    "{server} is {}, in maintenance",
+                    if online { "up" } else { "down" }
+                );
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to check {server} maintenance windows: {e}"),
+        }
+        let key = if online {
+            "status.back_up"
+        } else {
+            "status.down"
+        };
+        self.announce(t!(key, server = server.name()).into_owned())
+            .await;
+    }
+
+    async fn announce(&self, message: String) {
+        if let Some(channel) = self.announcement_channel
+            && let Err(e) = channel.say(&self.http, message).await
+        {
+            tracing::error!("Failed to announce: {e}");
+        }
+    }
+}
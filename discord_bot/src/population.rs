@@ -0,0 +1,237 @@
+//! Online player counts sampled from the poller, bucketed for charting.
+
+#[cfg(feature = "server")]
+use crate::{
+    error::AppError,
+    game_manager::{GameServer, ServerStatus},
+    models::{NewPopulationSample, PopulationSample},
+    queries::{create_population_sample, delete_population_samples_before},
+    state::db_pool,
+};
+use {
+    chrono::{DateTime, Utc},
+    rust_i18n::t,
+    serde::{Deserialize, Serialize},
+    std::time::Duration,
+};
+
+/// How often each server's population is recorded, however often it is polled
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// How often samples too old to be charted are deleted
+pub const PRUNE_INTERVAL: Duration = Duration::from_hours(1);
+
+/// The span of history a chart covers
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum PopulationRange {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl PopulationRange {
+    pub const ALL: [Self; 3] = [Self::Day, Self::Week, Self::Month];
+
+    /// The range's button label in `locale`
+    #[must_use]
+    pub fn label(self, locale: &str) -> String {
+        let key = match self {
+            Self::Day => "web.population.day",
+            Self::Week => "web.population.week",
+            Self::Month => "web.population.month",
+        };
+        t!(key, locale = locale).into_owned()
+    }
+
+    #[must_use]
+    pub const fn duration(self) -> Duration {
+        match self {
+            Self::Day => Duration::from_hours(24),
+            Self::Week => Duration::from_hours(7 * 24),
+            Self::Month => Duration::from_hours(30 * 24),
+        }
+    }
+
+    /// How long samples are kept, the longest range any chart covers
+    #[must_use]
+    pub fn retention() -> Duration {
+        Self::ALL
+            .into_iter()
+            .map(Self::duration)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Width of each point on the chart, chosen to keep a few hundred points per series at most
+    #[must_use]
+    pub const fn bucket(self) -> Duration {
+        match self {
+            Self::Day => Duration::from_mins(5),
+            Self::Week => Duration::from_hours(1),
+            Self::Month => Duration::from_hours(6),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PopulationPoint {
+    /// Start of the bucket
+    pub at: DateTime<Utc>,
+    /// Most players online at once during the bucket
+    pub players: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PopulationSeries {
+    /// `game_manager` id of the server
+    pub server: String,
+    pub name: String,
+    pub points: Vec<PopulationPoint>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PopulationChart {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub bucket_seconds: i64,
+    pub series: Vec<PopulationSeries>,
+}
+
+/// Record a server's current population
+///
+/// # Errors
+///
+/// Fails if the database fails.
+#[cfg(feature = "server")]
+pub async fn record_sample(status: &ServerStatus) -> Result<(), AppError> {
+    create_population_sample(
+        db_pool()?,
+        NewPopulationSample {
+            server: status.id.id().to_owned(),
+            online: status.online,
+            players: i32::try_from(status.players.len()).unwrap_or(i32::MAX),
+            max_players: status.max_players.and_then(|m| i32::try_from(m).ok()),
+        },
+    )
+    .await
+}
+
+/// Delete samples too old to be charted
+///
+/// # Errors
+///
+/// Fails if the database fails.
+#[cfg(feature = "server")]
+pub async fn prune_samples() -> Result<(), AppError> {
+    let cutoff = Utc::now() - PopulationRange::retention();
+    delete_population_samples_before(db_pool()?, cutoff).await
+}
+
+/// Group samples into one series per server with the peak player count of each bucket. Buckets without
+/// samples, such as while the bot was down, are left out rather than drawn as zero.
+#[cfg(feature = "server")]
+#[must_use]
+pub fn bucketize(
+    samples: &[PopulationSample],
+    since: DateTime<Utc>,
+    bucket: Duration,
+) -> Vec<PopulationSeries> {
+    use poise::ChoiceParameter;
+
+    let bucket_seconds = i64::try_from(bucket.as_secs()).unwrap_or(i64::MAX).max(1);
+    GameServer::ALL
+        .into_iter()
+        .map(|server| {
+            let mut points: Vec<PopulationPoint> = vec![];
+            for sample in samples.iter().filter(|s| s.server == server.id()) {
+                let offset = (sample.sampled_at - since).num_seconds().max(0) / bucket_seconds;
+                let at = since + chrono::Duration::seconds(offset * bucket_seconds);
+                match points.last_mut() {
+                    Some(point) if point.at == at => {
+                        point.players = point.players.max(sample.players);
+                    }
+                    _ => points.push(PopulationPoint {
+                        at,
+                        players: sample.players,
+                    }),
+                }
+            }
+            PopulationSeries {
+                server: server.id().to_owned(),
+                name: server.name().to_owned(),
+                points,
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2026-10-19T{time}Z")
+            .parse()
+            .expect("invalid timestamp")
+    }
+
+    fn sample(server: GameServer, time: &str, players: i32) -> PopulationSample {
+        PopulationSample {
+            id: 0,
+            server: server.id().to_owned(),
+            sampled_at: at(time),
+            online: true,
+            players,
+            max_players: Some(20),
+        }
+    }
+
+    fn point(time: &str, players: i32) -> PopulationPoint {
+        PopulationPoint {
+            at: at(time),
+            players,
+        }
+    }
+
+    #[test]
+    fn buckets_hold_the_peak_player_count() {
+        let samples = [
+            // samples from before the chart starts are clamped into the first bucket
+            PopulationSample {
+                sampled_at: at("00:00:00") - chrono::Duration::minutes(1),
+                ..sample(GameServer::MinecraftGeyser, "00:00:00", 1)
+            },
+            sample(GameServer::Terraria, "00:02:00", 4),
+            sample(GameServer::MinecraftGeyser, "00:01:00", 2),
+            sample(GameServer::MinecraftGeyser, "00:04:59", 5),
+            sample(GameServer::MinecraftGeyser, "00:05:00", 1),
+            // nothing was sampled between 00:10 and 00:20
+            sample(GameServer::MinecraftGeyser, "00:20:00", 3),
+            PopulationSample {
+                server: String::from("retired_server"),
+                ..sample(GameServer::Terraria, "00:03:00", 9)
+            },
+        ];
+
+        let series = bucketize(&samples, at("00:00:00"), Duration::from_mins(5));
+        let points: Vec<_> = series
+            .iter()
+            .map(|series| (series.server.as_str(), series.points.clone()))
+            .collect();
+        assert_eq!(
+            points,
+            [
+                (
+                    "minecraft_geyser",
+                    vec![
+                        point("00:00:00", 5),
+                        point("00:05:00", 1),
+                        point("00:20:00", 3)
+                    ]
+                ),
+                ("minecraft_modded", vec![]),
+                ("terraria", vec![point("00:00:00", 4)]),
+            ]
+        );
+    }
+}
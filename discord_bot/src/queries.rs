@@ -1,34 +1,60 @@
-use diesel::{RunQueryDsl, SelectableHelper};
+//! Database queries, each run on a pooled connection. They fail only if no connection can be had or
+//! the query itself fails, so none of them document their errors.
+#![allow(clippy::missing_errors_doc)]
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, HasQuery, NullableExpressionMethods,
+    OptionalExtension, PgConnection, PgTextExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+    SelectableHelper,
+};
 
 use crate::error::AppError;
 use crate::models::{
-    NewReactionMessage, NewSelfAssignableRole, ReactionMessage, SelfAssignableRole,
+    ChatBridgeChannel, MaintenanceWindow, MinecraftLink, NewChatBridgeChannel,
+    NewMaintenanceWindow, NewMinecraftLink, NewPlaySession, NewPopulationSample,
+    NewReactionMessage, NewRestartSchedule, NewSelfAssignableRole, PlaySession, PopulationSample,
+    ReactionMessage, RestartSchedule, SelfAssignableRole,
+};
+use crate::schema::{
+    chat_bridge_channels, maintenance_windows, minecraft_links, play_sessions, population_samples,
+    reaction_messages, restart_schedules, self_assignable_roles,
 };
-use crate::schema::{reaction_messages, self_assignable_roles};
+
+/// Run a blocking diesel query on a pooled connection
+async fn interact<T, F>(pool: &deadpool_diesel::postgres::Pool, query: F) -> Result<T, AppError>
+where
+    F: FnOnce(&mut PgConnection) -> QueryResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let conn = pool.get().await?;
+    Ok(conn.interact(query).await??)
+}
 
 pub async fn create_self_assignable_role(
     pool: deadpool_diesel::postgres::Pool,
     new_role: NewSelfAssignableRole,
 ) -> Result<SelfAssignableRole, AppError> {
-    let conn = pool.get().await.unwrap();
-    let res = conn
-        .interact(|conn| {
-            diesel::insert_into(self_assignable_roles::table)
-                .values(new_role)
-                .returning(SelfAssignableRole::as_returning())
-                .get_result(conn)
-        })
-        .await
-        .unwrap()
-        .unwrap();
-    Ok(res)
+    interact(&pool, |conn| {
+        diesel::insert_into(self_assignable_roles::table)
+            .values(new_role)
+            .returning(SelfAssignableRole::as_returning())
+            .get_result(conn)
+    })
+    .await
 }
 
 pub async fn create_reaction_message(
     pool: deadpool_diesel::postgres::Pool,
     new_message: NewReactionMessage,
 ) -> Result<ReactionMessage, AppError> {
-    todo!()
+    interact(&pool, |conn| {
+        diesel::insert_into(reaction_messages::table)
+            .values(new_message)
+            .returning(ReactionMessage::as_returning())
+            .get_result(conn)
+    })
+    .await
 }
 
 pub async fn delete_self_assignable_role(
@@ -36,14 +62,28 @@ pub async fn delete_self_assignable_role(
     guild_id: String,
     role_id: String,
 ) -> Result<(), AppError> {
-    todo!()
+    interact(&pool, move |conn| {
+        diesel::delete(
+            self_assignable_roles::table
+                .filter(self_assignable_roles::guild_id.eq(guild_id))
+                .filter(self_assignable_roles::role_id.eq(role_id)),
+        )
+        .execute(conn)
+    })
+    .await
+    .map(|_| ())
 }
 
 pub async fn get_self_assignable_roles(
     pool: deadpool_diesel::postgres::Pool,
     guild_id: String,
 ) -> Result<Vec<SelfAssignableRole>, AppError> {
-    todo!()
+    interact(&pool, move |conn| {
+        SelfAssignableRole::query()
+            .filter(self_assignable_roles::guild_id.eq(guild_id))
+            .load(conn)
+    })
+    .await
 }
 
 pub async fn get_reaction_message(
@@ -51,5 +91,440 @@ pub async fn get_reaction_message(
     guild_id: String,
     message_id: String,
 ) -> Result<Option<ReactionMessage>, AppError> {
-    todo!()
+    interact(&pool, move |conn| {
+        ReactionMessage::query()
+            .filter(reaction_messages::guild_id.eq(guild_id))
+            .filter(reaction_messages::message_id.eq(message_id))
+            .first(conn)
+            .optional()
+    })
+    .await
+}
+
+pub async fn create_minecraft_link(
+    pool: deadpool_diesel::postgres::Pool,
+    new_link: NewMinecraftLink,
+) -> Result<MinecraftLink, AppError> {
+    interact(&pool, |conn| {
+        diesel::insert_into(minecraft_links::table)
+            .values(new_link)
+            .returning(MinecraftLink::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+pub async fn get_minecraft_link(
+    pool: deadpool_diesel::postgres::Pool,
+    guild_id: String,
+    discord_user_id: String,
+) -> Result<Option<MinecraftLink>, AppError> {
+    interact(&pool, move |conn| {
+        MinecraftLink::query()
+            .filter(minecraft_links::guild_id.eq(guild_id))
+            .filter(minecraft_links::discord_user_id.eq(discord_user_id))
+            .first(conn)
+            .optional()
+    })
+    .await
+}
+
+pub async fn get_minecraft_link_by_uuid(
+    pool: deadpool_diesel::postgres::Pool,
+    minecraft_uuid: uuid::Uuid,
+) -> Result<Option<MinecraftLink>, AppError> {
+    interact(&pool, move |conn| {
+        MinecraftLink::query()
+            .filter(minecraft_links::minecraft_uuid.eq(minecraft_uuid))
+            .first(conn)
+            .optional()
+    })
+    .await
+}
+
+/// Every link a Discord user has, in any guild
+pub async fn get_minecraft_links_for_user(
+    pool: deadpool_diesel::postgres::Pool,
+    discord_user_id: String,
+) -> Result<Vec<MinecraftLink>, AppError> {
+    interact(&pool, move |conn| {
+        MinecraftLink::query()
+            .filter(minecraft_links::discord_user_id.eq(discord_user_id))
+            .order(minecraft_links::created_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+/// Links approved or denied by a Discord user
+pub async fn get_minecraft_links_decided_by(
+    pool: deadpool_diesel::postgres::Pool,
+    decided_by: String,
+) -> Result<Vec<MinecraftLink>, AppError> {
+    interact(&pool, move |conn| {
+        MinecraftLink::query()
+            .filter(minecraft_links::decided_by.eq(decided_by))
+            .order(minecraft_links::created_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+pub async fn get_minecraft_links_with_status(
+    pool: deadpool_diesel::postgres::Pool,
+    guild_id: String,
+    status: String,
+) -> Result<Vec<MinecraftLink>, AppError> {
+    interact(&pool, move |conn| {
+        MinecraftLink::query()
+            .filter(minecraft_links::guild_id.eq(guild_id))
+            .filter(minecraft_links::status.eq(status))
+            .order(minecraft_links::created_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+pub async fn set_minecraft_link_status(
+    pool: deadpool_diesel::postgres::Pool,
+    id: uuid::Uuid,
+    status: String,
+    decided_by: String,
+) -> Result<MinecraftLink, AppError> {
+    interact(&pool, move |conn| {
+        diesel::update(minecraft_links::table.find(id))
+            .set((
+                minecraft_links::status.eq(status),
+                minecraft_links::decided_by.eq(Some(decided_by)),
+            ))
+            .returning(MinecraftLink::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+pub async fn delete_minecraft_link(
+    pool: deadpool_diesel::postgres::Pool,
+    id: uuid::Uuid,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::delete(minecraft_links::table.find(id)).execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn get_chat_bridge_channels(
+    pool: deadpool_diesel::postgres::Pool,
+) -> Result<Vec<ChatBridgeChannel>, AppError> {
+    interact(&pool, |conn| ChatBridgeChannel::query().load(conn)).await
+}
+
+pub async fn set_chat_bridge_channel(
+    pool: deadpool_diesel::postgres::Pool,
+    channel: NewChatBridgeChannel,
+) -> Result<ChatBridgeChannel, AppError> {
+    interact(&pool, move |conn| {
+        diesel::insert_into(chat_bridge_channels::table)
+            .values(&channel)
+            .on_conflict(chat_bridge_channels::channel_id)
+            .do_update()
+            .set((
+                &channel,
+                chat_bridge_channels::updated_at.eq(diesel::dsl::now),
+            ))
+            .returning(ChatBridgeChannel::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+pub async fn create_play_sessions(
+    pool: deadpool_diesel::postgres::Pool,
+    sessions: Vec<NewPlaySession>,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::insert_into(play_sessions::table)
+            .values(sessions)
+            .execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn get_open_play_sessions(
+    pool: deadpool_diesel::postgres::Pool,
+    server: String,
+) -> Result<Vec<PlaySession>, AppError> {
+    interact(&pool, move |conn| {
+        PlaySession::query()
+            .filter(play_sessions::server.eq(server))
+            .filter(play_sessions::left_at.is_null())
+            .load(conn)
+    })
+    .await
+}
+
+/// End the open sessions of the given players on a server
+pub async fn close_play_sessions(
+    pool: deadpool_diesel::postgres::Pool,
+    server: String,
+    player_ids: Vec<String>,
+    left_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::update(play_sessions::table)
+            .filter(play_sessions::server.eq(server))
+            .filter(play_sessions::player_id.eq_any(player_ids))
+            .filter(play_sessions::left_at.is_null())
+            .set(play_sessions::left_at.eq(Some(left_at)))
+            .execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+/// Note that the players with open sessions on a server were online at `seen_at`
+pub async fn touch_play_sessions(
+    pool: deadpool_diesel::postgres::Pool,
+    server: String,
+    seen_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::update(play_sessions::table)
+            .filter(play_sessions::server.eq(server))
+            .filter(play_sessions::left_at.is_null())
+            .set(play_sessions::last_seen_at.eq(seen_at))
+            .execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+/// End the open sessions of the given players on a server when they were last seen
+pub async fn close_play_sessions_at_last_seen(
+    pool: deadpool_diesel::postgres::Pool,
+    server: String,
+    player_ids: Vec<String>,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::update(play_sessions::table)
+            .filter(play_sessions::server.eq(server))
+            .filter(play_sessions::player_id.eq_any(player_ids))
+            .filter(play_sessions::left_at.is_null())
+            .set(play_sessions::left_at.eq(play_sessions::last_seen_at.nullable()))
+            .execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+/// Sessions still open or ended after `since`, or every session without a bound
+pub async fn get_play_sessions_since(
+    pool: deadpool_diesel::postgres::Pool,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<PlaySession>, AppError> {
+    interact(&pool, move |conn| {
+        let mut query = PlaySession::query().into_boxed();
+        if let Some(since) = since {
+            query = query.filter(
+                play_sessions::left_at
+                    .is_null()
+                    .or(play_sessions::left_at.ge(since)),
+            );
+        }
+        query.order(play_sessions::joined_at.asc()).load(conn)
+    })
+    .await
+}
+
+/// Every session of the player with this name, ignoring case
+pub async fn get_play_sessions_for_player(
+    pool: deadpool_diesel::postgres::Pool,
+    player_name: String,
+) -> Result<Vec<PlaySession>, AppError> {
+    // underscores are common in Minecraft names, so match them literally
+    let pattern = player_name
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    interact(&pool, move |conn| {
+        PlaySession::query()
+            .filter(play_sessions::player_name.ilike(pattern))
+            .order(play_sessions::joined_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+/// Every session of the players with these ids
+pub async fn get_play_sessions_for_player_ids(
+    pool: deadpool_diesel::postgres::Pool,
+    player_ids: Vec<String>,
+) -> Result<Vec<PlaySession>, AppError> {
+    interact(&pool, move |conn| {
+        PlaySession::query()
+            .filter(play_sessions::player_id.eq_any(player_ids))
+            .order(play_sessions::joined_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+/// Delete every session of the players with these ids, returning how many there were
+pub async fn delete_play_sessions_for_player_ids(
+    pool: deadpool_diesel::postgres::Pool,
+    player_ids: Vec<String>,
+) -> Result<usize, AppError> {
+    interact(&pool, move |conn| {
+        diesel::delete(play_sessions::table.filter(play_sessions::player_id.eq_any(player_ids)))
+            .execute(conn)
+    })
+    .await
+}
+
+pub async fn create_population_sample(
+    pool: deadpool_diesel::postgres::Pool,
+    sample: NewPopulationSample,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::insert_into(population_samples::table)
+            .values(sample)
+            .execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn get_population_samples_since(
+    pool: deadpool_diesel::postgres::Pool,
+    since: DateTime<Utc>,
+) -> Result<Vec<PopulationSample>, AppError> {
+    interact(&pool, move |conn| {
+        PopulationSample::query()
+            .filter(population_samples::sampled_at.ge(since))
+            .order(population_samples::sampled_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+pub async fn delete_population_samples_before(
+    pool: deadpool_diesel::postgres::Pool,
+    before: DateTime<Utc>,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::delete(population_samples::table.filter(population_samples::sampled_at.lt(before)))
+            .execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn create_restart_schedule(
+    pool: deadpool_diesel::postgres::Pool,
+    schedule: NewRestartSchedule,
+) -> Result<RestartSchedule, AppError> {
+    interact(&pool, move |conn| {
+        diesel::insert_into(restart_schedules::table)
+            .values(schedule)
+            .returning(RestartSchedule::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+pub async fn get_restart_schedules(
+    pool: deadpool_diesel::postgres::Pool,
+) -> Result<Vec<RestartSchedule>, AppError> {
+    interact(&pool, |conn| {
+        RestartSchedule::query()
+            .order(restart_schedules::created_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+pub async fn set_restart_schedule_last_run(
+    pool: deadpool_diesel::postgres::Pool,
+    id: uuid::Uuid,
+    last_run_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    interact(&pool, move |conn| {
+        diesel::update(restart_schedules::table.find(id))
+            .set(restart_schedules::last_run_at.eq(Some(last_run_at)))
+            .execute(conn)
+    })
+    .await
+    .map(|_| ())
+}
+
+pub async fn delete_restart_schedule(
+    pool: deadpool_diesel::postgres::Pool,
+    id: uuid::Uuid,
+) -> Result<bool, AppError> {
+    interact(&pool, move |conn| {
+        diesel::delete(restart_schedules::table.find(id)).execute(conn)
+    })
+    .await
+    .map(|deleted| deleted > 0)
+}
+
+pub async fn create_maintenance_window(
+    pool: deadpool_diesel::postgres::Pool,
+    window: NewMaintenanceWindow,
+) -> Result<MaintenanceWindow, AppError> {
+    interact(&pool, move |conn| {
+        diesel::insert_into(maintenance_windows::table)
+            .values(window)
+            .returning(MaintenanceWindow::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+/// Windows that have not ended yet, soonest first
+pub async fn get_upcoming_maintenance_windows(
+    pool: deadpool_diesel::postgres::Pool,
+    now: DateTime<Utc>,
+) -> Result<Vec<MaintenanceWindow>, AppError> {
+    interact(&pool, move |conn| {
+        MaintenanceWindow::query()
+            .filter(maintenance_windows::ends_at.gt(now))
+            .order(maintenance_windows::starts_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+/// Every window a Discord user added, past and upcoming
+pub async fn get_maintenance_windows_created_by(
+    pool: deadpool_diesel::postgres::Pool,
+    created_by: String,
+) -> Result<Vec<MaintenanceWindow>, AppError> {
+    interact(&pool, move |conn| {
+        MaintenanceWindow::query()
+            .filter(maintenance_windows::created_by.eq(created_by))
+            .order(maintenance_windows::starts_at.asc())
+            .load(conn)
+    })
+    .await
+}
+
+pub async fn delete_maintenance_window(
+    pool: deadpool_diesel::postgres::Pool,
+    id: uuid::Uuid,
+) -> Result<bool, AppError> {
+    interact(&pool, move |conn| {
+        diesel::delete(maintenance_windows::table.find(id)).execute(conn)
+    })
+    .await
+    .map(|deleted| deleted > 0)
+}
+
+/// Run a trivial query, proving a connection can be checked out and the database answers
+pub async fn ping(pool: deadpool_diesel::postgres::Pool) -> Result<(), AppError> {
+    interact(&pool, |conn| diesel::sql_query("SELECT 1").execute(conn))
+        .await
+        .map(|_| ())
 }
@@ -0,0 +1,133 @@
+//! Applying configuration changes without restarting the bot.
+//!
+//! The config file is checked for changes every few seconds, and `SIGHUP` or `/config reload` reload it
+//! straight away. A reload reads every layer again, but the environment and flags are fixed for the life
+//! of the process, so in practice it picks up the config file and secret files.
+//!
+//! A configuration that fails validation is rejected whole and the running one kept. A valid one replaces
+//! the shared state in one step and is handed to the chat bridge and the `game_manager` client, while the
+//! poller and scheduler read the announcement channel on their next tick. Settings only read at startup,
+//! such as the Discord token, which would mean reconnecting to the gateway, keep their old value until the
+//! bot is restarted.
+
+use {
+    crate::{
+        chat_bridge::CHAT_BRIDGE, config::Config, error::AppError, game_manager::GAME_MANAGER,
+        state::GLOBAL_STATE,
+    },
+    std::{
+        path::Path,
+        sync::OnceLock,
+        time::{Duration, SystemTime},
+    },
+    tokio::{
+        signal::unix::{SignalKind, signal},
+        sync::{Mutex, watch},
+    },
+};
+
+/// The running configuration, locked for the whole of a reload so reloads never interleave
+static CONFIG: OnceLock<Mutex<Config>> = OnceLock::new();
+
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn set_config(config: Config) {
+    let _ = CONFIG.set(Mutex::new(config));
+}
+
+/// The outcome of a successful reload
+#[derive(Debug, Default)]
+pub struct Reloaded {
+    /// Whether anything that can be applied while running changed
+    pub changed: bool,
+    /// Keys of changed settings that only apply after a restart
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Load the configuration again and apply it
+///
+/// # Errors
+///
+/// Fails with every problem found if the new configuration is invalid, in which case nothing changes.
+pub async fn reload() -> Result<Reloaded, AppError> {
+    let running = CONFIG
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("configuration not loaded")))?;
+    let mut running = running.lock().await;
+    let mut config = Config::load()
+        .inspect_err(|e| tracing::error!("Keeping the running configuration, {e}"))?;
+    let restart_required = config.keep_startup_settings(&running);
+    for key in &restart_required {
+        tracing::warn!("{key} changed, restart the bot to apply it");
+    }
+    let changed = config != *running;
+    if changed {
+        if let Some(state) = GLOBAL_STATE.get() {
+            *state.lock().await = config.state.clone();
+        }
+        if let Some(bridge) = CHAT_BRIDGE.get() {
+            bridge.reconfigure(
+                &config.chat_channels,
+                config.chat_bridge_rate_limit,
+                config.chat_bridge_rate_window,
+            );
+        }
+        if let Some(client) = GAME_MANAGER.get() {
+            client
+                .reconfigure(
+                    &config.game_manager_url,
+                    config.game_manager_client_id.clone(),
+                    config.game_manager_client_secret.clone(),
+                )
+                .await?;
+        }
+        rust_i18n::set_locale(&config.locale);
+        *running = config;
+        drop(running);
+        tracing::info!("Applied the reloaded configuration");
+    } else {
+        drop(running);
+        tracing::info!("Configuration unchanged");
+    }
+    Ok(Reloaded {
+        changed,
+        restart_required,
+    })
+}
+
+async fn modified(file: Option<&Path>) -> Option<SystemTime> {
+    tokio::fs::metadata(file?).await.ok()?.modified().ok()
+}
+
+/// Reload whenever the config file changes or the bot receives `SIGHUP`, until shutdown. Run under the
+/// supervisor.
+///
+/// # Errors
+///
+/// Fails if the signal handler cannot be installed. Failed reloads are logged and the watch goes on.
+pub async fn watch(mut shutdown_rx: watch::Receiver<()>) -> Result<(), AppError> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    let file = match CONFIG.get() {
+        Some(config) => config.lock().await.file.clone(),
+        None => None,
+    };
+    let mut last_modified = modified(file.as_deref()).await;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let modified = modified(file.as_deref()).await;
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                tracing::info!("Config file changed, reloading");
+            }
+            _ = hangup.recv() => tracing::info!("Received SIGHUP, reloading the configuration"),
+            _ = shutdown_rx.changed() => return Ok(()),
+        }
+        // reload logs its outcome, a bad edit must not stop the watch
+        let _ = reload().await;
+    }
+}
@@ -0,0 +1,231 @@
+//! Restarting a server without pulling the rug out from under its players.
+//!
+//! Manual and scheduled restarts go through [`restart_with_policy`], which decides whether to restart now,
+//! skip the restart or wait for the server to empty depending on the [`RestartPolicy`].
+
+use {
+    crate::{
+        error::AppError,
+        game_manager::{GAME_MANAGER, GameManagerClient, GameServer, Player, ServerAction},
+        scheduler::add_maintenance,
+    },
+    chrono::Utc,
+    rust_i18n::t,
+    std::time::Duration,
+    tokio::time::{Instant, sleep},
+};
+
+/// Seconds before a forced restart that players still online are warned
+pub const COUNTDOWN: [u64; 3] = [60, 30, 10];
+/// How often the player list is checked while waiting for a server to empty
+const EMPTY_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Down alerts are suppressed for this long after a restart begins
+const RESTART_WINDOW_MINUTES: i64 = 10;
+
+/// What to do when players are online at the time of a restart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum RestartPolicy {
+    /// Restart anyway, after counting down in game
+    #[name = "Force with countdown"]
+    Force,
+    /// Skip the restart
+    #[name = "Only when empty"]
+    WhenEmpty,
+    /// Wait for everyone to leave, forcing the restart after the maximum wait
+    #[name = "Wait for empty"]
+    WaitForEmpty,
+}
+
+impl std::fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::str::FromStr for RestartPolicy {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Force" => Ok(Self::Force),
+            "WhenEmpty" => Ok(Self::WhenEmpty),
+            "WaitForEmpty" => Ok(Self::WaitForEmpty),
+            _ => Err(AppError::Other(format!("unknown restart policy {s}"))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestartOutcome {
+    Restarted,
+    /// Players were online and the policy said not to restart
+    Skipped(Vec<Player>),
+}
+
+/// What a restart does next, given who is online
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    Restart,
+    /// Count down in game, then restart
+    Countdown,
+    Skip,
+    /// Check again after a while
+    Wait,
+}
+
+/// Decide how a restart with `policy` proceeds with `players` online, `waited_out` once
+/// [`RestartPolicy::WaitForEmpty`] has waited as long as it may
+const fn next_step(policy: RestartPolicy, players: &[Player], waited_out: bool) -> Step {
+    if players.is_empty() {
+        return Step::Restart;
+    }
+    match policy {
+        RestartPolicy::WhenEmpty => Step::Skip,
+        RestartPolicy::Force => Step::Countdown,
+        RestartPolicy::WaitForEmpty if waited_out => Step::Countdown,
+        RestartPolicy::WaitForEmpty => Step::Wait,
+    }
+}
+
+fn client() -> Result<&'static GameManagerClient, AppError> {
+    GAME_MANAGER
+        .get()
+        .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))
+}
+
+/// Players online on a server, none if it is down
+///
+/// # Errors
+///
+/// Fails if `game_manager` cannot be reached.
+pub async fn online_players(server: GameServer) -> Result<Vec<Player>, AppError> {
+    let status = client()?.status(server).await?;
+    Ok(if status.online {
+        status.players
+    } else {
+        vec![]
+    })
+}
+
+/// Warn players of an imminent restart at each step of [`COUNTDOWN`], returning once it has run out
+///
+/// # Errors
+///
+/// Fails if the `game_manager` client is not configured. Failed warnings are logged.
+pub async fn countdown(server: GameServer) -> Result<(), AppError> {
+    let client = client()?;
+    let mut remaining = COUNTDOWN[0];
+    for seconds in COUNTDOWN {
+        sleep(Duration::from_secs(remaining - seconds)).await;
+        remaining = seconds;
+        let message = t!("restart.countdown", seconds = seconds);
+        if let Err(e) = client.broadcast(server, &message).await {
+            tracing::warn!("Restart countdown on {server} failed: {e}");
+        }
+    }
+    sleep(Duration::from_secs(remaining)).await;
+    Ok(())
+}
+
+/// Restart a server now, opening a maintenance window so it is not reported as down
+///
+/// # Errors
+///
+/// Fails if `game_manager` or the database fails.
+pub async fn restart_now(server: GameServer, reason: &str) -> Result<(), AppError> {
+    let now = Utc::now();
+    add_maintenance(
+        server,
+        now,
+        now + chrono::Duration::minutes(RESTART_WINDOW_MINUTES),
+        reason.to_owned(),
+        String::from("restart"),
+    )
+    .await?;
+    client()?.control(server, ServerAction::Restart).await?;
+    tracing::info!("Restarted {server}: {reason}");
+    Ok(())
+}
+
+/// Restart a server according to `policy`.
+///
+/// `max_wait` bounds how long [`RestartPolicy::WaitForEmpty`] waits before forcing the restart. This can
+/// take as long as `max_wait` plus the countdown, so callers that must not block should spawn it.
+///
+/// # Errors
+///
+/// Fails if `game_manager` or the database fails.
+pub async fn restart_with_policy(
+    server: GameServer,
+    policy: RestartPolicy,
+    max_wait: Duration,
+    reason: &str,
+) -> Result<RestartOutcome, AppError> {
+    let mut players = online_players(server).await?;
+    let deadline = Instant::now() + max_wait;
+    let mut announced = false;
+    loop {
+        match next_step(policy, &players, Instant::now() >= deadline) {
+            Step::Restart => break,
+            Step::Countdown => {
+                countdown(server).await?;
+                break;
+            }
+            Step::Skip => return Ok(RestartOutcome::Skipped(players)),
+            Step::Wait => {
+                if !announced {
+                    announced = true;
+                    let message = t!("restart.waiting", minutes = max_wait.as_secs().div_ceil(60));
+                    if let Err(e) = client()?.broadcast(server, &message).await {
+                        tracing::warn!("Announcing pending restart on {server} failed: {e}");
+                    }
+                }
+                sleep(EMPTY_POLL_INTERVAL.min(deadline - Instant::now())).await;
+                match online_players(server).await {
+                    Ok(online) => players = online,
+                    Err(e) => tracing::debug!("Checking whether {server} is empty failed: {e}"),
+                }
+            }
+        }
+    }
+    restart_now(server, reason).await?;
+    Ok(RestartOutcome::Restarted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restarts_according_to_the_policy() {
+        let players = [Player {
+            name: String::from("Steve"),
+            platform: None,
+            uuid: None,
+            xuid: None,
+        }];
+        for (policy, players, waited_out, expected) in [
+            (RestartPolicy::Force, &[][..], false, Step::Restart),
+            (RestartPolicy::Force, &players[..], false, Step::Countdown),
+            (RestartPolicy::WhenEmpty, &[][..], false, Step::Restart),
+            (RestartPolicy::WhenEmpty, &players[..], false, Step::Skip),
+            (RestartPolicy::WhenEmpty, &players[..], true, Step::Skip),
+            (RestartPolicy::WaitForEmpty, &[][..], false, Step::Restart),
+            (RestartPolicy::WaitForEmpty, &[][..], true, Step::Restart),
+            (RestartPolicy::WaitForEmpty, &players[..], false, Step::Wait),
+            (
+                RestartPolicy::WaitForEmpty,
+                &players[..],
+                true,
+                Step::Countdown,
+            ),
+        ] {
+            assert_eq!(
+                next_step(policy, players, waited_out),
+                expected,
+                "{policy} with {} players, waited out: {waited_out}",
+                players.len()
+            );
+        }
+    }
+}
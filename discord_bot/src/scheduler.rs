@@ -0,0 +1,319 @@
+//! Scheduled restarts and maintenance windows.
+//!
+//! Each restart schedule is a five field cron expression evaluated in UTC. Players are warned in game and
+//! in the announcement channel 15, 5 and 1 minutes before the restart, which is then performed with the
+//! schedule's restart policy. A restart opens a short maintenance window so the poller does not report it
+//! as an outage, and admins can add one-off windows for planned work.
+
+use {
+    crate::{
+        error::AppError,
+        game_manager::{GAME_MANAGER, GameServer},
+        i18n::plural,
+        models::{MaintenanceWindow, NewMaintenanceWindow, RestartSchedule},
+        queries::{
+            create_maintenance_window, get_restart_schedules, get_upcoming_maintenance_windows,
+            set_restart_schedule_last_run,
+        },
+        restart::{RestartOutcome, RestartPolicy, restart_with_policy},
+        state::{announcement_channel, db_pool},
+    },
+    chrono::{DateTime, Duration, Utc},
+    croner::Cron,
+    poise::{ChoiceParameter, serenity_prelude as serenity},
+    rust_i18n::t,
+    std::{collections::HashMap, sync::Arc},
+    uuid::Uuid,
+};
+
+/// Minutes before a restart that players are warned
+pub const WARNINGS: [i64; 3] = [15, 5, 1];
+/// A restart missed by more than this, e.g. because the bot was down, is skipped rather than run late
+const GRACE_MINUTES: i64 = 5;
+
+/// Parse a five field cron expression such as `0 4 * * *`
+///
+/// # Errors
+///
+/// Fails with a description of the problem if the expression is invalid.
+pub fn parse_cron(expression: &str) -> Result<Cron, String> {
+    Cron::new(expression).parse().map_err(|e| e.to_string())
+}
+
+/// When a schedule next fires, counting from its last run or its creation
+#[must_use]
+pub fn next_run(schedule: &RestartSchedule) -> Option<DateTime<Utc>> {
+    let after = schedule.last_run_at.unwrap_or(schedule.created_at);
+    parse_cron(&schedule.cron)
+        .ok()?
+        .find_next_occurrence(&after, false)
+        .ok()
+}
+
+/// The first eight characters of an id, enough to tell schedules apart in Discord
+#[must_use]
+pub fn short_id(id: Uuid) -> String {
+    id.simple().to_string()[..8].to_owned()
+}
+
+/// Whether down alerts for `server` are suppressed at `now`
+///
+/// # Errors
+///
+/// Fails if the database fails.
+pub async fn in_maintenance(server: GameServer, now: DateTime<Utc>) -> Result<bool, AppError> {
+    Ok(get_upcoming_maintenance_windows(db_pool()?, now)
+        .await?
+        .iter()
+        .any(|window| window.server == server.id() && window.starts_at <= now))
+}
+
+/// Add a maintenance window for `server`
+///
+/// # Errors
+///
+/// Fails if the database fails.
+pub async fn add_maintenance(
+    server: GameServer,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    reason: String,
+    created_by: String,
+) -> Result<MaintenanceWindow, AppError> {
+    create_maintenance_window(
+        db_pool()?,
+        NewMaintenanceWindow {
+            server: server.id().to_owned(),
+            starts_at,
+            ends_at,
+            reason,
+            created_by,
+        },
+    )
+    .await
+}
+
+/// The smallest warning already given for each schedule's next run
+#[derive(Default)]
+struct Warnings(HashMap<(Uuid, DateTime<Utc>), i64>);
+
+impl Warnings {
+    /// The warning to give at `now` for the run of `schedule` at `next`, if one is due and wasn't given
+    /// yet. Only the smallest due warning is given, so a schedule added shortly before it fires skips the
+    /// earlier ones.
+    fn due(&mut self, schedule: Uuid, next: DateTime<Utc>, now: DateTime<Utc>) -> Option<i64> {
+        let left = next - now;
+        let warning = WARNINGS
+            .into_iter()
+            .filter(|minutes| left <= Duration::minutes(*minutes))
+            .min()?;
+        if self
+            .0
+            .get(&(schedule, next))
+            .is_some_and(|warned| *warned <= warning)
+        {
+            return None;
+        }
+        self.0.insert((schedule, next), warning);
+        Some(warning)
+    }
+
+    /// Forget the warnings for runs that are due, including those of schedules deleted or changed since
+    fn prune(&mut self, now: DateTime<Utc>) {
+        self.0.retain(|(_, next), _| *next > now);
+    }
+}
+
+pub struct Scheduler {
+    http: Arc<serenity::Http>,
+    /// Refreshed from the global state every tick
+    announcement_channel: Option<serenity::ChannelId>,
+    warnings: Warnings,
+}
+
+impl Scheduler {
+    #[must_use]
+    pub fn new(http: Arc<serenity::Http>) -> Self {
+        Self {
+            http,
+            announcement_channel: None,
+            warnings: Warnings::default(),
+        }
+    }
+
+    /// Give any warnings that are due and run any restarts that are due
+    ///
+    /// # Errors
+    ///
+    /// Fails if the schedules cannot be loaded. Failures of a single schedule are logged.
+    pub async fn tick(&mut self) -> Result<(), AppError> {
+        self.announcement_channel = announcement_channel().await;
+        let now = Utc::now();
+        self.warnings.prune(now);
+        for schedule in get_restart_schedules(db_pool()?).await? {
+            let (Some(server), Some(next)) =
+                (GameServer::from_id(&schedule.server), next_run(&schedule))
+            else {
+                tracing::warn!("Restart schedule {} is invalid", schedule.id);
+                continue;
+            };
+            if let Err(e) = self.step(&schedule, server, next, now).await {
+                tracing::error!("Restart schedule {} for {server} failed: {e}", schedule.id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn step(
+        &mut self,
+        schedule: &RestartSchedule,
+        server: GameServer,
+        next: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        if now >= next {
+            set_restart_schedule_last_run(db_pool()?, schedule.id, now).await?;
+            if now - next > Duration::minutes(GRACE_MINUTES) {
+                tracing::warn!("Skipping {server} restart missed at {next}");
+                return Ok(());
+            }
+            self.restart(schedule, server);
+            return Ok(());
+        }
+        // a restart that only happens on an empty server has nobody to warn
+        if schedule.policy == RestartPolicy::WhenEmpty.to_string() {
+            return Ok(());
+        }
+        if self.warnings.due(schedule.id, next, now).is_none() {
+            return Ok(());
+        }
+        let left = next - now;
+        // a schedule added shortly before it fires starts at a later warning, so say how long is left
+        let minutes = (left.num_seconds() + 59) / 60;
+        let message = t!(
+            &plural(
+                "schedule.warning",
+                usize::try_from(minutes).unwrap_or_default()
+            ),
+            server = server.name(),
+            minutes = minutes
+        )
+        .into_owned();
+        let client = GAME_MANAGER
+            .get()
+            .ok_or_else(|| AppError::Other(String::from("game manager client not configured")))?;
+        if let Err(e) = client.broadcast(server, &message).await {
+            tracing::warn!("Warning players on {server} failed: {e}");
+        }
+        self.announce(&message).await;
+        Ok(())
+    }
+
+    /// Run the restart in the background, since waiting for players to leave can take a while
+    fn restart(&self, schedule: &RestartSchedule, server: GameServer) {
+        let policy = schedule.policy.parse().unwrap_or(RestartPolicy::Force);
+        let max_wait = std::time::Duration::from_secs(
+            u64::try_from(schedule.max_wait_minutes).unwrap_or_default() * 60,
+        );
+        let http = self.http.clone();
+        let channel = self.announcement_channel;
+        tokio::spawn(async move {
+            let reason = t!("schedule.reason");
+            let message = match restart_with_policy(server, policy, max_wait, &reason).await {
+                Ok(RestartOutcome::Restarted) => t!("schedule.restarted", server = server.name()),
+                Ok(RestartOutcome::Skipped(players)) => t!(
+                    &plural("schedule.skipped", players.len()),
+                    server = server.name(),
+                    count = players.len()
+                ),
+                Err(e) => {
+                    tracing::error!("Scheduled restart of {server} failed: {e}");
+                    t!("schedule.failed", server = server.name())
+                }
+            };
+            if let Some(channel) = channel
+                && let Err(e) = channel.say(&http, message).await
+            {
+                tracing::error!("Failed to announce restart: {e}");
+            }
+        });
+    }
+
+    async fn announce(&self, message: &str) {
+        if let Some(channel) = self.announcement_channel
+            && let Err(e) = channel.say(&self.http, message).await
+        {
+            tracing::error!("Failed to announce restart: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_15_5_and_1_minutes_before_once_each() {
+        let mut warnings = Warnings::default();
+        let schedule = Uuid::new_v4();
+        let next = "2026-10-19T04:00:00Z"
+            .parse::<DateTime<Utc>>()
+            .expect("invalid timestamp");
+        let at = |minutes: i64, seconds: i64| {
+            next - Duration::minutes(minutes) - Duration::seconds(seconds)
+        };
+        for (now, expected) in [
+            (at(16, 0), None),
+            (at(15, 1), None),
+            (at(15, 0), Some(15)),
+            (at(14, 59), None),
+            (at(6, 0), None),
+            (at(5, 0), Some(5)),
+            (at(4, 0), None),
+            (at(1, 0), Some(1)),
+            (at(0, 30), None),
+        ] {
+            assert_eq!(warnings.due(schedule, next, now), expected, "at {now}");
+        }
+    }
+
+    #[test]
+    fn a_late_schedule_starts_at_a_later_warning() {
+        let mut warnings = Warnings::default();
+        let schedule = Uuid::new_v4();
+        let now = "2026-10-19T04:00:00Z"
+            .parse::<DateTime<Utc>>()
+            .expect("invalid timestamp");
+        let next = now + Duration::minutes(3);
+        assert_eq!(warnings.due(schedule, next, now), Some(5));
+        assert_eq!(warnings.due(schedule, next, now), None);
+        assert_eq!(
+            warnings.due(schedule, next, next - Duration::seconds(59)),
+            Some(1)
+        );
+        // another schedule firing at the same time is warned about on its own
+        assert_eq!(warnings.due(Uuid::new_v4(), next, now), Some(5));
+    }
+
+    #[test]
+    fn forgets_warnings_once_the_restart_is_due() {
+        let mut warnings = Warnings::default();
+        let now = "2026-10-19T04:00:00Z"
+            .parse::<DateTime<Utc>>()
+            .expect("invalid timestamp");
+        let soon = now + Duration::minutes(1);
+        let later = now + Duration::minutes(10);
+        assert_eq!(warnings.due(Uuid::new_v4(), soon, now), Some(1));
+        assert_eq!(warnings.due(Uuid::new_v4(), later, now), Some(15));
+
+        warnings.prune(now);
+        assert_eq!(warnings.0.len(), 2);
+        warnings.prune(soon);
+        assert_eq!(
+            warnings.0.keys().map(|(_, next)| *next).collect::<Vec<_>>(),
+            [later]
+        );
+        warnings.prune(later + Duration::days(1));
+        assert!(warnings.0.is_empty());
+    }
+}
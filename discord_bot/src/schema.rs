@@ -1,5 +1,63 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    chat_bridge_channels (channel_id) {
+        channel_id -> Varchar,
+        enabled -> Bool,
+        updated_by -> Varchar,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    maintenance_windows (id) {
+        id -> Uuid,
+        server -> Varchar,
+        starts_at -> Timestamptz,
+        ends_at -> Timestamptz,
+        reason -> Varchar,
+        created_by -> Varchar,
+    }
+}
+
+diesel::table! {
+    minecraft_links (id) {
+        id -> Uuid,
+        guild_id -> Varchar,
+        discord_user_id -> Varchar,
+        minecraft_uuid -> Uuid,
+        minecraft_name -> Varchar,
+        status -> Varchar,
+        created_at -> Timestamptz,
+        decided_by -> Nullable<Varchar>,
+        platform -> Varchar,
+    }
+}
+
+diesel::table! {
+    play_sessions (id) {
+        id -> Uuid,
+        server -> Varchar,
+        player_id -> Varchar,
+        player_name -> Varchar,
+        platform -> Nullable<Varchar>,
+        joined_at -> Timestamptz,
+        left_at -> Nullable<Timestamptz>,
+        last_seen_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    population_samples (id) {
+        id -> Int8,
+        server -> Varchar,
+        sampled_at -> Timestamptz,
+        online -> Bool,
+        players -> Int4,
+        max_players -> Nullable<Int4>,
+    }
+}
+
 diesel::table! {
     reaction_messages (id) {
         guild_id -> Varchar,
@@ -9,6 +67,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    restart_schedules (id) {
+        id -> Uuid,
+        server -> Varchar,
+        cron -> Varchar,
+        created_by -> Varchar,
+        created_at -> Timestamptz,
+        last_run_at -> Nullable<Timestamptz>,
+        policy -> Varchar,
+        max_wait_minutes -> Int4,
+    }
+}
+
 diesel::table! {
     self_assignable_roles (id) {
         emoji -> Varchar,
@@ -18,4 +89,13 @@ diesel::table! {
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(reaction_messages, self_assignable_roles,);
+diesel::allow_tables_to_appear_in_same_query!(
+    chat_bridge_channels,
+    maintenance_windows,
+    minecraft_links,
+    play_sessions,
+    population_samples,
+    reaction_messages,
+    restart_schedules,
+    self_assignable_roles,
+);
@@ -1,5 +1,4 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 #[cfg(feature = "server")]
 use {crate::error::AppError, std::sync::Arc, tokio::sync::Mutex};
 
@@ -14,17 +13,23 @@ pub enum MessageType {
 
 impl std::fmt::Display for MessageType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{self:?}")
     }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct AppState {
+    /// Channel player joins and leaves are announced in, empty to disable announcements
+    pub announcement_channel_id: String,
     pub base_url: String,
+    /// Name the bot goes by in the Terms of Service and Privacy Policy
+    pub bot_name: String,
     pub discord_client_id: String,
     pub discord_client_secret: String,
     pub discord_public_key: String,
     pub discord_token: String,
+    /// Where questions about the legal pages and data requests go, see [`crate::legal`]
+    pub legal_contact: String,
     pub user_agent: String,
 }
 
@@ -33,5 +38,43 @@ pub fn set_global_state(state: Arc<Mutex<AppState>>) {
     let _ = GLOBAL_STATE.set(state);
 }
 
+// Database connection pool - this will be set by the main function
+#[cfg(feature = "server")]
+pub static DB_POOL: std::sync::OnceLock<deadpool_diesel::postgres::Pool> =
+    std::sync::OnceLock::new();
+
+#[cfg(feature = "server")]
+pub fn set_db_pool(pool: deadpool_diesel::postgres::Pool) {
+    let _ = DB_POOL.set(pool);
+}
+
+/// A handle to the database connection pool
+///
+/// # Errors
+///
+/// Fails if called before the pool has been set up by `main`.
+#[cfg(feature = "server")]
+pub fn db_pool() -> Result<deadpool_diesel::postgres::Pool, AppError> {
+    DB_POOL
+        .get()
+        .cloned()
+        .ok_or_else(|| AppError::Other(String::from("database pool not initialised")))
+}
+
+/// The channel player joins and leaves are announced in. It is read from the global state on every use
+/// so a reloaded configuration applies without restarting the poller.
+#[cfg(feature = "server")]
+pub async fn announcement_channel() -> Option<poise::serenity_prelude::ChannelId> {
+    GLOBAL_STATE
+        .get()?
+        .lock()
+        .await
+        .announcement_channel_id
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .map(poise::serenity_prelude::ChannelId::new)
+}
+
 #[cfg(feature = "server")]
 pub type Context<'a> = poise::Context<'a, Arc<Mutex<AppState>>, AppError>;
@@ -0,0 +1,161 @@
+//! Keeps the bot's long running tasks alive.
+//!
+//! The web server, the Discord client and the interval task each run under [`Supervisor::spawn`], which
+//! restarts a task that returns or panics before shutdown, waiting longer after each consecutive failure.
+//! Failures of all tasks count against one crash budget: once more than the allowed number happen within
+//! the window, the supervisor gives up and reports it, and `main` exits with an error so systemd can
+//! restart the whole process rather than leaving it running degraded. Each task's state is shown by the
+//! health endpoints.
+
+use {
+    crate::{error::AppError, health::HEALTH},
+    chrono::{DateTime, Utc},
+    serde::Serialize,
+    std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex, PoisonError},
+        time::{Duration, Instant},
+    },
+    tokio::sync::{mpsc, watch},
+};
+
+/// Delay before the first restart of a task, doubled after each consecutive failure
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A task that ran at least this long before failing starts again from the initial backoff
+const STABLE_AFTER: Duration = Duration::from_mins(5);
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Failed and waiting to be started again
+    Restarting,
+    /// Stopped for shutdown
+    Stopped,
+    /// Failed after the crash budget ran out
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct TaskStatus {
+    pub state: TaskState,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// When the task entered its current state
+    pub since: DateTime<Utc>,
+}
+
+/// Allows `limit` failures within `window`
+struct CrashBudget {
+    limit: usize,
+    window: Duration,
+    crashes: VecDeque<Instant>,
+}
+
+impl CrashBudget {
+    /// Record a failure, returning whether the budget still allows a restart
+    fn record(&mut self, now: Instant) -> bool {
+        self.crashes.push_back(now);
+        while self
+            .crashes
+            .front()
+            .is_some_and(|crash| now.duration_since(*crash) > self.window)
+        {
+            self.crashes.pop_front();
+        }
+        self.crashes.len() <= self.limit
+    }
+}
+
+#[derive(Clone)]
+pub struct Supervisor {
+    shutdown: watch::Receiver<()>,
+    budget: Arc<Mutex<CrashBudget>>,
+    fatal: mpsc::Sender<String>,
+}
+
+impl Supervisor {
+    /// A supervisor allowing `limit` failures within `window` across all its tasks. Tasks stop restarting
+    /// once `shutdown` fires. When the budget runs out the reason is sent on the returned receiver.
+    #[must_use]
+    pub fn new(
+        shutdown: watch::Receiver<()>,
+        limit: usize,
+        window: Duration,
+    ) -> (Self, mpsc::Receiver<String>) {
+        let (fatal, fatal_rx) = mpsc::channel(1);
+        let supervisor = Self {
+            shutdown,
+            budget: Arc::new(Mutex::new(CrashBudget {
+                limit,
+                window,
+                crashes: VecDeque::new(),
+            })),
+            fatal,
+        };
+        (supervisor, fatal_rx)
+    }
+
+    fn shutting_down(&self) -> bool {
+        // an error means the sender is gone, which only happens on the way out
+        self.shutdown.has_changed().unwrap_or(true)
+    }
+
+    /// Run the future made by `task` until shutdown, restarting it with backoff whenever it returns or
+    /// panics before then. `task` is given a shutdown receiver to stop gracefully on.
+    pub fn spawn<F, Fut>(&self, name: &'static str, task: F) -> tokio::task::JoinHandle<()>
+    where
+        F: Fn(watch::Receiver<()>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            let mut restarts = 0;
+            loop {
+                HEALTH.set_task(name, TaskState::Running, restarts, None);
+                let started = Instant::now();
+                let result = tokio::spawn(task(supervisor.shutdown.clone())).await;
+                if supervisor.shutting_down() {
+                    HEALTH.set_task(name, TaskState::Stopped, restarts, None);
+                    return;
+                }
+                let error = match result {
+                    Ok(Ok(())) => String::from("exited"),
+                    Ok(Err(e)) => e.to_string(),
+                    Err(e) => format!("panicked: {e}"),
+                };
+                tracing::error!("Task {name} failed: {error}");
+                let allowed = supervisor
+                    .budget
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .record(Instant::now());
+                if !allowed {
+                    HEALTH.set_task(name, TaskState::Failed, restarts, Some(error.clone()));
+                    // only the first reason is read, the rest are dropped rather than waited on
+                    let _ = supervisor
+                        .fatal
+                        .try_send(format!("crash budget exhausted, {name} {error}"));
+                    return;
+                }
+                if started.elapsed() >= STABLE_AFTER {
+                    backoff = INITIAL_BACKOFF;
+                }
+                HEALTH.set_task(name, TaskState::Restarting, restarts, Some(error));
+                tracing::info!("Restarting {name} in {backoff:?}");
+                let mut shutdown = supervisor.shutdown.clone();
+                tokio::select! {
+                    () = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.changed() => {
+                        HEALTH.set_task(name, TaskState::Stopped, restarts, None);
+                        return;
+                    }
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                restarts += 1;
+            }
+        })
+    }
+}
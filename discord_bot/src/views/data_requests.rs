@@ -0,0 +1,42 @@
+use {
+    crate::{app::Route, components::use_locale},
+    dioxus::prelude::*,
+    rust_i18n::t,
+};
+
+/// How to get a copy of your data or have it deleted, linked from the Privacy Policy. The requests
+/// themselves are slash commands, so Discord has already confirmed who is asking.
+#[component]
+pub fn DataRequests() -> Element {
+    let locale = use_locale();
+    // the commands as Discord shows them to someone using this language
+    let command = |subcommand: &str| {
+        let privacy = t!("commands.privacy.name", locale = locale);
+        let key = format!("commands.privacy.{subcommand}.name");
+        let subcommand = t!(&key, locale = locale).into_owned();
+        format!("/{privacy} {subcommand}")
+    };
+    rsx! {
+        article {
+            class: "legal",
+            h1 { {t!("web.data_requests.title", locale = locale).into_owned()} }
+            p { {t!("web.data_requests.intro", locale = locale).into_owned()} }
+            h2 { {t!("web.data_requests.export.title", locale = locale).into_owned()} }
+            p {
+                {t!("web.data_requests.export.body", locale = locale, command = command("export")).into_owned()}
+            }
+            h2 { {t!("web.data_requests.delete.title", locale = locale).into_owned()} }
+            p {
+                {t!("web.data_requests.delete.body", locale = locale, command = command("delete")).into_owned()}
+            }
+            p {
+                {t!("web.data_requests.other", locale = locale).into_owned()}
+                " "
+                Link {
+                    to: Route::PrivacyPolicy {},
+                    {t!("web.nav.privacy_policy", locale = locale).into_owned()}
+                }
+            }
+        }
+    }
+}
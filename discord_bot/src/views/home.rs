@@ -1,18 +1,158 @@
-use dioxus::prelude::*;
+use {
+    crate::{components::use_locale, i18n::choice},
+    dioxus::prelude::*,
+    rust_i18n::t,
+    serde::{Deserialize, Serialize},
+};
+
+/// How often the status cards are refreshed, in milliseconds
+#[cfg(target_arch = "wasm32")]
+const REFRESH_INTERVAL_MS: u32 = 15_000;
+
+/// Wait until the status cards are due a refresh. Only the browser refreshes them, elsewhere this never
+/// returns.
+async fn refresh_delay() {
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::TimeoutFuture::new(REFRESH_INTERVAL_MS).await;
+    #[cfg(not(target_arch = "wasm32"))]
+    std::future::pending::<()>().await;
+}
+
+/// What the Home page shows for one game server
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServerCard {
+    /// `game_manager` id of the server
+    pub id: String,
+    pub name: String,
+    /// `None` when `game_manager` could not be asked
+    pub online: Option<bool>,
+    pub version: Option<String>,
+    pub players: Vec<String>,
+    pub max_players: Option<u32>,
+}
 
 /// The Home page component that will be rendered when the current route is `[Route::Home]`
 #[component]
 pub fn Home() -> Element {
+    let locale = use_locale();
+    let initial = use_server_future(fetch_server_cards)?;
+    let mut latest = use_signal(|| None::<Vec<ServerCard>>);
+    use_future(move || async move {
+        loop {
+            refresh_delay().await;
+            if let Ok(cards) = fetch_server_cards().await {
+                latest.set(Some(cards));
+            }
+        }
+    });
+    let cards = latest().or_else(|| {
+        initial
+            .read()
+            .as_ref()
+            .and_then(|r| r.as_ref().ok().cloned())
+    });
     rsx! {
         // We can create elements inside the rsx macro with the element name followed by a block of attributes and children.
         div {
             // Attributes should be defined in the element before any children
             id: "hero",
+            div {
+                id: "servers",
+                if let Some(cards) = cards {
+                    for card in cards {
+                        StatusCard { key: "{card.id}", card }
+                    }
+                } else {
+                    p { {t!("web.home.unavailable", locale = locale).into_owned()} }
+                }
+            }
             // After all attributes are defined, we can define child elements and components
             div { id: "links",
                 // The RSX macro also supports text nodes surrounded by quotes
-                a { href: "https://packwiz.toyvo.dev", "Minecraft Modpack" }
+                a { href: "https://packwiz.toyvo.dev", {t!("web.home.modpack", locale = locale).into_owned()} }
             }
         }
     }
 }
+
+#[component]
+fn StatusCard(card: ServerCard) -> Element {
+    let locale = use_locale();
+    // the state is also styled on, so it stays in English there
+    let state = match card.online {
+        Some(true) => "online",
+        Some(false) => "offline",
+        None => "unknown",
+    };
+    let key = format!("web.home.{state}");
+    let label = t!(&key, locale = locale).into_owned();
+    let count = card.max_players.map_or_else(
+        || {
+            t!(
+                "web.home.players",
+                locale = locale,
+                count = card.players.len()
+            )
+        },
+        |max| {
+            t!(
+                "web.home.players_of",
+                locale = locale,
+                count = card.players.len(),
+                max = max
+            )
+        },
+    );
+    rsx! {
+        div {
+            class: "server-card",
+            "data-state": state,
+            h2 { {choice(&card.name, locale)} }
+            p { class: "server-state", {label} }
+            if let Some(version) = card.version {
+                p { {t!("web.home.version", locale = locale, version = version).into_owned()} }
+            }
+            if card.online == Some(true) {
+                p { "{count}" }
+                if !card.players.is_empty() {
+                    ul {
+                        for player in card.players {
+                            li { key: "{player}", {player} }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Status of every game server as of the poller's last tick. A server that could not be polled is
+/// reported as unknown rather than failing the whole page.
+#[allow(clippy::unused_async)] // server functions have to be async
+#[server]
+async fn fetch_server_cards() -> Result<Vec<ServerCard>, ServerFnError> {
+    use {
+        crate::{
+            game_manager::{GameServer, Player},
+            poller::cached_status,
+        },
+        poise::ChoiceParameter,
+    };
+
+    Ok(GameServer::ALL
+        .into_iter()
+        .map(|server| {
+            let status = cached_status(server);
+            ServerCard {
+                id: server.id().to_owned(),
+                name: server.name().to_owned(),
+                online: status.as_ref().map(|status| status.online),
+                version: status.as_ref().and_then(|status| status.version.clone()),
+                players: status.as_ref().map_or_else(Vec::new, |status| {
+                    status.players.iter().map(Player::display_name).collect()
+                }),
+                max_players: status.and_then(|status| status.max_players),
+            }
+        })
+        .collect())
+}
@@ -1,38 +1,46 @@
-use {chrono::prelude::*, dioxus::prelude::*, std::time::Duration};
+use {
+    crate::{components::use_locale, i18n::choice},
+    chrono::prelude::*,
+    dioxus::prelude::*,
+    rust_i18n::t,
+    std::time::Duration,
+};
 
-const VALID_SERVICES: [&str; 3] = [
-    "arion-minecraft-modded.service",
-    "arion-minecraft-geyser.service",
-    "arion-terraria.service",
+/// Server ids known to `game_manager` and their display names
+const VALID_SERVERS: [(&str, &str); 3] = [
+    ("minecraft_modded", "Modded Minecraft"),
+    ("minecraft_geyser", "Java/Bedrock Minecraft"),
+    ("terraria", "Terraria"),
 ];
 
 #[component]
 pub fn Logs() -> Element {
+    let locale = use_locale();
     let mut logs = use_signal(String::new);
-    let unit = use_signal(|| String::from("arion-minecraft-modded.service"));
+    let server = use_signal(|| String::from("minecraft_modded"));
     let now = Utc::now();
     let since = now - Duration::from_secs(3600);
     let until = now;
     rsx! {
         form {
             label {
-                r#for: "unit-select",
-                "Unit"
+                r#for: "server-select",
+                {t!("web.logs.server", locale = locale).into_owned()}
             }
             select {
-                id: "unit-select",
-                name: "unit",
-                for service in VALID_SERVICES {
+                id: "server-select",
+                name: "server",
+                for (id, name) in VALID_SERVERS {
                     option {
-                        value: service,
-                        selected: service == unit.read().as_str(),
-                        {service}
+                        value: id,
+                        selected: id == server.read().as_str(),
+                        {choice(name, locale)}
                     }
                 }
             }
             label {
                 r#for: "since-input",
-                "Since"
+                {t!("web.logs.since", locale = locale).into_owned()}
             }
             input {
                 id: "since-input",
@@ -42,7 +50,7 @@ pub fn Logs() -> Element {
             }
             label {
                 r#for: "until-input",
-                "Until"
+                {t!("web.logs.until", locale = locale).into_owned()}
             }
             input {
                 id: "until-input",
@@ -51,16 +59,16 @@ pub fn Logs() -> Element {
                 value: until.format("%Y-%m-%dT%H:%M:%S").to_string(),
             }
             small {
-                "Note: time shown in UTC"
+                {t!("web.logs.utc", locale = locale).into_owned()}
             }
             button {
                 onclick: move |_| async move {
-                    if let Ok(new_logs) = fetch_logs(unit.to_string(), since.to_rfc3339(), until.to_rfc3339()).await {
+                    if let Ok(new_logs) = fetch_logs(server.to_string(), since.to_rfc3339(), until.to_rfc3339()).await {
                         logs.set(new_logs);
                     }
                 },
                 r#type: "button",
-                "Fetch Logs"
+                {t!("web.logs.fetch", locale = locale).into_owned()}
             }
         }
         pre {
@@ -70,20 +78,20 @@ pub fn Logs() -> Element {
     }
 }
 
+/// Logs are read on the game host by `game_manager`, so the dashboard can run elsewhere
 #[server]
-async fn fetch_logs(unit: String, since: String, until: String) -> Result<String, ServerFnError> {
-    if !VALID_SERVICES.contains(&unit.as_str()) {
-        return Err(ServerFnError::Args(String::from("invalid unit")));
-    }
-    let journalctl_args = [
-        "--utc",
-        "-u",
-        unit.as_str(),
-        "-S",
-        since.as_str(),
-        "-U",
-        until.as_str(),
-    ];
-    tracing::info!("fetching logs for unit: {unit}");
-    Ok(journalctl_args.join(" "))
+async fn fetch_logs(server: String, since: String, until: String) -> Result<String, ServerFnError> {
+    use crate::game_manager::{GAME_MANAGER, GameServer};
+
+    let Some(game_server) = GameServer::ALL.into_iter().find(|s| s.id() == server) else {
+        return Err(ServerFnError::Args(String::from("invalid server")));
+    };
+    let client = GAME_MANAGER
+        .get()
+        .ok_or_else(|| ServerFnError::new("game manager client not configured"))?;
+    tracing::info!("fetching logs for server: {server}");
+    client
+        .logs(game_server, &since, &until)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
 }
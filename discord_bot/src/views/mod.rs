@@ -8,12 +8,21 @@
 //! The [`Navbar`] component will be rendered on all pages of our app since every page is under the layout. The layout defines
 //! a common wrapper around all child routes.
 
+mod data_requests;
+pub use data_requests::DataRequests;
+
 mod home;
 pub use home::Home;
 
 mod logs;
 pub use logs::Logs;
 
+mod playtime;
+pub use playtime::Playtime;
+
+mod population;
+pub use population::Population;
+
 mod privacy_policy;
 pub use privacy_policy::PrivacyPolicy;
 
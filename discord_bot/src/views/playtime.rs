@@ -0,0 +1,92 @@
+use {
+    crate::{
+        components::use_locale,
+        playtime::{PlayerPlaytime, format_duration},
+    },
+    dioxus::prelude::*,
+    rust_i18n::t,
+};
+
+/// Number of players shown on the weekly leaderboard
+const LEADERBOARD_SIZE: usize = 10;
+
+#[component]
+pub fn Playtime() -> Element {
+    let locale = use_locale();
+    let players = use_server_future(fetch_playtime)?;
+    let players = match &*players.read() {
+        Some(Ok(players)) => players.clone(),
+        Some(Err(e)) => {
+            return rsx! { p { {t!("web.playtime.failed", locale = locale, error = e).into_owned()} } };
+        }
+        None => return rsx! { p { {t!("web.loading", locale = locale).into_owned()} } },
+    };
+    let mut leaderboard: Vec<_> = players
+        .iter()
+        .filter(|p| p.week_seconds > 0)
+        .cloned()
+        .collect();
+    leaderboard.sort_by_key(|p| std::cmp::Reverse(p.week_seconds));
+    leaderboard.truncate(LEADERBOARD_SIZE);
+    rsx! {
+        h2 { {t!("web.playtime.this_week", locale = locale).into_owned()} }
+        if leaderboard.is_empty() {
+            p { {t!("playtime.nobody", locale = locale).into_owned()} }
+        } else {
+            ol {
+                for player in leaderboard {
+                    li {
+                        key: "{player.name}",
+                        "{player.name}: {format_duration(player.week_seconds)}"
+                    }
+                }
+            }
+        }
+        h2 { {t!("web.playtime.all_time", locale = locale).into_owned()} }
+        table {
+            thead {
+                tr {
+                    th { {t!("web.playtime.player", locale = locale).into_owned()} }
+                    th { {t!("web.playtime.total", locale = locale).into_owned()} }
+                    th { {t!("web.playtime.this_week", locale = locale).into_owned()} }
+                    th { {t!("web.playtime.last_seen", locale = locale).into_owned()} }
+                }
+            }
+            tbody {
+                for player in players {
+                    tr {
+                        key: "{player.name}",
+                        td { "{player.name}" }
+                        td { {format_duration(player.total_seconds)} }
+                        td { {format_duration(player.week_seconds)} }
+                        td {
+                            {
+                                player.last_seen.map_or_else(
+                                    || t!("web.playtime.online_now", locale = locale).into_owned(),
+                                    |last_seen| last_seen.format("%Y-%m-%d %H:%M UTC").to_string(),
+                                )
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Playtime of every player seen, most played first
+#[server]
+async fn fetch_playtime() -> Result<Vec<PlayerPlaytime>, ServerFnError> {
+    use crate::{
+        playtime::{summarize, week_start},
+        queries::get_play_sessions_since,
+        state::db_pool,
+    };
+
+    let now = chrono::Utc::now();
+    let pool = db_pool().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let sessions = get_play_sessions_since(pool, None)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(summarize(&sessions, now, week_start(now)))
+}
@@ -0,0 +1,194 @@
+use {
+    crate::{
+        components::use_locale,
+        i18n::choice,
+        population::{PopulationChart, PopulationRange, PopulationSeries},
+    },
+    dioxus::prelude::*,
+    rust_i18n::t,
+};
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 300.0;
+/// Room left of and below the plot for axis labels
+const MARGIN: f64 = 40.0;
+const COLORS: [&str; 3] = ["#4e79a7", "#f28e2b", "#59a14f"];
+
+#[component]
+pub fn Population() -> Element {
+    let locale = use_locale();
+    let mut range = use_signal(PopulationRange::default);
+    let chart = use_server_future(move || fetch_population(range()))?;
+    rsx! {
+        div {
+            for option in PopulationRange::ALL {
+                button {
+                    r#type: "button",
+                    disabled: option == range(),
+                    onclick: move |_| range.set(option),
+                    {option.label(locale)}
+                }
+            }
+        }
+        match &*chart.read() {
+            Some(Ok(chart)) => rsx! { Chart { chart: chart.clone() } },
+            Some(Err(e)) => rsx! { p { {t!("web.population.failed", locale = locale, error = e).into_owned()} } },
+            None => rsx! { p { {t!("web.loading", locale = locale).into_owned()} } },
+        }
+    }
+}
+
+/// Split a series into runs of consecutive buckets so gaps in the data are not drawn across
+fn runs(series: &PopulationSeries, bucket_seconds: i64) -> Vec<Vec<(i64, i32)>> {
+    let mut runs: Vec<Vec<(i64, i32)>> = vec![];
+    for point in &series.points {
+        let at = point.at.timestamp();
+        match runs.last_mut() {
+            Some(run)
+                if run
+                    .last()
+                    .is_some_and(|(last, _)| at - last <= bucket_seconds) =>
+            {
+                run.push((at, point.players));
+            }
+            _ => runs.push(vec![(at, point.players)]),
+        }
+    }
+    runs
+}
+
+#[component]
+fn Chart(chart: PopulationChart) -> Element {
+    let locale = use_locale();
+    let since = chart.since.timestamp();
+    let span = (chart.until.timestamp() - since).max(1);
+    let max_players = chart
+        .series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|p| p.players))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let plot_width = WIDTH - MARGIN;
+    let plot_height = HEIGHT - MARGIN;
+    #[allow(clippy::cast_precision_loss)]
+    let x = move |at: i64| ((at - since) as f64 / span as f64).mul_add(plot_width, MARGIN);
+    let y = move |players: i32| {
+        (f64::from(players) / f64::from(max_players)).mul_add(-plot_height, plot_height)
+    };
+    let ticks: std::collections::BTreeSet<i32> = (0..=4).map(|i| max_players * i / 4).collect();
+    // (server, colour, polyline points, first point) for each run
+    let lines = chart
+        .series
+        .iter()
+        .zip(COLORS.iter().cycle())
+        .flat_map(|(series, color)| {
+            runs(series, chart.bucket_seconds)
+                .into_iter()
+                .map(move |run| {
+                    let points: Vec<_> = run
+                        .iter()
+                        .map(|(at, players)| (x(*at), y(*players)))
+                        .collect();
+                    let polyline = points
+                        .iter()
+                        .map(|(x, y)| format!("{x:.1},{y:.1}"))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    (series.server.clone(), *color, polyline, points[0])
+                })
+        });
+    let format = if span > 2 * 24 * 3600 {
+        "%m-%d"
+    } else {
+        "%H:%M"
+    };
+    rsx! {
+        svg {
+            width: "{WIDTH}",
+            height: "{HEIGHT}",
+            view_box: "0 0 {WIDTH} {HEIGHT}",
+            role: "img",
+            for players in ticks {
+                line {
+                    x1: "{MARGIN}",
+                    x2: "{WIDTH}",
+                    y1: "{y(players)}",
+                    y2: "{y(players)}",
+                    stroke: "#ccc",
+                }
+                text {
+                    x: "{MARGIN - 6.0}",
+                    y: "{y(players) + 4.0}",
+                    text_anchor: "end",
+                    font_size: "12",
+                    "{players}"
+                }
+            }
+            text {
+                x: "{MARGIN}",
+                y: "{HEIGHT - 12.0}",
+                font_size: "12",
+                {chart.since.format(format).to_string()}
+            }
+            text {
+                x: "{WIDTH}",
+                y: "{HEIGHT - 12.0}",
+                text_anchor: "end",
+                font_size: "12",
+                {chart.until.format(format).to_string()}
+            }
+            for (server, color, points, (cx, cy)) in lines {
+                if points.contains(' ') {
+                    polyline {
+                        key: "{server}-{points}",
+                        points,
+                        fill: "none",
+                        stroke: color,
+                        stroke_width: "2",
+                    }
+                } else {
+                    // a lone bucket has no line to draw, so mark it with a dot
+                    circle {
+                        key: "{server}-{points}",
+                        cx: "{cx:.1}",
+                        cy: "{cy:.1}",
+                        r: "2",
+                        fill: color,
+                    }
+                }
+            }
+        }
+        ul {
+            for (series, color) in chart.series.iter().zip(COLORS.iter().cycle()) {
+                li {
+                    key: "{series.server}",
+                    span { color: *color, "■ " }
+                    {choice(&series.name, locale)}
+                }
+            }
+        }
+        small { {t!("web.times_in_utc", locale = locale).into_owned()} }
+    }
+}
+
+/// Peak player counts per server over the range, bucketed so each series stays small
+#[server]
+async fn fetch_population(range: PopulationRange) -> Result<PopulationChart, ServerFnError> {
+    use crate::{population::bucketize, queries::get_population_samples_since, state::db_pool};
+
+    let until = chrono::Utc::now();
+    let since = until
+        - chrono::Duration::from_std(range.duration())
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let pool = db_pool().map_err(|e| ServerFnError::new(e.to_string()))?;
+    let samples = get_population_samples_since(pool, since)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(PopulationChart {
+        since,
+        until,
+        bucket_seconds: i64::try_from(range.bucket().as_secs()).unwrap_or(i64::MAX),
+        series: bucketize(&samples, since, range.bucket()),
+    })
+}
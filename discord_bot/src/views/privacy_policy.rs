@@ -1,10 +1,23 @@
-use dioxus::prelude::*;
+use {
+    crate::{
+        app::Route,
+        components::{LegalPage, use_locale},
+        legal::LegalDocument,
+    },
+    dioxus::prelude::*,
+    rust_i18n::t,
+};
 
 #[component]
 pub fn PrivacyPolicy() -> Element {
+    let locale = use_locale();
     rsx! {
-        div {
-            "Privacy Policy"
+        LegalPage { document: LegalDocument::PrivacyPolicy }
+        p {
+            Link {
+                to: Route::DataRequests {},
+                {t!("web.legal.data_requests", locale = locale).into_owned()}
+            }
         }
     }
 }
@@ -1,10 +1,11 @@
-use dioxus::prelude::*;
+use {
+    crate::{components::LegalPage, legal::LegalDocument},
+    dioxus::prelude::*,
+};
 
 #[component]
 pub fn TermsOfService() -> Element {
     rsx! {
-        div {
-            "Terms of Service"
-        }
+        LegalPage { document: LegalDocument::TermsOfService }
     }
 }
@@ -74,6 +74,13 @@
                   DISCORD_TOKEN
                 '';
               };
+              config_file = lib.mkOption {
+                type = lib.types.nullOr lib.types.path;
+                default = null;
+                description = ''
+                  Path to a TOML config file, settings in the environment take precedence over it
+                '';
+              };
               env = lib.mkOption {
                 type = lib.types.attrs;
                 default = { };
@@ -106,6 +113,7 @@
                   script = ''
                     export $(cat ${cfg.env_file} | xargs)
                     export RUST_BACKTRACE=full
+                    ${lib.optionalString (cfg.config_file != null) "export DISCORD_BOT_CONFIG=${cfg.config_file}"}
                     ${lib.concatStringsSep "\n" (
                       lib.mapAttrsToList (name: value: "export ${name}=${toString value}") cfg.env
                     )}
@@ -0,0 +1,443 @@
+use {
+    crate::{AppState, KEYS, error::AppError, keys::KeyStore},
+    axum::{
+        Json,
+        extract::{FromRequestParts, State, rejection::JsonRejection},
+        http::{StatusCode, request::Parts},
+    },
+    axum_extra::headers::{Authorization, HeaderMapExt, authorization::Bearer},
+    jsonwebtoken::{Algorithm, Header, Validation, decode, decode_header, encode, jwk::JwkSet},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::HashMap,
+        path::PathBuf,
+        sync::{Arc, OnceLock},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    tokio::sync::Mutex,
+};
+
+/// Access tokens are short lived, clients are expected to refresh them
+pub const ACCESS_TOKEN_TTL: usize = 5 * 60;
+/// Refresh tokens let a long-running client such as the bot keep its session without re-sending its secret
+pub const REFRESH_TOKEN_TTL: usize = 30 * 24 * 60 * 60;
+
+pub static REVOCATIONS: OnceLock<Revocations> = OnceLock::new();
+
+fn now() -> usize {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as usize)
+        .unwrap_or_default()
+}
+
+/// Token ids (`jti`) that have been revoked, with the expiry of the token they belong to.
+///
+/// Entries are dropped once the token would have expired anyway. The list is written to disk on every
+/// change so a revoked refresh token stays revoked across restarts.
+pub struct Revocations {
+    /// Where the list is persisted, kept in memory only without one
+    path: Option<PathBuf>,
+    revoked: std::sync::Mutex<HashMap<String, usize>>,
+}
+
+impl Revocations {
+    pub fn load(path: PathBuf) -> Self {
+        let revoked = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            revoked: std::sync::Mutex::new(revoked),
+        }
+    }
+
+    /// A list that is never written to disk, for tests
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            revoked: std::sync::Mutex::default(),
+        }
+    }
+
+    /// Revoke a token, returning whether it wasn't revoked already. Checking and revoking happen under
+    /// one lock, so of two requests racing to use the same refresh token only one wins.
+    pub fn revoke(&self, jti: &str, exp: usize) -> bool {
+        let mut revoked = self.revoked.lock().expect("revocation lock poisoned");
+        let now = now();
+        revoked.retain(|_, exp| *exp > now);
+        if revoked.insert(jti.to_owned(), exp).is_some() {
+            return false;
+        }
+        let Some(path) = &self.path else {
+            return true;
+        };
+        match serde_json::to_string(&*revoked) {
+            Ok(contents) => {
+                if let Err(e) = std::fs::write(path, contents) {
+                    tracing::error!("Failed to persist revoked tokens: {e}");
+                }
+            }
+            Err(e) => tracing::error!("Failed to serialize revoked tokens: {e}"),
+        }
+        true
+    }
+
+    pub fn is_revoked(&self, jti: &str) -> bool {
+        self.revoked
+            .lock()
+            .expect("revocation lock poisoned")
+            .contains_key(jti)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenUse {
+    Access,
+    Refresh,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub aud: String,         // Optional. Audience
+    pub exp: usize, // Required (validate_exp defaults to true in validation). Expiration time (as UTC timestamp)
+    pub iat: usize, // Optional. Issued at (as UTC timestamp)
+    pub iss: String, // Optional. Issuer
+    pub jti: String, // Optional. Token id, used for revocation
+    pub nbf: usize, // Optional. Not Before (as UTC timestamp)
+    pub scope: String, // Optional. Space separated list of granted scopes
+    pub sub: String, // Optional. Subject (whom token refers to)
+    pub token_use: TokenUse, // Private. Whether this is an access or a refresh token
+}
+
+impl Claims {
+    fn new(state: &AppState, sub: &str, scope: &str, token_use: TokenUse) -> Self {
+        let now = now();
+        let ttl = match token_use {
+            TokenUse::Access => ACCESS_TOKEN_TTL,
+            TokenUse::Refresh => REFRESH_TOKEN_TTL,
+        };
+        Self {
+            aud: state.audience.clone(),
+            exp: now + ttl,
+            iat: now,
+            iss: state.issuer.clone(),
+            jti: uuid::Uuid::new_v4().to_string(),
+            nbf: now,
+            scope: scope.to_owned(),
+            sub: sub.to_owned(),
+            token_use,
+        }
+    }
+
+    fn encode(&self) -> Result<String, AppError> {
+        KEYS.get()
+            .and_then(|keys| {
+                keys.signing_key(|kid, key| {
+                    let mut header = Header::new(Algorithm::RS256);
+                    header.kid = Some(kid.to_owned());
+                    encode(&header, self, key)
+                })
+            })
+            .ok_or(AppError::TokenCreation)?
+            .map_err(|_| AppError::TokenCreation)
+    }
+
+    pub fn require_scope(&self, scope: &'static str) -> Result<(), AppError> {
+        if self
+            .scope
+            .split_whitespace()
+            .any(|granted| granted == scope)
+        {
+            Ok(())
+        } else {
+            Err(AppError::MissingScope(scope))
+        }
+    }
+
+    /// Validate a token against the active keys, the configured issuer and audience, its intended use and
+    /// the revocation list.
+    pub fn decode(token: &str, state: &AppState, token_use: TokenUse) -> Result<Self, AppError> {
+        // Look up the key the token was signed with
+        let kid = decode_header(token)
+            .ok()
+            .and_then(|header| header.kid)
+            .ok_or_else(|| AppError::InvalidToken(String::from("malformed token header")))?;
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&state.audience]);
+        validation.set_issuer(&[&state.issuer]);
+        let claims = KEYS
+            .get()
+            .and_then(|keys| keys.decoding_key(&kid, |key| decode::<Self>(token, key, &validation)))
+            .ok_or_else(|| AppError::InvalidToken(String::from("unknown signing key")))?
+            .map_err(|e| AppError::InvalidToken(e.to_string()))?
+            .claims;
+
+        if claims.token_use != token_use {
+            return Err(AppError::InvalidToken(String::from("wrong token type")));
+        }
+        if REVOCATIONS
+            .get()
+            .is_some_and(|revocations| revocations.is_revoked(&claims.jti))
+        {
+            return Err(AppError::InvalidToken(String::from(
+                "token has been revoked",
+            )));
+        }
+        Ok(claims)
+    }
+}
+
+impl FromRequestParts<Arc<Mutex<AppState>>> for Claims {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<Mutex<AppState>>,
+    ) -> Result<Self, Self::Rejection> {
+        // Extract the token from the authorization header
+        let bearer = parts
+            .headers
+            .typed_get::<Authorization<Bearer>>()
+            .ok_or_else(|| AppError::InvalidToken(String::from("missing bearer token")))?;
+        let state = state.lock().await.clone();
+        Self::decode(bearer.token(), &state, TokenUse::Access)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthBody {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: usize,
+    pub refresh_token: String,
+}
+
+impl AuthBody {
+    /// Issue a fresh access and refresh token pair for `sub` carrying `scope`
+    fn issue(state: &AppState, sub: &str, scope: &str) -> Result<Self, AppError> {
+        Ok(AuthBody {
+            access_token: Claims::new(state, sub, scope, TokenUse::Access).encode()?,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_TTL,
+            refresh_token: Claims::new(state, sub, scope, TokenUse::Refresh).encode()?,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthPayload {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshPayload {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokePayload {
+    pub token: String,
+}
+
+pub async fn jwks() -> Json<JwkSet> {
+    Json(
+        KEYS.get()
+            .map(KeyStore::jwks)
+            .unwrap_or(JwkSet { keys: vec![] }),
+    )
+}
+
+pub async fn authorize(
+    State(state): State<Arc<Mutex<AppState>>>,
+    payload: Result<Json<AuthPayload>, JsonRejection>,
+) -> Result<Json<AuthBody>, AppError> {
+    let Json(payload) = payload?;
+    // Check if the user sent the credentials
+    if payload.client_id.is_empty() || payload.client_secret.is_empty() {
+        return Err(AppError::InvalidRequest(String::from(
+            "missing credentials",
+        )));
+    }
+    let state = state.lock().await.clone();
+    // An unconfigured client secret never matches, so the API stays closed until credentials are set
+    if state.client_secret.is_empty()
+        || payload.client_id != state.client_id
+        || payload.client_secret != state.client_secret
+    {
+        return Err(AppError::WrongCredentials);
+    }
+    Ok(Json(AuthBody::issue(
+        &state,
+        &payload.client_id,
+        &state.client_scopes,
+    )?))
+}
+
+/// Exchange a refresh token for a new token pair. The presented refresh token is revoked so each one can
+/// only be used once. The new pair carries the token's scopes that the client is still granted, so
+/// scopes taken away from the client don't live on in its refresh tokens.
+pub async fn refresh(
+    State(state): State<Arc<Mutex<AppState>>>,
+    payload: Result<Json<RefreshPayload>, JsonRejection>,
+) -> Result<Json<AuthBody>, AppError> {
+    let Json(payload) = payload?;
+    let state = state.lock().await.clone();
+    let claims = Claims::decode(&payload.refresh_token, &state, TokenUse::Refresh)?;
+    // decode only saw the token unrevoked, a concurrent refresh may have used it since
+    if REVOCATIONS
+        .get()
+        .is_some_and(|revocations| !revocations.revoke(&claims.jti, claims.exp))
+    {
+        return Err(AppError::InvalidToken(String::from(
+            "token has been revoked",
+        )));
+    }
+    let scope = granted_scope(&claims.scope, &state.client_scopes);
+    Ok(Json(AuthBody::issue(&state, &claims.sub, &scope)?))
+}
+
+/// The scopes of `scope` that are also in `granted`, both space separated
+fn granted_scope(scope: &str, granted: &str) -> String {
+    let granted: Vec<_> = granted.split_whitespace().collect();
+    scope
+        .split_whitespace()
+        .filter(|scope| granted.contains(scope))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Revoke an access or refresh token. Like RFC 7009 this succeeds for tokens that are already invalid, so
+/// the response does not reveal anything about the token.
+pub async fn revoke(
+    State(state): State<Arc<Mutex<AppState>>>,
+    payload: Result<Json<RevokePayload>, JsonRejection>,
+) -> Result<StatusCode, AppError> {
+    let Json(payload) = payload?;
+    let state = state.lock().await.clone();
+    let claims = Claims::decode(&payload.token, &state, TokenUse::Refresh)
+        .or_else(|_| Claims::decode(&payload.token, &state, TokenUse::Access));
+    if let (Ok(claims), Some(revocations)) = (claims, REVOCATIONS.get()) {
+        revocations.revoke(&claims.jti, claims.exp);
+    }
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration, tempfile::TempDir};
+
+    const CLIENT_ID: &str = "discord_bot";
+    const CLIENT_SECRET: &str = "secret";
+
+    fn state(client_scopes: &str) -> Arc<Mutex<AppState>> {
+        KEYS.get_or_init(|| {
+            // the keys are kept in memory and never due for rotation, so their directory can go
+            let dir = TempDir::new().expect("creating a temporary directory failed");
+            KeyStore::load(
+                dir.path().to_path_buf(),
+                Duration::from_secs(24 * 60 * 60),
+                Duration::from_secs(60 * 60),
+                None,
+            )
+            .expect("loading the key store failed")
+        });
+        REVOCATIONS.get_or_init(Revocations::in_memory);
+        Arc::new(Mutex::new(AppState {
+            issuer: String::from("game_manager"),
+            audience: String::from("game_manager"),
+            client_id: String::from(CLIENT_ID),
+            client_secret: String::from(CLIENT_SECRET),
+            client_scopes: String::from(client_scopes),
+            ..AppState::default()
+        }))
+    }
+
+    async fn login(state: &Arc<Mutex<AppState>>) -> AuthBody {
+        let payload = AuthPayload {
+            client_id: String::from(CLIENT_ID),
+            client_secret: String::from(CLIENT_SECRET),
+        };
+        authorize(State(state.clone()), Ok(Json(payload)))
+            .await
+            .expect("authorizing failed")
+            .0
+    }
+
+    async fn refresh_with(
+        state: &Arc<Mutex<AppState>>,
+        refresh_token: &str,
+    ) -> Result<AuthBody, AppError> {
+        let payload = RefreshPayload {
+            refresh_token: refresh_token.to_owned(),
+        };
+        refresh(State(state.clone()), Ok(Json(payload)))
+            .await
+            .map(|Json(body)| body)
+    }
+
+    #[tokio::test]
+    async fn refresh_tokens_work_once() {
+        let state = state("servers:read");
+        let tokens = login(&state).await;
+        let refreshed = refresh_with(&state, &tokens.refresh_token)
+            .await
+            .expect("refreshing failed");
+        assert!(matches!(
+            refresh_with(&state, &tokens.refresh_token).await,
+            Err(AppError::InvalidToken(_))
+        ));
+        // the new refresh token works in turn
+        refresh_with(&state, &refreshed.refresh_token)
+            .await
+            .expect("refreshing with the new token failed");
+    }
+
+    #[tokio::test]
+    async fn revoked_refresh_tokens_are_rejected() {
+        let state = state("servers:read");
+        let tokens = login(&state).await;
+        let payload = RevokePayload {
+            token: tokens.refresh_token.clone(),
+        };
+        assert_eq!(
+            revoke(State(state.clone()), Ok(Json(payload)))
+                .await
+                .expect("revoking failed"),
+            StatusCode::OK
+        );
+        assert!(matches!(
+            refresh_with(&state, &tokens.refresh_token).await,
+            Err(AppError::InvalidToken(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn refresh_drops_scopes_taken_away_from_the_client() {
+        let state = state("servers:read servers:control logs:read");
+        let tokens = login(&state).await;
+        state.lock().await.client_scopes = String::from("logs:read servers:read backups:read");
+        let refreshed = refresh_with(&state, &tokens.refresh_token)
+            .await
+            .expect("refreshing failed");
+        let current = state.lock().await.clone();
+        for token in [refreshed.access_token, refreshed.refresh_token] {
+            let claims = Claims::decode(&token, &current, TokenUse::Access)
+                .or_else(|_| Claims::decode(&token, &current, TokenUse::Refresh))
+                .expect("decoding the refreshed token failed");
+            // scopes granted since are not added either
+            assert_eq!(claims.scope, "servers:read logs:read");
+        }
+    }
+
+    #[test]
+    fn granted_scope_keeps_only_granted_scopes() {
+        assert_eq!(granted_scope("a b c", "c a"), "a c");
+        assert_eq!(granted_scope("a  b", "b"), "b");
+        assert_eq!(granted_scope("a b", ""), "");
+        assert_eq!(granted_scope("", "a"), "");
+    }
+}
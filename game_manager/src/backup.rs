@@ -0,0 +1,625 @@
+//! Compressed world backups, stored per server under the backup directory.
+//!
+//! A backup is a `tar.gz` of the server's world directory named `<server>-<timestamp>.tar.gz`, so the
+//! directory listing is the only index. The game is told to flush its world to disk first: Minecraft with
+//! `save-off` and `save-all flush` over RCON, turning saving back on afterwards, and Terraria through the
+//! TShock world save endpoint. Old backups are pruned to the newest one per hour, day and week, keeping as
+//! many of each as configured. Restoring archives the world it replaces as a pre-restore backup, named
+//! `<server>-<timestamp>-pre-restore.tar.gz`, which is pruned separately.
+//!
+//! Archives are written under a `.partial` name and only renamed once `tar` succeeded, so an archive cut
+//! short is never listed, restored or counted towards retention. Leftovers are removed when pruning.
+
+use {
+    crate::{AppState, HTTP, auth::Claims, error::AppError, rcon, servers::ServerId},
+    axum::{
+        Json,
+        extract::{Path, State, rejection::PathRejection},
+        http::StatusCode,
+    },
+    chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc},
+    serde::Serialize,
+    std::{
+        collections::HashSet,
+        ffi::OsStr,
+        path::{Path as FsPath, PathBuf},
+        process::Output,
+        sync::{Arc, PoisonError},
+        time::{Duration, Instant},
+    },
+    tokio::{fs, process::Command, sync::Mutex},
+};
+
+/// Scope required to list backups
+pub const BACKUPS_READ: &str = "backups:read";
+/// Scope required to create and restore backups
+pub const BACKUPS_WRITE: &str = "backups:write";
+
+/// Format of the timestamp in backup file names, always UTC
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+pub const EXTENSION: &str = ".tar.gz";
+const PRE_RESTORE_SUFFIX: &str = "-pre-restore";
+/// Appended to an archive while it is being written
+const PARTIAL: &str = ".partial";
+/// How long `tar` may run, well within the request timeout so a stuck archive still lets the game save
+/// again
+const TAR_TIMEOUT: Duration = Duration::from_mins(20);
+
+/// Servers with a backup or restore in progress
+static BUSY: std::sync::Mutex<Vec<ServerId>> = std::sync::Mutex::new(vec![]);
+
+/// Marks a server busy until dropped, so two backups or restores of the same world never overlap
+pub struct BusyGuard(ServerId);
+
+impl BusyGuard {
+    pub fn acquire(id: ServerId) -> Result<Self, AppError> {
+        let mut busy = BUSY.lock().unwrap_or_else(PoisonError::into_inner);
+        if busy.contains(&id) {
+            return Err(AppError::Busy(format!(
+                "a backup or restore of {id} is already running"
+            )));
+        }
+        busy.push(id);
+        Ok(Self(id))
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        BUSY.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|id| *id != self.0);
+    }
+}
+
+/// Run `work` in a task of its own and wait for it. The work carries on if the request waiting for it is
+/// dropped, as when the client disconnects or the request times out, so a backup or restore is never
+/// abandoned halfway with the game stopped or not saving.
+pub async fn detached<T: Send + 'static>(
+    work: impl Future<Output = Result<T, AppError>> + Send + 'static,
+) -> Result<T, AppError> {
+    tokio::spawn(work).await?
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// The file name without its extension, e.g. `terraria-20261019T040000Z`
+    pub id: String,
+    pub server: ServerId,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+    /// Taken automatically of the world a restore replaced
+    pub pre_restore: bool,
+    /// How long creating the backup took, only known right after creating it
+    pub duration_ms: Option<u64>,
+}
+
+impl AppState {
+    /// The world directory of a server, if backups are configured for it
+    pub fn world_dir(&self, id: ServerId) -> Option<PathBuf> {
+        let dir = match id {
+            ServerId::MinecraftGeyser => &self.minecraft_geyser_world_dir,
+            ServerId::MinecraftModded => &self.minecraft_modded_world_dir,
+            ServerId::Terraria => &self.terraria_world_dir,
+        };
+        (!dir.is_empty()).then(|| PathBuf::from(dir))
+    }
+
+    fn backup_dir(&self, id: ServerId) -> PathBuf {
+        PathBuf::from(&self.backup_dir).join(id.to_string())
+    }
+}
+
+pub fn world_dir(state: &AppState, id: ServerId) -> Result<PathBuf, AppError> {
+    state
+        .world_dir(id)
+        .ok_or_else(|| AppError::NotFound(format!("{id} has no world directory configured")))
+}
+
+/// Parse the creation time out of a backup id
+fn created_at(id: ServerId, backup: &str) -> Option<DateTime<Utc>> {
+    let timestamp = backup.strip_prefix(&format!("{id}-"))?;
+    let timestamp = timestamp
+        .strip_suffix(PRE_RESTORE_SUFFIX)
+        .unwrap_or(timestamp);
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Backups of a server, newest first
+pub async fn list_backups(state: &AppState, id: ServerId) -> Result<Vec<BackupInfo>, AppError> {
+    let mut entries = match fs::read_dir(state.backup_dir(id)).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut backups = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(backup) = name.strip_suffix(EXTENSION) else {
+            continue;
+        };
+        let Some(created_at) = created_at(id, backup) else {
+            continue;
+        };
+        backups.push(BackupInfo {
+            id: backup.to_owned(),
+            server: id,
+            created_at,
+            size_bytes: entry.metadata().await?.len(),
+            pre_restore: backup.ends_with(PRE_RESTORE_SUFFIX),
+            duration_ms: None,
+        });
+    }
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+    Ok(backups)
+}
+
+/// Find a backup by id. Only ids from the listing are accepted, so the id can never name a path outside
+/// the backup directory.
+pub async fn find_backup(
+    state: &AppState,
+    id: ServerId,
+    backup: &str,
+) -> Result<BackupInfo, AppError> {
+    list_backups(state, id)
+        .await?
+        .into_iter()
+        .find(|b| b.id == backup)
+        .ok_or_else(|| AppError::NotFound(format!("no backup {backup} of {id}")))
+}
+
+pub fn archive_path(state: &AppState, backup: &BackupInfo) -> PathBuf {
+    state
+        .backup_dir(backup.server)
+        .join(format!("{}{EXTENSION}", backup.id))
+}
+
+/// Run `tar` with `args`, killing it if it takes longer than [`TAR_TIMEOUT`]
+pub async fn tar_output(args: &[&OsStr]) -> Result<Output, AppError> {
+    let output = Command::new("tar").args(args).kill_on_drop(true).output();
+    tokio::time::timeout(TAR_TIMEOUT, output)
+        .await
+        .map_err(|_| AppError::Command("tar".into(), format!("timed out after {TAR_TIMEOUT:?}")))?
+        .map_err(AppError::from)
+}
+
+/// Run `tar` with `args`, failing with its stderr if it does not succeed
+pub async fn tar(args: &[&OsStr]) -> Result<(), AppError> {
+    let output = tar_output(args).await?;
+    if !output.status.success() {
+        return Err(AppError::Command(
+            "tar".into(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Split a world directory into its parent and name, which is how it is stored in archives
+pub fn split_world(world: &FsPath) -> Result<(&FsPath, &OsStr), AppError> {
+    world.parent().zip(world.file_name()).ok_or_else(|| {
+        AppError::InvalidRequest(format!("{} is not a world directory", world.display()))
+    })
+}
+
+/// Tell the game to write its world to disk and stop writing until [`resume_saving`]. A server that is not
+/// running has nothing to flush, so failing to reach it is not an error.
+///
+/// ref: https://tshock.readme.io/reference/v2worldsave
+async fn pause_saving(state: &AppState, id: ServerId) -> Result<(), AppError> {
+    let result = match id {
+        ServerId::MinecraftGeyser | ServerId::MinecraftModded => {
+            match rcon::run(state, id, "save-off").await {
+                Ok(_) => rcon::run(state, id, "save-all flush").await.map(|_| ()),
+                Err(e) => Err(e),
+            }
+        }
+        ServerId::Terraria => HTTP
+            .get(format!("{}/v2/world/save", state.tshock_base_url))
+            .query(&[("token", state.tshock_token.as_str())])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .map_err(AppError::from),
+    };
+    match result {
+        Err(AppError::UpstreamUnreachable(e) | AppError::UpstreamTimeout(e)) => {
+            tracing::info!("Backing up {id} without saving, it is not reachable: {e}");
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+async fn resume_saving(state: &AppState, id: ServerId) {
+    if matches!(id, ServerId::MinecraftGeyser | ServerId::MinecraftModded)
+        && let Err(e) = rcon::run(state, id, "save-on").await
+    {
+        tracing::debug!("Turning saving back on for {id} failed: {e}");
+    }
+}
+
+/// Save the world and archive it, then prune old backups. Saving is turned back on whatever happens, as
+/// pausing it may fail after `save-off` went through. Requests run this [`detached`], so it is never
+/// dropped before saving is back on.
+pub async fn create_backup(state: &AppState, id: ServerId) -> Result<BackupInfo, AppError> {
+    let world = world_dir(state, id)?;
+    let _busy = BusyGuard::acquire(id)?;
+    let result = match pause_saving(state, id).await {
+        Ok(()) => archive_world(state, id, &world, false).await,
+        Err(e) => Err(e),
+    };
+    resume_saving(state, id).await;
+    let info = result?;
+    prune_backups(state, id).await?;
+    Ok(info)
+}
+
+/// Archive a world directory as it is on disk. Callers make sure the game is not writing to it.
+pub async fn archive_world(
+    state: &AppState,
+    id: ServerId,
+    world: &FsPath,
+    pre_restore: bool,
+) -> Result<BackupInfo, AppError> {
+    let started = Instant::now();
+    let created_at = Utc::now();
+    let backup = format!(
+        "{id}-{}{}",
+        created_at.format(TIMESTAMP_FORMAT),
+        if pre_restore { PRE_RESTORE_SUFFIX } else { "" }
+    );
+    let dir = state.backup_dir(id);
+    fs::create_dir_all(&dir).await?;
+    let archive = dir.join(format!("{backup}{EXTENSION}"));
+    let partial = dir.join(format!("{backup}{EXTENSION}{PARTIAL}"));
+    let (parent, name) = split_world(world)?;
+
+    let result = tar(&[
+        OsStr::new("-czf"),
+        partial.as_os_str(),
+        OsStr::new("-C"),
+        parent.as_os_str(),
+        name,
+    ])
+    .await;
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    fs::rename(&partial, &archive).await?;
+
+    let info = BackupInfo {
+        id: backup,
+        server: id,
+        created_at,
+        size_bytes: fs::metadata(&archive).await?.len(),
+        pre_restore,
+        duration_ms: Some(u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)),
+    };
+    tracing::info!(
+        "Backed up {id} to {} ({} bytes)",
+        archive.display(),
+        info.size_bytes
+    );
+    Ok(info)
+}
+
+/// The hour, day or week a backup falls in, as (year, ordinal day or ISO week, hour)
+type Bucket = (i32, u32, u32);
+
+/// The backups to keep: the newest in each of the last `hourly` hours, `daily` days and `weekly` ISO weeks
+/// that have a backup. `backups` must be newest first.
+pub fn retained(
+    backups: &[BackupInfo],
+    hourly: usize,
+    daily: usize,
+    weekly: usize,
+) -> HashSet<String> {
+    let mut keep = HashSet::new();
+    let mut bucket = |count: usize, key: &dyn Fn(&DateTime<Utc>) -> Bucket| {
+        let mut seen = HashSet::new();
+        for backup in backups {
+            if seen.len() >= count && !seen.contains(&key(&backup.created_at)) {
+                break;
+            }
+            if seen.insert(key(&backup.created_at)) {
+                keep.insert(backup.id.clone());
+            }
+        }
+    };
+    bucket(hourly, &|t| (t.year(), t.ordinal(), t.hour()));
+    bucket(daily, &|t| (t.year(), t.ordinal(), 0));
+    bucket(weekly, &|t| (t.iso_week().year(), t.iso_week().week(), 0));
+    keep
+}
+
+/// Delete the backups of a server that fall outside the retention policy, and archives left partial by a
+/// backup that never finished. Pre-restore backups are kept apart from the others so a restore never
+/// pushes a regular backup out. Callers hold the server's [`BusyGuard`], so no archive is being written.
+pub async fn prune_backups(state: &AppState, id: ServerId) -> Result<(), AppError> {
+    remove_partial(state, id).await?;
+    let (pre_restore, backups): (Vec<_>, Vec<_>) = list_backups(state, id)
+        .await?
+        .into_iter()
+        .partition(|b| b.pre_restore);
+    let mut keep = retained(
+        &backups,
+        state.backup_keep_hourly,
+        state.backup_keep_daily,
+        state.backup_keep_weekly,
+    );
+    keep.extend(
+        pre_restore
+            .iter()
+            .take(state.backup_keep_pre_restore)
+            .map(|b| b.id.clone()),
+    );
+    for backup in backups
+        .iter()
+        .chain(&pre_restore)
+        .filter(|b| !keep.contains(&b.id))
+    {
+        tracing::info!("Pruning backup {}", backup.id);
+        fs::remove_file(archive_path(state, backup)).await?;
+    }
+    Ok(())
+}
+
+async fn remove_partial(state: &AppState, id: ServerId) -> Result<(), AppError> {
+    let mut entries = match fs::read_dir(state.backup_dir(id)).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name().to_string_lossy().ends_with(PARTIAL) {
+            tracing::info!("Removing unfinished archive {}", entry.path().display());
+            fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Back up every server with a world directory, logging failures
+pub async fn backup_all(state: &AppState) {
+    for id in ServerId::ALL {
+        if state.world_dir(id).is_none() {
+            continue;
+        }
+        if let Err(e) = create_backup(state, id).await {
+            tracing::error!("Scheduled backup of {id} failed: {e}");
+        }
+    }
+}
+
+pub async fn list(
+    claims: Claims,
+    State(state): State<Arc<Mutex<AppState>>>,
+    id: Result<Path<ServerId>, PathRejection>,
+) -> Result<Json<Vec<BackupInfo>>, AppError> {
+    claims.require_scope(BACKUPS_READ)?;
+    let Path(id) = id?;
+    let state = state.lock().await.clone();
+    Ok(Json(list_backups(&state, id).await?))
+}
+
+pub async fn create(
+    claims: Claims,
+    State(state): State<Arc<Mutex<AppState>>>,
+    id: Result<Path<ServerId>, PathRejection>,
+) -> Result<(StatusCode, Json<BackupInfo>), AppError> {
+    claims.require_scope(BACKUPS_WRITE)?;
+    let Path(id) = id?;
+    tracing::info!("{} requested a backup of {id}", claims.sub);
+    let state = state.lock().await.clone();
+    let backup = detached(async move { create_backup(&state, id).await }).await?;
+    Ok((StatusCode::CREATED, Json(backup)))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tempfile::TempDir};
+
+    fn backup(created_at: &str) -> BackupInfo {
+        let created_at = created_at.parse().expect("invalid timestamp");
+        BackupInfo {
+            id: backup_id(created_at, false),
+            server: ServerId::Terraria,
+            created_at,
+            size_bytes: 0,
+            pre_restore: false,
+            duration_ms: None,
+        }
+    }
+
+    fn backup_id(created_at: DateTime<Utc>, pre_restore: bool) -> String {
+        format!(
+            "{}-{}{}",
+            ServerId::Terraria,
+            created_at.format(TIMESTAMP_FORMAT),
+            if pre_restore { PRE_RESTORE_SUFFIX } else { "" }
+        )
+    }
+
+    #[test]
+    fn retains_the_newest_backup_of_each_bucket() {
+        /// What is tested, the backups newest first, how many hourly, daily and weekly to keep and the
+        /// indices of the backups kept
+        type Case<'a> = (&'a str, &'a [&'a str], usize, usize, usize, &'a [usize]);
+        let cases: &[Case] = &[
+            (
+                "newest of each hour",
+                &[
+                    "2026-10-19T05:10:00Z",
+                    "2026-10-19T05:00:00Z",
+                    "2026-10-19T04:59:59Z",
+                    "2026-10-19T04:00:00Z",
+                    "2026-10-19T03:30:00Z",
+                ],
+                2,
+                0,
+                0,
+                &[0, 2],
+            ),
+            (
+                "newest of each day across midnight",
+                &[
+                    "2026-10-19T00:10:00Z",
+                    "2026-10-18T23:50:00Z",
+                    "2026-10-18T12:00:00Z",
+                    "2026-10-17T12:00:00Z",
+                ],
+                0,
+                2,
+                0,
+                &[0, 1],
+            ),
+            (
+                "days across the new year",
+                &["2027-01-01T00:00:00Z", "2026-12-31T23:59:59Z"],
+                0,
+                2,
+                0,
+                &[0, 1],
+            ),
+            (
+                "ISO weeks start on Monday",
+                &[
+                    "2026-10-19T00:00:00Z",
+                    "2026-10-18T23:59:59Z",
+                    "2026-10-12T00:00:00Z",
+                ],
+                0,
+                0,
+                2,
+                &[0, 1],
+            ),
+            (
+                "an ISO week spanning the new year is one week",
+                &[
+                    "2027-01-01T12:00:00Z",
+                    "2026-12-31T12:00:00Z",
+                    "2026-12-27T12:00:00Z",
+                ],
+                0,
+                0,
+                2,
+                &[0, 2],
+            ),
+            (
+                "buckets overlap",
+                &[
+                    "2026-10-19T05:00:00Z",
+                    "2026-10-19T04:00:00Z",
+                    "2026-10-18T04:00:00Z",
+                    "2026-10-11T04:00:00Z",
+                ],
+                1,
+                2,
+                3,
+                &[0, 2, 3],
+            ),
+            (
+                "keeping none",
+                &["2026-10-19T05:00:00Z", "2026-10-18T05:00:00Z"],
+                0,
+                0,
+                0,
+                &[],
+            ),
+            (
+                "fewer buckets than kept",
+                &["2026-10-19T05:00:00Z"],
+                24,
+                7,
+                4,
+                &[0],
+            ),
+        ];
+        for (what, created, hourly, daily, weekly, kept) in cases {
+            let backups: Vec<_> = created.iter().map(|t| backup(t)).collect();
+            let expected: HashSet<_> = kept.iter().map(|&i| backups[i].id.clone()).collect();
+            assert_eq!(
+                retained(&backups, *hourly, *daily, *weekly),
+                expected,
+                "{what}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn prunes_pre_restore_backups_separately() {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let state = AppState {
+            backup_dir: dir.path().to_string_lossy().into_owned(),
+            backup_keep_hourly: 1,
+            backup_keep_pre_restore: 1,
+            ..AppState::default()
+        };
+        let backups = state.backup_dir(ServerId::Terraria);
+        fs::create_dir_all(&backups)
+            .await
+            .expect("creating the backup directory failed");
+        let at = |t: &str| t.parse::<DateTime<Utc>>().expect("invalid timestamp");
+        let files = [
+            // the pre-restore backups are newer, but must not take the regular backup's place
+            backup_id(at("2026-10-19T07:00:00Z"), true),
+            backup_id(at("2026-10-19T06:00:00Z"), true),
+            backup_id(at("2026-10-19T05:00:00Z"), false),
+            backup_id(at("2026-10-19T04:00:00Z"), false),
+        ];
+        for file in &files {
+            fs::write(backups.join(format!("{file}{EXTENSION}")), "")
+                .await
+                .expect("writing a backup failed");
+        }
+        let partial = backups.join(format!("{}{EXTENSION}{PARTIAL}", files[0]));
+        fs::write(&partial, "")
+            .await
+            .expect("writing a partial archive failed");
+        fs::write(backups.join("notes.txt"), "")
+            .await
+            .expect("writing an unrelated file failed");
+
+        prune_backups(&state, ServerId::Terraria)
+            .await
+            .expect("pruning failed");
+
+        let left: Vec<_> = list_backups(&state, ServerId::Terraria)
+            .await
+            .expect("listing failed")
+            .into_iter()
+            .map(|b| b.id)
+            .collect();
+        assert_eq!(left, [files[0].clone(), files[2].clone()]);
+        assert!(!partial.exists());
+        assert!(backups.join("notes.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn partial_archives_are_not_listed() {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let state = AppState {
+            backup_dir: dir.path().to_string_lossy().into_owned(),
+            ..AppState::default()
+        };
+        let backups = state.backup_dir(ServerId::Terraria);
+        fs::create_dir_all(&backups)
+            .await
+            .expect("creating the backup directory failed");
+        let id = backup_id(Utc::now(), false);
+        fs::write(backups.join(format!("{id}{EXTENSION}{PARTIAL}")), "")
+            .await
+            .expect("writing a partial archive failed");
+        assert!(
+            list_backups(&state, ServerId::Terraria)
+                .await
+                .expect("listing failed")
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! In-game chat read back from a server's journal, for relaying to Discord.
+//!
+//! Callers page through the journal with the cursor returned by the previous call, so no line is
+//! delivered twice even when several arrive within the same second.
+
+use {
+    crate::{
+        AppState, HTTP, auth::Claims, controller::LOGS_READ, error::AppError, rcon,
+        servers::ServerId,
+    },
+    axum::{
+        Json,
+        extract::{
+            Path, Query, State,
+            rejection::{JsonRejection, PathRejection, QueryRejection},
+        },
+        http::StatusCode,
+    },
+    serde::{Deserialize, Serialize},
+    std::sync::Arc,
+    tokio::{process::Command, sync::Mutex},
+};
+
+/// Scope required to broadcast a message to everyone on a server
+pub const SERVERS_BROADCAST: &str = "servers:broadcast";
+
+/// Never return more than this many journal lines in one call
+const MAX_LINES: &str = "500";
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub author: String,
+    pub content: String,
+}
+
+/// Parse a chat line from a vanilla, Paper or Fabric server log, e.g.
+/// `[12:34:56] [Server thread/INFO]: <Steve> hello` or `... [Not Secure] <Steve> hello`
+pub fn parse_minecraft_chat(line: &str) -> Option<ChatMessage> {
+    let (_, message) = line.split_once("]: ")?;
+    let message = message.strip_prefix("[Not Secure] ").unwrap_or(message);
+    let (author, content) = message.strip_prefix('<')?.split_once("> ")?;
+    if author.is_empty() || author.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(ChatMessage {
+        author: author.to_owned(),
+        content: content.trim_end().to_owned(),
+    })
+}
+
+/// Parse a chat line from the TShock console, e.g. `<Steve the Guide> hello`. Unlike Minecraft names,
+/// Terraria names may contain spaces.
+pub fn parse_terraria_chat(line: &str) -> Option<ChatMessage> {
+    let (author, content) = line.strip_prefix('<')?.split_once("> ")?;
+    if author.trim().is_empty() {
+        return None;
+    }
+    Some(ChatMessage {
+        author: author.to_owned(),
+        content: content.trim_end().to_owned(),
+    })
+}
+
+/// Parse a chat line from a server's log, if the line is chat
+pub fn parse_chat(id: ServerId, line: &str) -> Option<ChatMessage> {
+    match id {
+        ServerId::MinecraftGeyser | ServerId::MinecraftModded => parse_minecraft_chat(line),
+        ServerId::Terraria => parse_terraria_chat(line),
+    }
+}
+
+/// Read journal lines after `cursor`, returning them with the cursor of the last line read. Without a
+/// cursor only the cursor of the newest line is returned, so a new reader starts from now rather than
+/// replaying history.
+pub async fn read_journal_after(
+    id: ServerId,
+    cursor: Option<&str>,
+) -> Result<(Vec<String>, Option<String>), AppError> {
+    let mut command = Command::new("journalctl");
+    command.args(["--no-pager", "-o", "cat", "--show-cursor", "-u", id.unit()]);
+    match cursor {
+        Some(cursor) => command.args(["--after-cursor", cursor, "-n", MAX_LINES]),
+        None => command.args(["-n", "1"]),
+    };
+    let output = command.output().await?;
+    if !output.status.success() {
+        return Err(AppError::Command(
+            "journalctl".into(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = vec![];
+    let mut next = None;
+    for line in stdout.lines() {
+        match line.strip_prefix("-- cursor: ") {
+            Some(c) => next = Some(c.to_owned()),
+            None => lines.push(line.to_owned()),
+        }
+    }
+    if cursor.is_none() {
+        lines.clear();
+    }
+    Ok((lines, next))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatQuery {
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatBody {
+    /// Pass back on the next call to continue after the last line read
+    pub cursor: Option<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+pub async fn chat(
+    claims: Claims,
+    id: Result<Path<ServerId>, PathRejection>,
+    query: Result<Query<ChatQuery>, QueryRejection>,
+) -> Result<Json<ChatBody>, AppError> {
+    claims.require_scope(LOGS_READ)?;
+    let (Path(id), Query(query)) = (id?, query?);
+    let (lines, next) = read_journal_after(id, query.cursor.as_deref()).await?;
+    Ok(Json(ChatBody {
+        // nothing new since the given cursor, keep it
+        cursor: next.or(query.cursor),
+        messages: lines
+            .iter()
+            .filter_map(|line| parse_chat(id, line))
+            .collect(),
+    }))
+}
+
+/// Show a message to everyone on a server: with `tellraw` over RCON on Minecraft and the TShock broadcast
+/// endpoint on Terraria.
+///
+/// ref: https://tshock.readme.io/reference/v2serverbroadcast
+pub async fn send_broadcast(state: &AppState, id: ServerId, message: &str) -> Result<(), AppError> {
+    match id {
+        ServerId::MinecraftGeyser | ServerId::MinecraftModded => {
+            let components = serde_json::json!({ "text": message });
+            rcon::run(state, id, &format!("tellraw @a {components}")).await?;
+        }
+        ServerId::Terraria => {
+            HTTP.get(format!("{}/v2/server/broadcast", state.tshock_base_url))
+                .query(&[("msg", message), ("token", state.tshock_token.as_str())])
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BroadcastPayload {
+    pub message: String,
+}
+
+pub async fn broadcast(
+    claims: Claims,
+    State(state): State<Arc<Mutex<AppState>>>,
+    id: Result<Path<ServerId>, PathRejection>,
+    payload: Result<Json<BroadcastPayload>, JsonRejection>,
+) -> Result<StatusCode, AppError> {
+    claims.require_scope(SERVERS_BROADCAST)?;
+    let (Path(id), Json(payload)) = (id?, payload?);
+    let message = payload.message.trim();
+    if message.is_empty() {
+        return Err(AppError::InvalidRequest(String::from("empty message")));
+    }
+    let state = state.lock().await.clone();
+    send_broadcast(&state, id, message).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(author: &str, content: &str) -> Option<ChatMessage> {
+        Some(ChatMessage {
+            author: author.to_owned(),
+            content: content.to_owned(),
+        })
+    }
+
+    #[test]
+    fn parses_minecraft_chat() {
+        for (line, expected) in [
+            (
+                "[12:34:56] [Server thread/INFO]: <Steve> hello",
+                message("Steve", "hello"),
+            ),
+            (
+                "[12:34:56] [Server thread/INFO]: [Not Secure] <Steve> hello  ",
+                message("Steve", "hello"),
+            ),
+            // Forge names the logger too
+            (
+                "[12:34:56] [Server thread/INFO] [net.minecraft.server.MinecraftServer/]: <Steve> hello",
+                message("Steve", "hello"),
+            ),
+            // Floodgate prefixes Bedrock players
+            (
+                "[12:34:56] [Server thread/INFO]: <.Alex> hi",
+                message(".Alex", "hi"),
+            ),
+            (
+                r#"[12:34:56] [Server thread/INFO]: <Steve> {"text":"hi","color":"red"}"#,
+                message("Steve", r#"{"text":"hi","color":"red"}"#),
+            ),
+            (
+                "[12:34:56] [Server thread/INFO]: <Steve> §cred §lbold",
+                message("Steve", "§cred §lbold"),
+            ),
+            // chat can't pass itself off as another player
+            (
+                "[12:34:56] [Server thread/INFO]: <Steve> ]: <Notch> op me",
+                message("Steve", "]: <Notch> op me"),
+            ),
+            (
+                "[12:34:56] [Server thread/INFO]: Steve joined the game",
+                None,
+            ),
+            (
+                "[12:34:56] [Server thread/INFO]: [Server] restarting soon",
+                None,
+            ),
+            ("[12:34:56] [Server thread/INFO]: <Steve Jobs> hello", None),
+            ("[12:34:56] [Server thread/INFO]: <> hello", None),
+            ("[12:34:56] [Server thread/INFO]: <Steve>", None),
+            ("<Steve> hello", None),
+            ("", None),
+        ] {
+            assert_eq!(parse_minecraft_chat(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn parses_terraria_chat() {
+        for (line, expected) in [
+            ("<Steve> hello", message("Steve", "hello")),
+            (
+                "<Steve the Guide> hello there",
+                message("Steve the Guide", "hello there"),
+            ),
+            (
+                r#"<Steve> {"text":"hi"} > "quoted""#,
+                message("Steve", r#"{"text":"hi"} > "quoted""#),
+            ),
+            ("<Steve> §cred", message("Steve", "§cred")),
+            ("Steve has joined.", None),
+            ("<   > hello", None),
+            ("<Steve>hello", None),
+            ("", None),
+        ] {
+            assert_eq!(parse_terraria_chat(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn parses_chat_by_server() {
+        let line = "[12:34:56] [Server thread/INFO]: <Steve> hello";
+        assert_eq!(
+            parse_chat(ServerId::MinecraftModded, line),
+            message("Steve", "hello")
+        );
+        assert_eq!(parse_chat(ServerId::Terraria, line), None);
+    }
+}
@@ -0,0 +1,641 @@
+//! `game_manager`'s configuration, layered from a file, the environment and command line flags.
+//!
+//! Every setting has a key such as `TSHOCK_TOKEN`, looked up in this order with the first match winning:
+//!
+//! 1. a `--tshock-token <value>` or `--tshock-token=<value>` flag
+//! 2. the `TSHOCK_TOKEN` environment variable, or the file named by `TSHOCK_TOKEN_FILE`
+//! 3. a systemd credential named `tshock_token`, passed with `LoadCredential=`
+//! 4. `tshock_token = "..."` in the TOML file given by `--config` or `GAME_MANAGER_CONFIG`
+//! 5. the default
+//!
+//! Empty values count as unset, so the blank entries in `sample.env` don't hide the file. Values read
+//! from files have surrounding whitespace trimmed, which lets secrets stay out of the environment. All
+//! problems found while loading are collected and reported together, rather than one per restart.
+
+use {
+    crate::{
+        AppState,
+        backup::{BACKUPS_READ, BACKUPS_WRITE},
+        chat::SERVERS_BROADCAST,
+        controller::{LOGS_READ, SERVERS_CONTROL},
+        rcon::SERVERS_RCON,
+        servers::SERVERS_READ,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        fmt::Display,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::Arc,
+        time::{Duration, SystemTime},
+    },
+    tokio::{
+        signal::unix::{SignalKind, signal},
+        sync::Mutex,
+    },
+    toml::Table,
+};
+
+/// Environment variable naming the config file, for when `--config` isn't given
+const CONFIG_ENV: &str = "GAME_MANAGER_CONFIG";
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Scopes a client can be granted
+const SCOPES: [&str; 7] = [
+    BACKUPS_READ,
+    BACKUPS_WRITE,
+    LOGS_READ,
+    SERVERS_BROADCAST,
+    SERVERS_CONTROL,
+    SERVERS_RCON,
+    SERVERS_READ,
+];
+
+/// Every problem found in the configuration
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The configuration sources, collecting problems as settings are read
+struct Layers {
+    path: Option<PathBuf>,
+    flags: HashMap<String, String>,
+    file: Table,
+    /// The environment variables that are valid UTF-8
+    env: HashMap<String, String>,
+    credentials: Option<PathBuf>,
+    /// Keys that were read, anything else in the flags or file is a typo
+    used: HashSet<String>,
+    problems: Vec<String>,
+}
+
+impl Layers {
+    /// Read the process's arguments and environment
+    fn load() -> Self {
+        let env = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Self::new(std::env::args().skip(1), env)
+    }
+
+    /// Parse `args`, the arguments after the program name, and read the config file they or `env` name
+    fn new(args: impl IntoIterator<Item = String>, env: HashMap<String, String>) -> Self {
+        let mut problems = Vec::new();
+        let mut flags = HashMap::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                problems.push(format!("unexpected argument `{arg}`"));
+                continue;
+            };
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (flag.to_owned(), args.next()),
+            };
+            match value {
+                Some(value) => {
+                    flags.insert(name, value);
+                }
+                None => problems.push(format!("`--{name}` needs a value")),
+            }
+        }
+        let path = flags
+            .remove("config")
+            .or_else(|| env.get(CONFIG_ENV).cloned())
+            .filter(|path| !path.is_empty());
+        let file =
+            path.as_ref()
+                .map_or_else(Table::new, |path| match std::fs::read_to_string(path) {
+                    Ok(contents) => contents.parse().unwrap_or_else(|e| {
+                        problems.push(format!("{path} is not valid TOML: {e}"));
+                        Table::new()
+                    }),
+                    Err(e) => {
+                        problems.push(format!("cannot read {path}: {e}"));
+                        Table::new()
+                    }
+                });
+        let credentials = env.get("CREDENTIALS_DIRECTORY").map(PathBuf::from);
+        Self {
+            path: path.map(PathBuf::from),
+            flags,
+            file,
+            env,
+            credentials,
+            used: HashSet::new(),
+            problems,
+        }
+    }
+
+    /// An environment variable, `None` if it is empty
+    fn var(&self, key: &str) -> Option<String> {
+        self.env.get(key).filter(|value| !value.is_empty()).cloned()
+    }
+
+    fn read_file(&mut self, what: &str, path: &Path) -> Option<String> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Some(contents.trim().to_owned()).filter(|value| !value.is_empty()),
+            Err(e) => {
+                self.problems
+                    .push(format!("cannot read {what} from {}: {e}", path.display()));
+                None
+            }
+        }
+    }
+
+    /// The value of `key` from the first layer that sets it
+    fn raw(&mut self, key: &str) -> Option<String> {
+        let name = key.to_lowercase();
+        self.used.insert(name.clone());
+        if let Some(value) = self.flags.get(&name.replace('_', "-")) {
+            return Some(value.clone()).filter(|value| !value.is_empty());
+        }
+        let file_key = format!("{key}_FILE");
+        let value = self.var(key);
+        let file = self.var(&file_key);
+        match (value, file) {
+            (Some(_), Some(_)) => {
+                self.problems
+                    .push(format!("only one of {key} and {file_key} may be set"));
+                return None;
+            }
+            (Some(value), None) => return Some(value),
+            (None, Some(path)) => return self.read_file(key, path.as_ref()),
+            (None, None) => {}
+        }
+        if let Some(credential) = self.credentials.as_ref().map(|dir| dir.join(&name))
+            && credential.is_file()
+        {
+            return self.read_file(key, &credential);
+        }
+        match self.file.get(&name)? {
+            toml::Value::String(value) => Some(value.clone()).filter(|value| !value.is_empty()),
+            value => Some(value.to_string()),
+        }
+    }
+
+    fn string(&mut self, key: &str, default: &str) -> String {
+        self.raw(key).unwrap_or_else(|| default.to_owned())
+    }
+
+    fn required(&mut self, key: &str) -> String {
+        self.raw(key).unwrap_or_else(|| {
+            self.problems.push(format!("{key} must be set"));
+            String::new()
+        })
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, default: T) -> T
+    where
+        T::Err: Display,
+    {
+        match self.raw(key) {
+            None => default,
+            Some(value) => value.parse().unwrap_or_else(|e| {
+                self.problems
+                    .push(format!("{key} `{value}` is invalid: {e}"));
+                default
+            }),
+        }
+    }
+
+    /// A `host:port` address, empty if unset
+    fn address(&mut self, key: &str, default: &str) -> String {
+        let value = self.string(key, default);
+        let valid = value
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        if !value.is_empty() && !valid {
+            self.problems
+                .push(format!("{key} `{value}` is not a host:port address"));
+        }
+        value
+    }
+
+    /// An absolute path, empty if unset
+    fn path(&mut self, key: &str, default: &str) -> String {
+        let value = self.string(key, default);
+        if !value.is_empty() && !Path::new(&value).is_absolute() {
+            self.problems
+                .push(format!("{key} `{value}` is not an absolute path"));
+        }
+        value
+    }
+
+    /// A URL, kept as the string it was given as
+    fn url(&mut self, key: &str, default: &str) -> String {
+        let value = self.string(key, default);
+        if !value.is_empty()
+            && let Err(e) = url::Url::parse(&value)
+        {
+            self.problems
+                .push(format!("{key} `{value}` is not a URL: {e}"));
+        }
+        value
+    }
+
+    fn finish(mut self) -> Result<(), ConfigError> {
+        for name in self.flags.keys() {
+            if !self.used.contains(&name.replace('-', "_")) {
+                self.problems.push(format!("unknown flag `--{name}`"));
+            }
+        }
+        for name in self.file.keys() {
+            if !self.used.contains(name) {
+                self.problems
+                    .push(format!("unknown setting `{name}` in config file"));
+            }
+        }
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError(self.problems))
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Config {
+    /// The config file, if one was given
+    pub file: Option<PathBuf>,
+    /// Settings shared with the request handlers
+    pub state: AppState,
+    pub address: SocketAddr,
+    /// Where the JWT signing keys and revocations are kept
+    pub jwt_keys_dir: PathBuf,
+    /// A key from before signing keys were persisted and rotated, imported into an empty key store
+    pub jwt_private_key: Option<PathBuf>,
+    pub jwt_key_rotation: Duration,
+    /// How long tokens signed with a rotated out key stay valid
+    pub jwt_key_overlap: Duration,
+    /// How often every server is backed up, zero to disable scheduled backups
+    pub backup_interval: Duration,
+}
+
+impl Config {
+    /// Read and validate the configuration, failing with every problem found
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::from_layers(Layers::load())
+    }
+
+    fn from_layers(mut layers: Layers) -> Result<Self, ConfigError> {
+        let days = |layers: &mut Layers, key: &str, default: u64| {
+            Duration::from_secs(layers.parse(key, default) * 24 * 60 * 60)
+        };
+        let config = Self {
+            file: layers.path.clone(),
+            state: AppState {
+                minecraft_geyser_address: layers
+                    .address("MINECRAFT_GEYSER_ADDRESS", "localhost:25565"),
+                floodgate_prefix: layers.string("FLOODGATE_PREFIX", "."),
+                minecraft_geyser_rcon_address: layers.address("MINECRAFT_GEYSER_RCON_ADDRESS", ""),
+                minecraft_geyser_rcon_password: layers.string("MINECRAFT_GEYSER_RCON_PASSWORD", ""),
+                minecraft_modded_address: layers
+                    .address("MINECRAFT_MODDED_ADDRESS", "localhost:25566"),
+                minecraft_modded_rcon_address: layers.address("MINECRAFT_MODDED_RCON_ADDRESS", ""),
+                minecraft_modded_rcon_password: layers.string("MINECRAFT_MODDED_RCON_PASSWORD", ""),
+                terraria_address: layers.address("TERRARIA_ADDRESS", "localhost:7777"),
+                tshock_base_url: layers.url("TSHOCK_BASE_URL", "http://localhost:7878"),
+                tshock_token: layers.string("TSHOCK_TOKEN", ""),
+                issuer: layers.string("JWT_ISSUER", "game_manager"),
+                audience: layers.string("JWT_AUDIENCE", "discord_bot"),
+                client_id: layers.required("GAME_MANAGER_CLIENT_ID"),
+                client_secret: layers.required("GAME_MANAGER_CLIENT_SECRET"),
+                client_scopes: layers.string("GAME_MANAGER_CLIENT_SCOPES", &SCOPES.join(" ")),
+                backup_dir: layers.path("BACKUP_DIR", "/var/lib/game_manager/backups"),
+                minecraft_geyser_world_dir: layers.path("MINECRAFT_GEYSER_WORLD_DIR", ""),
+                minecraft_modded_world_dir: layers.path("MINECRAFT_MODDED_WORLD_DIR", ""),
+                terraria_world_dir: layers.path("TERRARIA_WORLD_DIR", ""),
+                backup_keep_hourly: layers.parse("BACKUP_KEEP_HOURLY", 24),
+                backup_keep_daily: layers.parse("BACKUP_KEEP_DAILY", 7),
+                backup_keep_weekly: layers.parse("BACKUP_KEEP_WEEKLY", 4),
+                backup_keep_pre_restore: layers.parse("BACKUP_KEEP_PRE_RESTORE", 3),
+            },
+            address: layers.parse(
+                "ADDRESS",
+                dioxus_cli_config::fullstack_address_or_localhost(),
+            ),
+            jwt_keys_dir: PathBuf::from(layers.string("JWT_KEYS_DIR", "keys")),
+            jwt_private_key: layers.raw("JWT_PRIVATE_KEY").map(PathBuf::from),
+            jwt_key_rotation: days(&mut layers, "JWT_KEY_ROTATION_DAYS", 30),
+            jwt_key_overlap: days(&mut layers, "JWT_KEY_OVERLAP_DAYS", 7),
+            backup_interval: Duration::from_secs(layers.parse("BACKUP_INTERVAL_MINUTES", 60) * 60),
+        };
+        let state = &config.state;
+        for scope in state.client_scopes.split_whitespace() {
+            if !SCOPES.contains(&scope) {
+                layers.problems.push(format!(
+                    "GAME_MANAGER_CLIENT_SCOPES has unknown scope `{scope}`"
+                ));
+            }
+        }
+        for (server, address, password) in [
+            (
+                "MINECRAFT_GEYSER",
+                &state.minecraft_geyser_rcon_address,
+                &state.minecraft_geyser_rcon_password,
+            ),
+            (
+                "MINECRAFT_MODDED",
+                &state.minecraft_modded_rcon_address,
+                &state.minecraft_modded_rcon_password,
+            ),
+        ] {
+            if !address.is_empty() && password.is_empty() {
+                layers.problems.push(format!(
+                    "{server}_RCON_PASSWORD must be set when {server}_RCON_ADDRESS is"
+                ));
+            }
+        }
+        if config.jwt_key_rotation.is_zero() {
+            layers
+                .problems
+                .push(String::from("JWT_KEY_ROTATION_DAYS must be above 0"));
+        }
+        layers.finish()?;
+        Ok(config)
+    }
+
+    /// Keep the settings that are only read at startup from the `running` configuration, returning the
+    /// keys of those this one would change
+    pub fn keep_startup_settings(&mut self, running: &Self) -> Vec<&'static str> {
+        fn keep<T: PartialEq + Clone>(
+            changed: &mut Vec<&'static str>,
+            key: &'static str,
+            new: &mut T,
+            old: &T,
+        ) {
+            if new != old {
+                changed.push(key);
+                new.clone_from(old);
+            }
+        }
+        let mut changed = Vec::new();
+        keep(&mut changed, CONFIG_ENV, &mut self.file, &running.file);
+        keep(&mut changed, "ADDRESS", &mut self.address, &running.address);
+        keep(
+            &mut changed,
+            "JWT_KEYS_DIR",
+            &mut self.jwt_keys_dir,
+            &running.jwt_keys_dir,
+        );
+        keep(
+            &mut changed,
+            "JWT_PRIVATE_KEY",
+            &mut self.jwt_private_key,
+            &running.jwt_private_key,
+        );
+        keep(
+            &mut changed,
+            "JWT_KEY_ROTATION_DAYS",
+            &mut self.jwt_key_rotation,
+            &running.jwt_key_rotation,
+        );
+        keep(
+            &mut changed,
+            "JWT_KEY_OVERLAP_DAYS",
+            &mut self.jwt_key_overlap,
+            &running.jwt_key_overlap,
+        );
+        keep(
+            &mut changed,
+            "BACKUP_INTERVAL_MINUTES",
+            &mut self.backup_interval,
+            &running.backup_interval,
+        );
+        changed
+    }
+}
+
+async fn modified(file: Option<&Path>) -> Option<SystemTime> {
+    tokio::fs::metadata(file?).await.ok()?.modified().ok()
+}
+
+/// Reload the configuration whenever its file changes or `game_manager` receives `SIGHUP`.
+///
+/// The environment and flags are fixed for the life of the process, so a reload picks up the config file
+/// and secret files. An invalid configuration is rejected whole and the running one kept. A valid one
+/// replaces the shared state in one step, which every request reads, so changed addresses, passwords and
+/// tokens apply to the next request. Settings only read at startup keep their old value until a restart.
+pub async fn watch(state: Arc<Mutex<AppState>>, mut running: Config) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::error!("Cannot reload the configuration on SIGHUP: {e}");
+            return;
+        }
+    };
+    let mut last_modified = modified(running.file.as_deref()).await;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let modified = modified(running.file.as_deref()).await;
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                tracing::info!("Config file changed, reloading");
+            }
+            _ = hangup.recv() => tracing::info!("Received SIGHUP, reloading the configuration"),
+        }
+        let mut config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!("Keeping the running configuration, {e}");
+                continue;
+            }
+        };
+        for key in config.keep_startup_settings(&running) {
+            tracing::warn!("{key} changed, restart game_manager to apply it");
+        }
+        if config == running {
+            tracing::info!("Configuration unchanged");
+            continue;
+        }
+        *state.lock().await = config.state.clone();
+        running = config;
+        tracing::info!("Applied the reloaded configuration");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tempfile::TempDir};
+
+    fn layers(args: &[&str], env: &[(&str, &str)]) -> Layers {
+        Layers::new(
+            args.iter().map(ToString::to_string),
+            env.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    fn write(dir: &TempDir, name: &str, contents: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).expect("writing a file failed");
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn layers_take_precedence_in_order() {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let config = write(&dir, "config.toml", "tshock_token = \"toml\"\n");
+        let secret = write(&dir, "secret", "  from file\n");
+        let credentials = dir.path().join("credentials");
+        std::fs::create_dir(&credentials).expect("creating the credentials directory failed");
+        std::fs::write(credentials.join("tshock_token"), "credential\n")
+            .expect("writing the credential failed");
+        let credentials = credentials.to_string_lossy().into_owned();
+
+        let flag = ["--tshock-token=flag"];
+        let env = [
+            ("GAME_MANAGER_CONFIG", config.as_str()),
+            ("CREDENTIALS_DIRECTORY", credentials.as_str()),
+            ("TSHOCK_TOKEN", "env"),
+        ];
+        let env_file = [
+            ("GAME_MANAGER_CONFIG", config.as_str()),
+            ("CREDENTIALS_DIRECTORY", credentials.as_str()),
+            ("TSHOCK_TOKEN_FILE", secret.as_str()),
+        ];
+        let blank_env = [
+            ("GAME_MANAGER_CONFIG", config.as_str()),
+            ("CREDENTIALS_DIRECTORY", credentials.as_str()),
+            ("TSHOCK_TOKEN", ""),
+        ];
+        for (args, env, expected) in [
+            (&flag[..], &env[..], "flag"),
+            (&[][..], &env[..], "env"),
+            (&[][..], &env_file[..], "from file"),
+            (&[][..], &blank_env[..], "credential"),
+            (&[][..], &env[..1], "toml"),
+            (&[][..], &[][..], "default"),
+        ] {
+            let mut layers = layers(args, env);
+            assert_eq!(layers.string("TSHOCK_TOKEN", "default"), expected);
+            assert!(layers.finish().is_ok(), "{expected}");
+        }
+    }
+
+    #[test]
+    fn flags_take_the_next_argument_or_an_inline_value() {
+        let mut layers = layers(&["--tshock-token", "separate", "--jwt-issuer=inline"], &[]);
+        assert_eq!(layers.string("TSHOCK_TOKEN", ""), "separate");
+        assert_eq!(layers.string("JWT_ISSUER", ""), "inline");
+        assert!(layers.finish().is_ok());
+    }
+
+    #[test]
+    fn a_value_and_a_file_conflict() {
+        let mut layers = layers(
+            &[],
+            &[
+                ("TSHOCK_TOKEN", "env"),
+                ("TSHOCK_TOKEN_FILE", "/run/secret"),
+            ],
+        );
+        assert_eq!(layers.raw("TSHOCK_TOKEN"), None);
+        let Err(ConfigError(problems)) = layers.finish() else {
+            panic!("conflicting settings were accepted");
+        };
+        assert_eq!(
+            problems,
+            ["only one of TSHOCK_TOKEN and TSHOCK_TOKEN_FILE may be set"]
+        );
+    }
+
+    #[test]
+    fn unknown_flags_and_settings_are_problems() {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let config = write(
+            &dir,
+            "config.toml",
+            "tshock_token = \"a\"\ntshok_token = \"b\"\n",
+        );
+        let mut layers = layers(
+            &[
+                "--config",
+                &config,
+                "--tshok-token=c",
+                "stray",
+                "--jwt-issuer",
+            ],
+            &[],
+        );
+        assert_eq!(layers.string("TSHOCK_TOKEN", ""), "a");
+        layers.string("JWT_ISSUER", "");
+        let Err(ConfigError(problems)) = layers.finish() else {
+            panic!("unknown settings were accepted");
+        };
+        assert_eq!(
+            problems,
+            [
+                "unexpected argument `stray`",
+                "`--jwt-issuer` needs a value",
+                "unknown flag `--tshok-token`",
+                "unknown setting `tshok_token` in config file",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_problem_is_reported_together() {
+        let Err(ConfigError(problems)) = Config::from_layers(layers(
+            &[
+                "--tshock-base-url=not a url",
+                "--backup-keep-daily=many",
+                "--game-manager-client-scopes=servers:read servers:destroy",
+            ],
+            &[
+                ("GAME_MANAGER_CLIENT_ID", "discord_bot"),
+                ("BACKUP_DIR", "relative/backups"),
+                ("MINECRAFT_GEYSER_RCON_ADDRESS", "localhost:25575"),
+            ],
+        )) else {
+            panic!("an invalid configuration was accepted");
+        };
+        assert_eq!(
+            problems,
+            [
+                "TSHOCK_BASE_URL `not a url` is not a URL: relative URL without a base",
+                "GAME_MANAGER_CLIENT_SECRET must be set",
+                "BACKUP_DIR `relative/backups` is not an absolute path",
+                "BACKUP_KEEP_DAILY `many` is invalid: invalid digit found in string",
+                "GAME_MANAGER_CLIENT_SCOPES has unknown scope `servers:destroy`",
+                "MINECRAFT_GEYSER_RCON_PASSWORD must be set when MINECRAFT_GEYSER_RCON_ADDRESS is",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_defaults_need_only_client_credentials() {
+        let config = Config::from_layers(layers(
+            &[],
+            &[
+                ("GAME_MANAGER_CLIENT_ID", "discord_bot"),
+                ("GAME_MANAGER_CLIENT_SECRET", "secret"),
+            ],
+        ))
+        .expect("the defaults are invalid");
+        assert_eq!(config.state.client_scopes, SCOPES.join(" "));
+        assert_eq!(
+            config.jwt_key_rotation,
+            Duration::from_secs(30 * 24 * 60 * 60)
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use {
+    crate::{auth::Claims, error::AppError, servers::ServerId},
+    axum::{
+        extract::{
+            Path, Query,
+            rejection::{PathRejection, QueryRejection},
+        },
+        http::StatusCode,
+    },
+    chrono::{DateTime, Utc},
+    serde::Deserialize,
+    tokio::process::Command,
+};
+
+/// Scope required to read a server's journal
+pub const LOGS_READ: &str = "logs:read";
+/// Scope required to start, stop or restart a server
+pub const SERVERS_CONTROL: &str = "servers:control";
+
+/// Never return more than this many journal lines in one call, the newest are kept
+const MAX_LOG_LINES: &str = "10000";
+
+impl ServerId {
+    /// The systemd unit running this server
+    pub const fn unit(self) -> &'static str {
+        match self {
+            Self::MinecraftGeyser => "arion-minecraft-geyser.service",
+            Self::MinecraftModded => "arion-minecraft-modded.service",
+            Self::Terraria => "arion-terraria.service",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitAction {
+    Restart,
+    Start,
+    Stop,
+}
+
+impl UnitAction {
+    const fn verb(self) -> &'static str {
+        match self {
+            Self::Restart => "restart",
+            Self::Start => "start",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+/// Read the journal of a server's unit between two instants, at most the last [`MAX_LOG_LINES`] of it
+pub async fn read_journal(
+    id: ServerId,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<String, AppError> {
+    let output = Command::new("journalctl")
+        .args([
+            "--utc",
+            "--no-pager",
+            "-u",
+            id.unit(),
+            // journalctl reads the bounds as local time unless told otherwise
+            "-S",
+            &since.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            "-U",
+            &until.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            "-n",
+            MAX_LOG_LINES,
+        ])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(AppError::Command(
+            "journalctl".into(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Start, stop or restart a server's unit through systemd, waiting until the unit has done so
+pub async fn control_unit(id: ServerId, action: UnitAction) -> Result<(), AppError> {
+    systemctl(&[action.verb(), id.unit()]).await
+}
+
+/// Queue starting, stopping or restarting a server's unit without waiting for it. A server can take
+/// longer to stop than a request may, so requests only wait for systemd to accept the job.
+pub async fn queue_unit(id: ServerId, action: UnitAction) -> Result<(), AppError> {
+    systemctl(&["--no-block", action.verb(), id.unit()]).await
+}
+
+async fn systemctl(args: &[&str]) -> Result<(), AppError> {
+    tracing::info!("Running systemctl {}", args.join(" "));
+    let output = Command::new("systemctl").args(args).output().await?;
+    if !output.status.success() {
+        return Err(AppError::Command(
+            "systemctl".into(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether a server's unit is currently running
+pub async fn unit_active(id: ServerId) -> Result<bool, AppError> {
+    let status = Command::new("systemctl")
+        .args(["is-active", "--quiet", id.unit()])
+        .status()
+        .await?;
+    Ok(status.success())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogsQuery {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+pub async fn logs(
+    claims: Claims,
+    id: Result<Path<ServerId>, PathRejection>,
+    query: Result<Query<LogsQuery>, QueryRejection>,
+) -> Result<String, AppError> {
+    claims.require_scope(LOGS_READ)?;
+    let (Path(id), Query(query)) = (id?, query?);
+    if query.since > query.until {
+        return Err(AppError::InvalidRequest(
+            "since must be before until".into(),
+        ));
+    }
+    tracing::info!("{} fetching logs for {id}", claims.sub);
+    read_journal(id, query.since, query.until).await
+}
+
+async fn control(
+    claims: &Claims,
+    id: ServerId,
+    action: UnitAction,
+) -> Result<StatusCode, AppError> {
+    claims.require_scope(SERVERS_CONTROL)?;
+    tracing::info!("{} requested {} of {id}", claims.sub, action.verb());
+    queue_unit(id, action).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn restart(
+    claims: Claims,
+    id: Result<Path<ServerId>, PathRejection>,
+) -> Result<StatusCode, AppError> {
+    control(&claims, id?.0, UnitAction::Restart).await
+}
+
+pub async fn start(
+    claims: Claims,
+    id: Result<Path<ServerId>, PathRejection>,
+) -> Result<StatusCode, AppError> {
+    control(&claims, id?.0, UnitAction::Start).await
+}
+
+pub async fn stop(
+    claims: Claims,
+    id: Result<Path<ServerId>, PathRejection>,
+) -> Result<StatusCode, AppError> {
+    control(&claims, id?.0, UnitAction::Stop).await
+}
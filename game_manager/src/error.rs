@@ -0,0 +1,192 @@
+use {
+    axum::{
+        Json,
+        extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+        http::{HeaderValue, StatusCode, header},
+        response::{IntoResponse, Response},
+    },
+    serde::Serialize,
+    serde_json::json,
+};
+
+/// Stable, machine readable error codes returned in the `code` field of every error body
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    UpstreamUnreachable,
+    UpstreamTimeout,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    InvalidRequest,
+    Conflict,
+    Internal,
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    /// Missing, malformed, expired or revoked bearer token
+    InvalidToken(String),
+    /// Client credentials were missing or wrong
+    WrongCredentials,
+    /// The token is valid but lacks the scope the route requires
+    MissingScope(&'static str),
+    NotFound(String),
+    InvalidRequest(String),
+    /// Another operation on the same server, such as a backup, is still running
+    Busy(String),
+    /// Something left on disk is in the way, such as a world an interrupted restore moved aside
+    Conflict(String),
+    /// A game server or TShock could not be reached
+    UpstreamUnreachable(String),
+    /// A game server or TShock did not answer in time
+    UpstreamTimeout(String),
+    /// A local command such as `journalctl` or `systemctl` failed
+    Command(String, String),
+    /// The RCON server rejected our login or sent something unexpected
+    Rcon(String),
+    Io(std::io::Error),
+    ReqwestError(reqwest::Error),
+    /// A task running work detached from its request panicked
+    Task(tokio::task::JoinError),
+    TokenCreation,
+}
+
+impl AppError {
+    pub const fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidToken(_) | Self::WrongCredentials => ErrorCode::Unauthorized,
+            Self::MissingScope(_) => ErrorCode::Forbidden,
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            Self::Busy(_) | Self::Conflict(_) => ErrorCode::Conflict,
+            Self::UpstreamUnreachable(_) => ErrorCode::UpstreamUnreachable,
+            Self::UpstreamTimeout(_) => ErrorCode::UpstreamTimeout,
+            Self::Command(..)
+            | Self::Rcon(_)
+            | Self::Io(_)
+            | Self::ReqwestError(_)
+            | Self::Task(_)
+            | Self::TokenCreation => ErrorCode::Internal,
+        }
+    }
+
+    pub const fn status(&self) -> StatusCode {
+        match self.code() {
+            ErrorCode::UpstreamUnreachable => StatusCode::BAD_GATEWAY,
+            ErrorCode::UpstreamTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The `WWW-Authenticate` challenge for 401 and 403 responses, see RFC 6750 section 3
+    fn challenge(&self) -> Option<String> {
+        match self {
+            Self::InvalidToken(description) => Some(format!(
+                r#"Bearer realm="game_manager", error="invalid_token", error_description="{}""#,
+                description.replace('"', "'")
+            )),
+            Self::WrongCredentials => Some(String::from(r#"Bearer realm="game_manager""#)),
+            Self::MissingScope(scope) => Some(format!(
+                r#"Bearer realm="game_manager", error="insufficient_scope", scope="{scope}""#
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidToken(e) => write!(f, "Invalid token: {e}"),
+            Self::WrongCredentials => write!(f, "Wrong credentials"),
+            Self::MissingScope(scope) => write!(f, "Missing scope: {scope}"),
+            Self::NotFound(e) => write!(f, "Not found: {e}"),
+            Self::InvalidRequest(e) => write!(f, "Invalid request: {e}"),
+            Self::Busy(e) => write!(f, "Busy: {e}"),
+            Self::Conflict(e) => write!(f, "Conflict: {e}"),
+            Self::UpstreamUnreachable(e) => write!(f, "Upstream unreachable: {e}"),
+            Self::UpstreamTimeout(e) => write!(f, "Upstream timed out: {e}"),
+            Self::Command(command, stderr) => write!(f, "{command} failed: {stderr}"),
+            Self::Rcon(e) => write!(f, "RCON error: {e}"),
+            Self::Io(e) => write!(f, "IO error: {e}"),
+            Self::ReqwestError(e) => write!(f, "Reqwest error: {e}"),
+            Self::Task(e) => write!(f, "Task error: {e}"),
+            Self::TokenCreation => write!(f, "Token creation error"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!("{self}");
+        } else {
+            tracing::debug!("{self}");
+        }
+        let challenge = self.challenge();
+        let body = Json(json!({
+            "status": status.as_u16(),
+            "code": self.code(),
+            "error": self.to_string(),
+        }));
+        let mut response = (status, body).into_response();
+        if let Some(value) = challenge.and_then(|c| HeaderValue::from_str(&c).ok()) {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, value);
+        }
+        response
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<tokio::task::JoinError> for AppError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        Self::Task(err)
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::UpstreamTimeout(err.to_string())
+        } else if err.is_connect() {
+            Self::UpstreamUnreachable(err.to_string())
+        } else {
+            Self::ReqwestError(err)
+        }
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(err: PathRejection) -> Self {
+        // only the server id can fail to parse, so a bad one means there is no such server
+        Self::NotFound(err.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(err: QueryRejection) -> Self {
+        Self::InvalidRequest(err.body_text())
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(err: JsonRejection) -> Self {
+        Self::InvalidRequest(err.body_text())
+    }
+}
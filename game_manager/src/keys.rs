@@ -0,0 +1,416 @@
+use {
+    jsonwebtoken::{
+        Algorithm, DecodingKey, EncodingKey,
+        jwk::{Jwk, JwkSet, PublicKeyUse},
+    },
+    rsa::pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey, EncodeRsaPublicKey, LineEnding},
+    std::{
+        path::{Path, PathBuf},
+        sync::RwLock,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// A single RSA signing key, identified in JWT headers and the JWKS by its `kid`.
+pub struct SigningKey {
+    pub kid: String,
+    /// Unix timestamp (seconds) the key was generated at, encoded as the prefix of the `kid`
+    pub created_at: u64,
+    pub encoding: EncodingKey,
+    pub decoding: DecodingKey,
+    pub jwk: Jwk,
+}
+
+#[derive(Debug)]
+pub enum KeyError {
+    Io(PathBuf, std::io::Error),
+    Pem(PathBuf, rsa::pkcs1::Error),
+    Rsa(rsa::Error),
+    Jwt(jsonwebtoken::errors::Error),
+    /// Generating a key on a blocking thread panicked
+    Task(tokio::task::JoinError),
+}
+
+impl std::fmt::Display for KeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "IO error for {}: {e}", path.display()),
+            Self::Pem(path, e) => write!(f, "Invalid PKCS#1 key in {}: {e}", path.display()),
+            Self::Rsa(e) => write!(f, "RSA error: {e}"),
+            Self::Jwt(e) => write!(f, "JWT error: {e}"),
+            Self::Task(e) => write!(f, "Task error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl From<rsa::Error> for KeyError {
+    fn from(err: rsa::Error) -> Self {
+        Self::Rsa(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for KeyError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Self::Jwt(err)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl SigningKey {
+    fn from_pem(kid: String, created_at: u64, pem: &str, path: &Path) -> Result<Self, KeyError> {
+        let private_key = rsa::RsaPrivateKey::from_pkcs1_pem(pem)
+            .map_err(|e| KeyError::Pem(path.to_path_buf(), e))?;
+        let public_pem = private_key
+            .to_public_key()
+            .to_pkcs1_pem(LineEnding::LF)
+            .map_err(|e| KeyError::Pem(path.to_path_buf(), e))?;
+        let encoding = EncodingKey::from_rsa_pem(pem.as_bytes())?;
+        let decoding = DecodingKey::from_rsa_pem(public_pem.as_bytes())?;
+        let mut jwk = Jwk::from_encoding_key(&encoding, Algorithm::RS256)?;
+        jwk.common.key_id = Some(kid.clone());
+        jwk.common.public_key_use = Some(PublicKeyUse::Signature);
+        Ok(Self {
+            kid,
+            created_at,
+            encoding,
+            decoding,
+            jwk,
+        })
+    }
+}
+
+/// Persisted set of signing keys.
+///
+/// Every key lives in `<dir>/<kid>.pem` as a PKCS#1 private key. The newest key signs new tokens, older
+/// keys stay available for verification until `rotation + overlap` has passed since they were created,
+/// so tokens issued just before a rotation remain valid until they expire.
+pub struct KeyStore {
+    dir: PathBuf,
+    rotation: Duration,
+    overlap: Duration,
+    keys: RwLock<Vec<SigningKey>>,
+}
+
+impl KeyStore {
+    /// Load every key in `dir`, creating the directory and a first key if there are none.
+    ///
+    /// `import` is the single unrotated key used before keys were persisted, set with `JWT_PRIVATE_KEY`.
+    /// It becomes the first key of an empty store, so tokens signed with it stay valid until it is
+    /// rotated out like any other key, and is ignored once the store has keys of its own.
+    pub fn load(
+        dir: PathBuf,
+        rotation: Duration,
+        overlap: Duration,
+        import: Option<&Path>,
+    ) -> Result<Self, KeyError> {
+        std::fs::create_dir_all(&dir).map_err(|e| KeyError::Io(dir.clone(), e))?;
+        let mut keys = vec![];
+        for entry in std::fs::read_dir(&dir).map_err(|e| KeyError::Io(dir.clone(), e))? {
+            let path = entry.map_err(|e| KeyError::Io(dir.clone(), e))?.path();
+            if path.extension().is_none_or(|ext| ext != "pem") {
+                continue;
+            }
+            let Some(kid) = path.file_stem().and_then(|s| s.to_str()).map(String::from) else {
+                continue;
+            };
+            // kids are `<created_at>-<uuid>`, anything else was not written by us
+            let Some(created_at) = kid.split_once('-').and_then(|(ts, _)| ts.parse().ok()) else {
+                tracing::warn!("Ignoring unrecognised key file {}", path.display());
+                continue;
+            };
+            let pem = std::fs::read_to_string(&path).map_err(|e| KeyError::Io(path.clone(), e))?;
+            keys.push(SigningKey::from_pem(kid, created_at, &pem, &path)?);
+        }
+        match import {
+            Some(path) if keys.is_empty() => {
+                let created_at = now();
+                let kid = format!("{created_at}-{}", uuid::Uuid::new_v4().simple());
+                let pem = std::fs::read_to_string(path)
+                    .map_err(|e| KeyError::Io(path.to_path_buf(), e))?;
+                let key = SigningKey::from_pem(kid, created_at, &pem, path)?;
+                let stored = key_path(&dir, &key.kid);
+                write_private(&stored, pem.as_bytes()).map_err(|e| KeyError::Io(stored, e))?;
+                tracing::info!("Imported {} as JWT signing key {}", path.display(), key.kid);
+                keys.push(key);
+            }
+            Some(path) => tracing::warn!(
+                "Ignoring JWT_PRIVATE_KEY {}, {} already has signing keys",
+                path.display(),
+                dir.display()
+            ),
+            None => {}
+        }
+        keys.sort_by_key(|key| key.created_at);
+        let store = Self {
+            dir,
+            rotation,
+            overlap,
+            keys: RwLock::new(keys),
+        };
+        let now = now();
+        if store.is_due(now) {
+            store.add(generate(&store.dir, now)?, now);
+        }
+        store.retire(now);
+        Ok(store)
+    }
+
+    /// Generate a new key when the current one is older than the rotation period and drop keys that
+    /// have outlived the overlap window. Returns whether a new key was generated.
+    pub async fn rotate_if_due(&self) -> Result<bool, KeyError> {
+        self.rotate_at(now()).await
+    }
+
+    async fn rotate_at(&self, now: u64) -> Result<bool, KeyError> {
+        let mut rotated = false;
+        if self.is_due(now) {
+            // generating a key takes a while, so it happens on a blocking thread without holding the lock
+            let dir = self.dir.clone();
+            let key = tokio::task::spawn_blocking(move || generate(&dir, now))
+                .await
+                .map_err(KeyError::Task)??;
+            rotated = self.add(key, now);
+        }
+        self.retire(now);
+        Ok(rotated)
+    }
+
+    fn is_due(&self, now: u64) -> bool {
+        let keys = self.keys.read().expect("key store lock poisoned");
+        self.is_due_for(&keys, now)
+    }
+
+    fn is_due_for(&self, keys: &[SigningKey], now: u64) -> bool {
+        keys.last()
+            .is_none_or(|key| now.saturating_sub(key.created_at) >= self.rotation.as_secs())
+    }
+
+    /// Make `key` the signing key if a rotation is still due, checked again under the lock so concurrent
+    /// callers can't both add one. Returns whether it was added, a key that lost the race is deleted.
+    fn add(&self, key: SigningKey, now: u64) -> bool {
+        let mut keys = self.keys.write().expect("key store lock poisoned");
+        if !self.is_due_for(&keys, now) {
+            drop(keys);
+            if let Err(e) = std::fs::remove_file(self.path_for(&key.kid)) {
+                tracing::warn!("Failed to remove unused key {}: {e}", key.kid);
+            }
+            return false;
+        }
+        tracing::info!("Generated JWT signing key {}", key.kid);
+        keys.push(key);
+        true
+    }
+
+    /// Drop every key but the newest that has outlived the overlap window
+    fn retire(&self, now: u64) {
+        let expiry = (self.rotation + self.overlap).as_secs();
+        let mut keys = self.keys.write().expect("key store lock poisoned");
+        let newest = keys.last().map(|key| key.kid.clone());
+        keys.retain(|key| {
+            let keep =
+                Some(&key.kid) == newest.as_ref() || now.saturating_sub(key.created_at) < expiry;
+            if !keep {
+                tracing::info!("Retiring JWT signing key {}", key.kid);
+                if let Err(e) = std::fs::remove_file(self.path_for(&key.kid)) {
+                    tracing::warn!("Failed to remove retired key {}: {e}", key.kid);
+                }
+            }
+            keep
+        });
+    }
+
+    fn path_for(&self, kid: &str) -> PathBuf {
+        key_path(&self.dir, kid)
+    }
+
+    /// The `kid` and key new tokens should be signed with.
+    pub fn signing_key<T>(&self, f: impl FnOnce(&str, &EncodingKey) -> T) -> Option<T> {
+        let keys = self.keys.read().expect("key store lock poisoned");
+        keys.last().map(|key| f(&key.kid, &key.encoding))
+    }
+
+    /// The verification key for a `kid` taken from a token header, if it is still active.
+    pub fn decoding_key<T>(&self, kid: &str, f: impl FnOnce(&DecodingKey) -> T) -> Option<T> {
+        let keys = self.keys.read().expect("key store lock poisoned");
+        keys.iter()
+            .find(|key| key.kid == kid)
+            .map(|key| f(&key.decoding))
+    }
+
+    /// Public halves of every active key, served from `/.well-known/jwks.json`.
+    pub fn jwks(&self) -> JwkSet {
+        let keys = self.keys.read().expect("key store lock poisoned");
+        JwkSet {
+            keys: keys.iter().map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+fn key_path(dir: &Path, kid: &str) -> PathBuf {
+    dir.join(format!("{kid}.pem"))
+}
+
+/// Generate a key and store it in `dir`
+fn generate(dir: &Path, created_at: u64) -> Result<SigningKey, KeyError> {
+    let kid = format!("{created_at}-{}", uuid::Uuid::new_v4().simple());
+    let path = key_path(dir, &kid);
+    let mut rng = rand::thread_rng();
+    let private_key = rsa::RsaPrivateKey::new(&mut rng, 2048)?;
+    let pem = private_key
+        .to_pkcs1_pem(LineEnding::LF)
+        .map_err(|e| KeyError::Pem(path.clone(), e))?;
+    write_private(&path, pem.as_bytes()).map_err(|e| KeyError::Io(path.clone(), e))?;
+    SigningKey::from_pem(kid, created_at, &pem, &path)
+}
+
+/// Write a private key readable only by the owner.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        jsonwebtoken::{Header, Validation},
+        serde_json::{Value, json},
+        tempfile::TempDir,
+    };
+
+    const ROTATION: Duration = Duration::from_secs(60 * 60);
+    const OVERLAP: Duration = Duration::from_secs(10 * 60);
+
+    fn load(dir: &TempDir, import: Option<&Path>) -> KeyStore {
+        KeyStore::load(dir.path().to_path_buf(), ROTATION, OVERLAP, import)
+            .expect("loading the key store failed")
+    }
+
+    fn kids(store: &KeyStore) -> Vec<String> {
+        store
+            .keys
+            .read()
+            .expect("key store lock poisoned")
+            .iter()
+            .map(|key| key.kid.clone())
+            .collect()
+    }
+
+    fn created_at(store: &KeyStore) -> u64 {
+        store
+            .keys
+            .read()
+            .expect("key store lock poisoned")
+            .last()
+            .expect("the store has no keys")
+            .created_at
+    }
+
+    fn sign(store: &KeyStore) -> String {
+        store
+            .signing_key(|kid, key| {
+                let header = Header {
+                    kid: Some(kid.to_owned()),
+                    ..Header::new(Algorithm::RS256)
+                };
+                jsonwebtoken::encode(&header, &json!({ "sub": "test" }), key)
+            })
+            .expect("the store has no signing key")
+            .expect("signing failed")
+    }
+
+    fn verifies(store: &KeyStore, token: &str) -> bool {
+        let kid = jsonwebtoken::decode_header(token)
+            .expect("invalid token header")
+            .kid
+            .expect("token has no kid");
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.required_spec_claims.clear();
+        store
+            .decoding_key(&kid, |key| {
+                jsonwebtoken::decode::<Value>(token, key, &validation).is_ok()
+            })
+            .unwrap_or(false)
+    }
+
+    #[tokio::test]
+    async fn rotates_and_retires_keys_after_the_overlap() {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let store = load(&dir, None);
+        let first = kids(&store);
+        assert_eq!(first.len(), 1);
+        let start = created_at(&store);
+        let token = sign(&store);
+
+        assert!(
+            !store
+                .rotate_at(start + ROTATION.as_secs() - 1)
+                .await
+                .expect("rotation failed")
+        );
+        assert_eq!(kids(&store), first);
+
+        let rotated_at = start + ROTATION.as_secs();
+        assert!(store.rotate_at(rotated_at).await.expect("rotation failed"));
+        let keys = kids(&store);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0], first[0]);
+        // the old key no longer signs but still verifies what it signed within the overlap
+        assert_ne!(
+            store.signing_key(|kid, _| kid.to_owned()).as_ref(),
+            Some(&first[0])
+        );
+        assert!(verifies(&store, &token));
+        assert!(verifies(&store, &sign(&store)));
+        // a reloaded store picks up both keys from disk
+        assert_eq!(
+            load(&dir, None).keys.read().expect("lock poisoned").len(),
+            2
+        );
+
+        let expiry = start + (ROTATION + OVERLAP).as_secs();
+        assert!(!store.rotate_at(expiry - 1).await.expect("rotation failed"));
+        assert!(verifies(&store, &token));
+        assert!(!store.rotate_at(expiry).await.expect("rotation failed"));
+        assert_eq!(kids(&store), keys[1..]);
+        assert!(!verifies(&store, &token));
+        assert!(!key_path(dir.path(), &first[0]).exists());
+        assert!(key_path(dir.path(), &keys[1]).exists());
+    }
+
+    #[test]
+    fn imports_a_key_into_an_empty_store_only() {
+        let source = TempDir::new().expect("creating a temporary directory failed");
+        let source_store = load(&source, None);
+        let imported = key_path(source.path(), &kids(&source_store)[0]);
+
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let store = load(&dir, Some(&imported));
+        let keys = kids(&store);
+        assert_eq!(keys.len(), 1);
+        assert_ne!(keys[0], kids(&source_store)[0]);
+        assert!(verifies(&store, &sign(&store)));
+        assert_eq!(
+            std::fs::read_to_string(key_path(dir.path(), &keys[0]))
+                .expect("reading the key failed"),
+            std::fs::read_to_string(&imported).expect("reading the imported key failed")
+        );
+
+        // once the store has keys of its own the import is ignored
+        let reloaded = load(&dir, Some(&imported));
+        assert_eq!(kids(&reloaded), keys);
+    }
+}
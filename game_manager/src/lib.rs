@@ -0,0 +1,188 @@
+#[cfg(feature = "server")]
+use {
+    axum::{
+        Router,
+        body::Body,
+        extract::Request,
+        http::{HeaderValue, header},
+        routing::{any, get, post},
+    },
+    error::AppError,
+    keys::KeyStore,
+    std::{
+        sync::{Arc, LazyLock},
+        time::Duration,
+    },
+    tokio::sync::Mutex,
+    tower_http::{
+        LatencyUnit, ServiceBuilderExt,
+        timeout::TimeoutLayer,
+        trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    },
+};
+use {
+    dioxus::prelude::*,
+    serde::{Deserialize, Serialize},
+};
+
+#[cfg(feature = "server")]
+pub mod auth;
+#[cfg(feature = "server")]
+pub mod backup;
+#[cfg(feature = "server")]
+pub mod chat;
+#[cfg(feature = "server")]
+pub mod config;
+#[cfg(feature = "server")]
+pub mod controller;
+#[cfg(feature = "server")]
+pub mod error;
+#[cfg(feature = "server")]
+pub mod keys;
+#[cfg(feature = "server")]
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod rcon;
+#[cfg(feature = "server")]
+pub mod restore;
+#[cfg(feature = "server")]
+pub mod servers;
+
+const FAVICON: Asset = asset!("/assets/favicon.ico");
+const MAIN_CSS: Asset = asset!("/assets/main.css");
+// Global state - this will be set by the main function
+#[cfg(feature = "server")]
+pub static GLOBAL_STATE: std::sync::OnceLock<Arc<Mutex<AppState>>> = std::sync::OnceLock::new();
+
+/// How long creating or restoring a backup may take before the request is abandoned
+#[cfg(feature = "server")]
+const BACKUP_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+#[cfg(feature = "server")]
+pub static KEYS: std::sync::OnceLock<KeyStore> = std::sync::OnceLock::new();
+
+/// How long TShock may take to answer a request
+#[cfg(feature = "server")]
+const TSHOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client for TShock's REST API, shared so its connections are reused
+#[cfg(feature = "server")]
+pub static HTTP: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    // building only fails without a TLS backend, which the default client would lack just the same
+    reqwest::Client::builder()
+        .timeout(TSHOCK_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct AppState {
+    pub minecraft_geyser_address: String,
+    /// Prefix Floodgate puts in front of Bedrock player names, `.` unless changed in its config
+    pub floodgate_prefix: String,
+    pub minecraft_geyser_rcon_address: String,
+    pub minecraft_geyser_rcon_password: String,
+    pub minecraft_modded_address: String,
+    pub minecraft_modded_rcon_address: String,
+    pub minecraft_modded_rcon_password: String,
+    pub terraria_address: String,
+    pub tshock_base_url: String,
+    pub tshock_token: String,
+    pub issuer: String,
+    pub audience: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Space separated scopes granted to the client, e.g. `logs:read servers:control`
+    pub client_scopes: String,
+    /// Backups are stored in a directory per server below this one
+    pub backup_dir: String,
+    /// World directories to back up, backups are disabled for a server whose directory is empty
+    pub minecraft_geyser_world_dir: String,
+    pub minecraft_modded_world_dir: String,
+    pub terraria_world_dir: String,
+    /// How many hourly, daily and weekly backups to keep
+    pub backup_keep_hourly: usize,
+    pub backup_keep_daily: usize,
+    pub backup_keep_weekly: usize,
+    /// How many backups taken automatically before a restore to keep
+    pub backup_keep_pre_restore: usize,
+}
+
+#[cfg(feature = "server")]
+pub fn set_global_state(state: Arc<Mutex<AppState>>) {
+    let _ = GLOBAL_STATE.set(state);
+}
+
+/// The `game_manager` API behind the logging, timeout and compression layers. The Dioxus app and the
+/// request metrics are layered on top of it in `main`, which is why the router's state is left open.
+#[cfg(feature = "server")]
+pub fn router<S: Clone + Send + Sync + 'static>(state: Arc<Mutex<AppState>>) -> Router<S> {
+    let sensitive_headers: std::sync::Arc<[_]> = vec![header::AUTHORIZATION, header::COOKIE].into();
+    // Build our middleware stack
+    let middleware = tower::ServiceBuilder::new()
+        // Mark the `Authorization` and `Cookie` headers as sensitive so it doesn't show in logs
+        .sensitive_request_headers(sensitive_headers.clone())
+        // Add high level tracing/logging to all requests
+        .layer(
+            TraceLayer::new_for_http()
+                .on_body_chunk(|chunk: &axum::body::Bytes, latency: std::time::Duration, _: &tracing::Span| {
+                    tracing::trace!(size_bytes = chunk.len(), latency = ?latency, "sending body chunk");
+                })
+                .make_span_with(DefaultMakeSpan::new().include_headers(true))
+                .on_response(DefaultOnResponse::new().include_headers(true).latency_unit(LatencyUnit::Micros)),
+        )
+        .sensitive_response_headers(sensitive_headers)
+        // Compress responses
+        .compression()
+        // Set a `Content-Type` if there isn't one already.
+        .insert_response_header_if_not_present(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+
+    // we want a deep integration of axum, dioxus, and state management, so we need to reimplement the dioxus axum wrapper, dioxus::server::router
+    Router::new()
+        .route("/.well-known/jwks.json", get(auth::jwks))
+        .route("/metrics", get(metrics::render))
+        .route("/api/authorize", post(auth::authorize))
+        .route("/api/token/refresh", post(auth::refresh))
+        .route("/api/token/revoke", post(auth::revoke))
+        .route("/api/servers/{id}/backups", get(backup::list))
+        .route("/api/servers/{id}/broadcast", post(chat::broadcast))
+        .route("/api/servers/{id}/chat", get(chat::chat))
+        .route("/api/servers/{id}/logs", get(controller::logs))
+        .route("/api/servers/{id}/players", get(servers::players))
+        .route("/api/servers/{id}/rcon", post(rcon::rcon))
+        .route("/api/servers/{id}/restart", post(controller::restart))
+        .route("/api/servers/{id}/start", post(controller::start))
+        .route("/api/servers/{id}/status", get(servers::status))
+        .route("/api/servers/{id}/stop", post(controller::stop))
+        .route("/api/{*rest}", any(api_not_found))
+        // Set a timeout
+        .layer(TimeoutLayer::new(std::time::Duration::from_secs(10)))
+        // Archiving or extracting a world takes far longer, so these routes are added after the timeout
+        .route(
+            "/api/servers/{id}/backups",
+            post(backup::create).layer(TimeoutLayer::new(BACKUP_TIMEOUT)),
+        )
+        .route(
+            "/api/servers/{id}/backups/{backup}/restore",
+            post(restore::restore).layer(TimeoutLayer::new(BACKUP_TIMEOUT)),
+        )
+        .layer(middleware)
+        .with_state(state)
+}
+
+#[component]
+pub fn App() -> Element {
+    rsx! {
+        document::Link { rel: "icon", href: FAVICON }
+        document::Link { rel: "stylesheet", href: MAIN_CSS }
+        h1 { "game manager" }
+    }
+}
+
+#[cfg(feature = "server")]
+async fn api_not_found(request: Request<Body>) -> AppError {
+    AppError::NotFound(format!("no route for {}", request.uri().path()))
+}
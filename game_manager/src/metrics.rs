@@ -0,0 +1,85 @@
+//! Prometheus metrics, served as text on `/metrics`.
+//!
+//! Only HTTP traffic is measured here. Game server state is recorded by the Discord bot, which polls it.
+
+use {
+    axum::{
+        extract::{MatchedPath, Request},
+        http::{StatusCode, header},
+        middleware::Next,
+        response::{IntoResponse, Response},
+    },
+    metrics::{counter, describe_counter, describe_histogram, histogram},
+    metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle},
+    std::{
+        sync::OnceLock,
+        time::{Duration, Instant},
+    },
+};
+
+static METRICS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Histogram buckets for request latencies, in seconds. Backups take minutes, hence the long tail.
+const LATENCY_BUCKETS: [f64; 13] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 60.0, 600.0,
+];
+/// How often histograms are compacted
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Install the Prometheus recorder. Must be called once, inside the Tokio runtime.
+pub fn install() -> Result<(), BuildError> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix(String::from("_duration_seconds")),
+            &LATENCY_BUCKETS,
+        )?
+        .install_recorder()?;
+    describe_counter!(
+        "http_requests_total",
+        "HTTP requests by method, route and status"
+    );
+    describe_histogram!(
+        "http_request_duration_seconds",
+        metrics::Unit::Seconds,
+        "HTTP request latency by method and route"
+    );
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+    let _ = METRICS.set(handle);
+    Ok(())
+}
+
+pub async fn render() -> Response {
+    match METRICS.get() {
+        Some(handle) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            handle.render(),
+        )
+            .into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "metrics are not enabled").into_response(),
+    }
+}
+
+/// Middleware counting requests and recording their latency, labelled with the route template rather
+/// than the path so server ids and backup names don't each get their own series
+pub async fn track_http(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| String::from("other"), |path| path.as_str().to_owned());
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let status = response.status().as_u16().to_string();
+    histogram!("http_request_duration_seconds", "method" => method.clone(), "route" => route.clone())
+        .record(started.elapsed().as_secs_f64());
+    counter!("http_requests_total", "method" => method, "route" => route, "status" => status)
+        .increment(1);
+    response
+}
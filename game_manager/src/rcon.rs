@@ -0,0 +1,290 @@
+//! Minimal client for the Source RCON protocol spoken by Minecraft servers.
+//!
+//! ref: https://minecraft.wiki/w/RCON
+
+use {
+    crate::{AppState, auth::Claims, error::AppError, servers::ServerId},
+    axum::{
+        Json,
+        extract::{
+            Path, State,
+            rejection::{JsonRejection, PathRejection},
+        },
+    },
+    serde::{Deserialize, Serialize},
+    std::{sync::Arc, time::Duration},
+    tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+        net::TcpStream,
+        sync::Mutex,
+        time::timeout,
+    },
+};
+
+/// Scope required to run RCON commands
+pub const SERVERS_RCON: &str = "servers:rcon";
+
+const LOGIN: i32 = 3;
+const COMMAND: i32 = 2;
+/// `SERVERDATA_RESPONSE_VALUE`, sent empty after a command to mark the end of its output
+const RESPONSE_VALUE: i32 = 0;
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Minecraft never sends a response body larger than this in a single packet
+const MAX_PACKET: i32 = 4096 + 10;
+
+pub struct RconClient<S = TcpStream> {
+    stream: S,
+    next_id: i32,
+}
+
+impl RconClient {
+    /// Connect to `address` and authenticate with `password`
+    pub async fn connect(address: &str, password: &str) -> Result<Self, AppError> {
+        let stream = timeout(TIMEOUT, TcpStream::connect(address))
+            .await
+            .map_err(|_| AppError::UpstreamTimeout(format!("rcon connect to {address}")))?
+            .map_err(|e| AppError::UpstreamUnreachable(format!("rcon {address}: {e}")))?;
+        Self::login(stream, password).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> RconClient<S> {
+    /// Authenticate with `password` over an open connection
+    pub async fn login(stream: S, password: &str) -> Result<Self, AppError> {
+        let mut client = Self { stream, next_id: 1 };
+        let id = client.send(LOGIN, password).await?;
+        // a failed login is answered with request id -1
+        let (response_id, _) = client.receive().await?;
+        if response_id != id {
+            return Err(AppError::Rcon(String::from("authentication failed")));
+        }
+        Ok(client)
+    }
+
+    /// Run a console command and return its output.
+    ///
+    /// Output longer than [`MAX_PACKET`] is split over several packets with nothing marking the last one,
+    /// so an empty [`RESPONSE_VALUE`] packet follows the command. The server handles packets in order and
+    /// answers that one only after the whole output, so its answer ends the output.
+    pub async fn command(&mut self, command: &str) -> Result<String, AppError> {
+        let id = self.send(COMMAND, command).await?;
+        let end = self.send(RESPONSE_VALUE, "").await?;
+        let mut output = vec![];
+        loop {
+            let (response_id, body) = self.receive().await?;
+            if response_id == end {
+                // decoded once complete, as a character may be split across packets
+                return Ok(String::from_utf8_lossy(&output).into_owned());
+            }
+            if response_id != id {
+                return Err(AppError::Rcon(format!(
+                    "unexpected response id {response_id}, expected {id}"
+                )));
+            }
+            output.extend_from_slice(&body);
+        }
+    }
+
+    async fn send(&mut self, kind: i32, body: &str) -> Result<i32, AppError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let body = body.as_bytes();
+        // id + type + body + two null terminators
+        let length = i32::try_from(body.len() + 10)
+            .map_err(|_| AppError::InvalidRequest(String::from("rcon command too long")))?;
+        let mut packet = Vec::with_capacity(body.len() + 14);
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body);
+        packet.extend_from_slice(&[0, 0]);
+        timeout(TIMEOUT, self.stream.write_all(&packet))
+            .await
+            .map_err(|_| AppError::UpstreamTimeout(String::from("rcon write")))??;
+        Ok(id)
+    }
+
+    async fn receive(&mut self) -> Result<(i32, Vec<u8>), AppError> {
+        timeout(TIMEOUT, async {
+            let length = self.stream.read_i32_le().await?;
+            if !(10..=MAX_PACKET).contains(&length) {
+                return Err(AppError::Rcon(format!("invalid packet length {length}")));
+            }
+            let id = self.stream.read_i32_le().await?;
+            let _kind = self.stream.read_i32_le().await?;
+            let mut body = vec![0; usize::try_from(length - 8).unwrap_or_default()];
+            self.stream.read_exact(&mut body).await?;
+            // strip the two null terminators
+            body.truncate(body.len().saturating_sub(2));
+            Ok((id, body))
+        })
+        .await
+        .map_err(|_| AppError::UpstreamTimeout(String::from("rcon read")))?
+    }
+}
+
+impl AppState {
+    /// RCON address and password of a server, if it has RCON configured
+    pub fn rcon(&self, id: ServerId) -> Option<(&str, &str)> {
+        let (address, password) = match id {
+            ServerId::MinecraftGeyser => (
+                &self.minecraft_geyser_rcon_address,
+                &self.minecraft_geyser_rcon_password,
+            ),
+            ServerId::MinecraftModded => (
+                &self.minecraft_modded_rcon_address,
+                &self.minecraft_modded_rcon_password,
+            ),
+            ServerId::Terraria => return None,
+        };
+        (!address.is_empty()).then_some((address.as_str(), password.as_str()))
+    }
+}
+
+/// Connect to a server's RCON port and run a single command
+pub async fn run(state: &AppState, id: ServerId, command: &str) -> Result<String, AppError> {
+    let (address, password) = state
+        .rcon(id)
+        .ok_or_else(|| AppError::NotFound(format!("{id} has no rcon configured")))?;
+    tracing::info!("Running rcon command on {id}: {command}");
+    RconClient::connect(address, password)
+        .await?
+        .command(command)
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RconPayload {
+    pub command: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RconBody {
+    pub output: String,
+}
+
+pub async fn rcon(
+    claims: Claims,
+    State(state): State<Arc<Mutex<AppState>>>,
+    id: Result<Path<ServerId>, PathRejection>,
+    payload: Result<Json<RconPayload>, JsonRejection>,
+) -> Result<Json<RconBody>, AppError> {
+    claims.require_scope(SERVERS_RCON)?;
+    let (Path(id), Json(payload)) = (id?, payload?);
+    if payload.command.trim().is_empty() {
+        return Err(AppError::InvalidRequest(String::from("empty command")));
+    }
+    let state = state.lock().await.clone();
+    let output = run(&state, id, payload.command.trim()).await?;
+    Ok(Json(RconBody { output }))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        tokio::io::{DuplexStream, duplex},
+    };
+
+    /// Read a packet the client sent, checking its framing
+    async fn read_packet(server: &mut DuplexStream) -> (i32, i32, String) {
+        let length = server
+            .read_i32_le()
+            .await
+            .expect("reading the length failed");
+        let mut packet = vec![0; usize::try_from(length).expect("negative length")];
+        server
+            .read_exact(&mut packet)
+            .await
+            .expect("reading the packet failed");
+        let (header, body) = packet.split_at(8);
+        let id = i32::from_le_bytes(header[..4].try_into().expect("short header"));
+        let kind = i32::from_le_bytes(header[4..].try_into().expect("short header"));
+        let body = body
+            .strip_suffix(&[0, 0])
+            .expect("body is not followed by two null bytes");
+        (
+            id,
+            kind,
+            String::from_utf8(body.to_vec()).expect("body is not UTF-8"),
+        )
+    }
+
+    async fn write_packet(server: &mut DuplexStream, id: i32, kind: i32, body: &[u8]) {
+        let length = i32::try_from(body.len() + 10).expect("body too long");
+        let mut packet = vec![];
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body);
+        packet.extend_from_slice(&[0, 0]);
+        server
+            .write_all(&packet)
+            .await
+            .expect("writing the packet failed");
+    }
+
+    #[tokio::test]
+    async fn joins_output_split_over_packets_until_the_sentinel() {
+        let (client, mut server) = duplex(1024);
+        let server = tokio::spawn(async move {
+            let (id, kind, password) = read_packet(&mut server).await;
+            assert_eq!((kind, password.as_str()), (LOGIN, "hunter2"));
+            write_packet(&mut server, id, COMMAND, b"").await;
+
+            let (id, kind, command) = read_packet(&mut server).await;
+            assert_eq!((kind, command.as_str()), (COMMAND, "whitelist list"));
+            let (end, kind, body) = read_packet(&mut server).await;
+            assert_eq!((kind, body.as_str()), (RESPONSE_VALUE, ""));
+            assert_ne!(end, id);
+            // `§` is two bytes in UTF-8, split across the packets
+            let output = "There are 2 whitelisted players: §aAlex, Steve".as_bytes();
+            let split = output.len() - "aAlex, Steve".len() - 1;
+            write_packet(&mut server, id, RESPONSE_VALUE, &output[..split]).await;
+            write_packet(&mut server, id, RESPONSE_VALUE, &output[split..]).await;
+            write_packet(&mut server, end, RESPONSE_VALUE, b"Unknown request 0").await;
+        });
+
+        let mut client = RconClient::login(client, "hunter2")
+            .await
+            .expect("logging in failed");
+        let output = client
+            .command("whitelist list")
+            .await
+            .expect("running the command failed");
+        assert_eq!(output, "There are 2 whitelisted players: §aAlex, Steve");
+        server.await.expect("the server panicked");
+    }
+
+    #[tokio::test]
+    async fn rejects_a_wrong_password() {
+        let (client, mut server) = duplex(1024);
+        tokio::spawn(async move {
+            read_packet(&mut server).await;
+            write_packet(&mut server, -1, COMMAND, b"").await;
+        });
+        assert!(matches!(
+            RconClient::login(client, "wrong").await,
+            Err(AppError::Rcon(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_responses_to_other_requests() {
+        let (client, mut server) = duplex(1024);
+        tokio::spawn(async move {
+            let (id, ..) = read_packet(&mut server).await;
+            write_packet(&mut server, id, COMMAND, b"").await;
+            let (id, ..) = read_packet(&mut server).await;
+            read_packet(&mut server).await;
+            write_packet(&mut server, id + 10, RESPONSE_VALUE, b"stray").await;
+        });
+        let mut client = RconClient::login(client, "hunter2")
+            .await
+            .expect("logging in failed");
+        assert!(matches!(
+            client.command("list").await,
+            Err(AppError::Rcon(_))
+        ));
+    }
+}
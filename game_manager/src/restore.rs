@@ -0,0 +1,454 @@
+//! Restoring a world from a backup, guarded so a bad archive never replaces a working world.
+//!
+//! The archive is checked before the server is touched: it must decompress cleanly, hold nothing but
+//! files and directories inside the world directory, and contain the file the game loads the world from.
+//! The restore then stops the server, archives the current world as a pre-restore backup, moves it aside,
+//! extracts the backup and compares what landed on disk with the archive listing before starting the
+//! server again, if it was running. Any failure after the world was moved aside puts it back. A dry run
+//! stops after the checks and reports how the world would change.
+//!
+//! A restore runs detached from its request, so it finishes even if the client goes away. Should the
+//! process die halfway anyway, the next restore moves the world left aside back into place first, and
+//! refuses to run while a world left aside sits next to another one.
+
+use {
+    crate::{
+        AppState,
+        auth::Claims,
+        backup::{
+            BACKUPS_READ, BACKUPS_WRITE, BackupInfo, BusyGuard, archive_path, archive_world,
+            detached, find_backup, prune_backups, split_world, tar, tar_output, world_dir,
+        },
+        controller::{UnitAction, control_unit, unit_active},
+        error::AppError,
+        servers::ServerId,
+    },
+    axum::{
+        Json,
+        extract::{
+            Path, Query, State,
+            rejection::{PathRejection, QueryRejection},
+        },
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::HashMap,
+        ffi::OsStr,
+        path::{Component, Path as FsPath},
+        sync::Arc,
+        time::Instant,
+    },
+    tokio::{fs, sync::Mutex},
+};
+
+/// Files in a world by path relative to the world directory, with their sizes
+type WorldFiles = HashMap<String, u64>;
+
+/// How restoring a backup changes the files of a world
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct WorldChanges {
+    /// Files only in the backup
+    pub added: usize,
+    /// Files only in the current world
+    pub removed: usize,
+    /// Files in both whose size differs
+    pub changed: usize,
+    pub unchanged: usize,
+    pub current_bytes: u64,
+    pub restored_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub backup: BackupInfo,
+    pub dry_run: bool,
+    /// Whether the server was running, and so was or would be stopped for the restore
+    pub was_running: bool,
+    pub changes: WorldChanges,
+    /// The archive of the replaced world, none for dry runs or when there was no world
+    pub pre_restore: Option<BackupInfo>,
+    pub duration_ms: u64,
+}
+
+fn invalid(backup: &BackupInfo, problem: &str) -> AppError {
+    AppError::InvalidRequest(format!("backup {} {problem}", backup.id))
+}
+
+/// List the files in an archive, checking that it decompresses and that every entry is a file or
+/// directory inside `world`. Anything else could write outside the world directory when extracted.
+async fn archive_files(
+    backup: &BackupInfo,
+    archive: &FsPath,
+    world: &OsStr,
+) -> Result<WorldFiles, AppError> {
+    let output = tar_output(&[OsStr::new("-tvzf"), archive.as_os_str()]).await?;
+    if !output.status.success() {
+        return Err(invalid(
+            backup,
+            &format!(
+                "cannot be read: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    let mut files = WorldFiles::new();
+    // GNU tar lists entries as `-rw-r--r-- user/group 1234 2026-10-19 04:00 world/level.dat`
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(mode), Some(size), Some(_)) = (fields.first(), fields.get(2), fields.get(5))
+        else {
+            return Err(invalid(
+                backup,
+                &format!("has an unexpected entry `{line}`"),
+            ));
+        };
+        let path = fields[5..].join(" ");
+        let mut components = FsPath::new(&path).components();
+        let inside = components.next() == Some(Component::Normal(world))
+            && components.all(|c| matches!(c, Component::Normal(_)));
+        if !inside {
+            return Err(invalid(
+                backup,
+                &format!("contains `{path}` outside the world"),
+            ));
+        }
+        match mode.chars().next() {
+            Some('d') => {}
+            Some('-') => {
+                let size = size
+                    .parse()
+                    .map_err(|_| invalid(backup, &format!("has an unexpected entry `{line}`")))?;
+                let relative = FsPath::new(&path).strip_prefix(world).map_err(|_| {
+                    invalid(backup, &format!("contains `{path}` outside the world"))
+                })?;
+                files.insert(relative.to_string_lossy().into_owned(), size);
+            }
+            _ => {
+                return Err(invalid(
+                    backup,
+                    &format!("contains `{path}`, which is not a file"),
+                ));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The files in a world directory, empty if there is none
+async fn world_files(world: &FsPath) -> Result<WorldFiles, AppError> {
+    let mut files = WorldFiles::new();
+    if !fs::try_exists(world).await? {
+        return Ok(files);
+    }
+    let mut pending = vec![world.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let kind = entry.file_type().await?;
+            if kind.is_dir() {
+                pending.push(entry.path());
+            } else if kind.is_file() {
+                let path = entry.path();
+                let relative = path.strip_prefix(world).unwrap_or(&path);
+                files.insert(
+                    relative.to_string_lossy().into_owned(),
+                    entry.metadata().await?.len(),
+                );
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `files` hold a world the game can load: `level.dat` for Minecraft, a `.wld` file for Terraria
+fn has_world(id: ServerId, files: &WorldFiles) -> bool {
+    match id {
+        ServerId::MinecraftGeyser | ServerId::MinecraftModded => files.contains_key("level.dat"),
+        ServerId::Terraria => files.keys().any(|path| {
+            !path.contains('/')
+                && FsPath::new(path)
+                    .extension()
+                    .is_some_and(|extension| extension == "wld")
+        }),
+    }
+}
+
+fn compare(current: &WorldFiles, restored: &WorldFiles) -> WorldChanges {
+    let mut changes = WorldChanges {
+        current_bytes: current.values().sum(),
+        restored_bytes: restored.values().sum(),
+        ..WorldChanges::default()
+    };
+    for (path, size) in restored {
+        match current.get(path) {
+            None => changes.added += 1,
+            Some(current) if current != size => changes.changed += 1,
+            Some(_) => changes.unchanged += 1,
+        }
+    }
+    changes.removed = current
+        .keys()
+        .filter(|path| !restored.contains_key(*path))
+        .count();
+    changes
+}
+
+/// Move back a world an interrupted restore left aside. When both are there it is not clear which one
+/// the server should have, so neither is touched.
+async fn recover_world(world: &FsPath, aside: &FsPath) -> Result<(), AppError> {
+    if !fs::try_exists(aside).await? {
+        return Ok(());
+    }
+    if fs::try_exists(world).await? {
+        return Err(AppError::Conflict(format!(
+            "{} was left by an interrupted restore, move it back or remove it first",
+            aside.display()
+        )));
+    }
+    tracing::warn!(
+        "Moving {} left by an interrupted restore back to {}",
+        aside.display(),
+        world.display()
+    );
+    fs::rename(aside, world).await?;
+    Ok(())
+}
+
+/// Check a backup and, unless `dry_run`, replace the server's world with it
+pub async fn restore_backup(
+    state: &AppState,
+    id: ServerId,
+    backup: &str,
+    dry_run: bool,
+) -> Result<RestoreReport, AppError> {
+    let world = world_dir(state, id)?;
+    let backup = find_backup(state, id, backup).await?;
+    let _busy = BusyGuard::acquire(id)?;
+    let started = Instant::now();
+    let (parent, name) = split_world(&world)?;
+    let archive = archive_path(state, &backup);
+    let mut aside = name.to_owned();
+    aside.push(".pre-restore");
+    let aside = parent.join(aside);
+    if !dry_run {
+        recover_world(&world, &aside).await?;
+    }
+
+    let restored = archive_files(&backup, &archive, name).await?;
+    if !has_world(id, &restored) {
+        return Err(invalid(&backup, &format!("does not contain a {id} world")));
+    }
+    let current = world_files(&world).await?;
+    let changes = compare(&current, &restored);
+    let was_running = unit_active(id).await?;
+    let mut report = RestoreReport {
+        backup,
+        dry_run,
+        was_running,
+        changes,
+        pre_restore: None,
+        duration_ms: 0,
+    };
+    if dry_run {
+        report.duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        return Ok(report);
+    }
+
+    // a server that was stopped on purpose stays stopped
+    let resume = move || async move {
+        if was_running {
+            control_unit(id, UnitAction::Start).await
+        } else {
+            Ok(())
+        }
+    };
+    control_unit(id, UnitAction::Stop).await?;
+    if !current.is_empty() {
+        match archive_world(state, id, &world, true).await {
+            Ok(pre_restore) => report.pre_restore = Some(pre_restore),
+            Err(e) => {
+                // without a copy of the current world there is no going back, so leave it alone
+                resume().await?;
+                return Err(e);
+            }
+        }
+    }
+    if fs::try_exists(&world).await? {
+        fs::rename(&world, &aside).await?;
+    }
+    let result = async {
+        tar(&[
+            OsStr::new("-xzf"),
+            archive.as_os_str(),
+            OsStr::new("-C"),
+            parent.as_os_str(),
+        ])
+        .await?;
+        if world_files(&world).await? != restored {
+            return Err(invalid(
+                &report.backup,
+                "did not extract to the files it lists",
+            ));
+        }
+        Ok::<_, AppError>(())
+    }
+    .await;
+    if let Err(e) = result {
+        // put the previous world back rather than starting on a half extracted one
+        if fs::try_exists(&world).await? {
+            fs::remove_dir_all(&world).await?;
+        }
+        if fs::try_exists(&aside).await? {
+            fs::rename(&aside, &world).await?;
+        }
+        resume().await?;
+        return Err(e);
+    }
+    if fs::try_exists(&aside).await? {
+        fs::remove_dir_all(&aside).await?;
+    }
+    resume().await?;
+    prune_backups(state, id).await?;
+    report.duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+    tracing::info!("Restored {id} from {}", report.backup.id);
+    Ok(report)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RestoreQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+pub async fn restore(
+    claims: Claims,
+    State(state): State<Arc<Mutex<AppState>>>,
+    path: Result<Path<(ServerId, String)>, PathRejection>,
+    query: Result<Query<RestoreQuery>, QueryRejection>,
+) -> Result<Json<RestoreReport>, AppError> {
+    let (Path((id, backup)), Query(query)) = (path?, query?);
+    // a dry run changes nothing, so reading backups is enough
+    claims.require_scope(if query.dry_run {
+        BACKUPS_READ
+    } else {
+        BACKUPS_WRITE
+    })?;
+    tracing::info!(
+        "{} requested a {}restore of {id} from {backup}",
+        claims.sub,
+        if query.dry_run { "dry run " } else { "" }
+    );
+    let state = state.lock().await.clone();
+    let report =
+        detached(async move { restore_backup(&state, id, &backup, query.dry_run).await }).await?;
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{
+            sync::atomic::{AtomicBool, Ordering},
+            time::Duration,
+        },
+        tempfile::TempDir,
+    };
+
+    /// A directory holding `world` and `world.pre-restore`, neither created yet
+    fn worlds() -> (TempDir, std::path::PathBuf, std::path::PathBuf) {
+        let dir = TempDir::new().expect("creating a temporary directory failed");
+        let world = dir.path().join("world");
+        let aside = dir.path().join("world.pre-restore");
+        (dir, world, aside)
+    }
+
+    async fn write_level(world: &FsPath, contents: &str) {
+        fs::create_dir_all(world)
+            .await
+            .expect("creating the world failed");
+        fs::write(world.join("level.dat"), contents)
+            .await
+            .expect("writing the world failed");
+    }
+
+    async fn level(world: &FsPath) -> String {
+        fs::read_to_string(world.join("level.dat"))
+            .await
+            .expect("reading the world failed")
+    }
+
+    #[tokio::test]
+    async fn world_left_aside_is_moved_back() {
+        let (_dir, world, aside) = worlds();
+        write_level(&aside, "previous").await;
+        recover_world(&world, &aside)
+            .await
+            .expect("recovering failed");
+        assert_eq!(level(&world).await, "previous");
+        assert!(!aside.exists());
+    }
+
+    #[tokio::test]
+    async fn world_left_aside_next_to_another_is_left_alone() {
+        let (_dir, world, aside) = worlds();
+        write_level(&world, "half extracted").await;
+        write_level(&aside, "previous").await;
+        let result = recover_world(&world, &aside).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))), "{result:?}");
+        assert_eq!(level(&world).await, "half extracted");
+        assert_eq!(level(&aside).await, "previous");
+    }
+
+    #[tokio::test]
+    async fn nothing_to_recover() {
+        let (_dir, world, aside) = worlds();
+        recover_world(&world, &aside)
+            .await
+            .expect("recovering nothing failed");
+        write_level(&world, "current").await;
+        recover_world(&world, &aside)
+            .await
+            .expect("recovering nothing failed");
+        assert_eq!(level(&world).await, "current");
+    }
+
+    #[tokio::test]
+    async fn detached_work_outlives_its_request() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = finished.clone();
+        let request = detached(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        // the request times out and is dropped long before the work is done
+        assert!(
+            tokio::time::timeout(Duration::from_millis(1), request)
+                .await
+                .is_err()
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn busy_guard_is_held_until_detached_work_is_done() {
+        let request = detached(async {
+            let _busy = BusyGuard::acquire(ServerId::Terraria)?;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(())
+        });
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), request)
+                .await
+                .is_err()
+        );
+        assert!(matches!(
+            BusyGuard::acquire(ServerId::Terraria),
+            Err(AppError::Busy(_))
+        ));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(BusyGuard::acquire(ServerId::Terraria).is_ok());
+    }
+}
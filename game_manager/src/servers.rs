@@ -0,0 +1,307 @@
+use {
+    crate::{AppState, HTTP, auth::Claims, error::AppError},
+    axum::{
+        Json,
+        extract::{Path, State, rejection::PathRejection},
+    },
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+    std::sync::Arc,
+    tokio::sync::Mutex,
+};
+
+/// Scope required to query server status and players
+pub const SERVERS_READ: &str = "servers:read";
+
+/// The game servers hosted on this machine, identified in routes as `/api/servers/{id}`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerId {
+    MinecraftGeyser,
+    MinecraftModded,
+    Terraria,
+}
+
+impl ServerId {
+    pub const ALL: [Self; 3] = [Self::MinecraftGeyser, Self::MinecraftModded, Self::Terraria];
+}
+
+impl std::fmt::Display for ServerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MinecraftGeyser => write!(f, "minecraft_geyser"),
+            Self::MinecraftModded => write!(f, "minecraft_modded"),
+            Self::Terraria => write!(f, "terraria"),
+        }
+    }
+}
+
+/// Which Minecraft client a player joined with. Bedrock players reach the geyser server through Floodgate.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    Java,
+    Bedrock,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Player {
+    /// The name without any Floodgate prefix
+    pub name: String,
+    /// `None` for games other than Minecraft
+    pub platform: Option<Platform>,
+    /// The Minecraft profile UUID, if the server reported one
+    pub uuid: Option<String>,
+    /// The Xbox user id of a Bedrock player
+    pub xuid: Option<String>,
+}
+
+impl Player {
+    /// A player whose name is all we know, e.g. from TShock
+    fn named(name: String) -> Self {
+        Self {
+            name,
+            platform: None,
+            uuid: None,
+            xuid: None,
+        }
+    }
+
+    /// Floodgate gives Bedrock players the UUID `00000000-0000-0000-xxxx-xxxxxxxxxxxx`, where the low 64 bits
+    /// are the XUID, and by default prefixes their name so it cannot clash with a Java name.
+    ///
+    /// ref: https://geysermc.org/wiki/floodgate/faq
+    fn minecraft(name: String, uuid: String, floodgate_prefix: &str) -> Self {
+        let xuid = uuid::Uuid::parse_str(&uuid)
+            .ok()
+            .map(|uuid| uuid.as_u64_pair())
+            .and_then(|(high, low)| (high == 0 && low != 0).then(|| low.to_string()));
+        let bedrock =
+            xuid.is_some() || (!floodgate_prefix.is_empty() && name.starts_with(floodgate_prefix));
+        let name = if bedrock && !floodgate_prefix.is_empty() {
+            name.strip_prefix(floodgate_prefix)
+                .map(String::from)
+                .unwrap_or(name)
+        } else {
+            name
+        };
+        Self {
+            name,
+            platform: Some(if bedrock {
+                Platform::Bedrock
+            } else {
+                Platform::Java
+            }),
+            uuid: Some(uuid),
+            xuid,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServerStatus {
+    pub id: ServerId,
+    pub online: bool,
+    pub version: Option<String>,
+    pub players: Vec<Player>,
+    pub max_players: Option<u32>,
+}
+
+impl ServerStatus {
+    fn offline(id: ServerId) -> Self {
+        Self {
+            id,
+            online: false,
+            version: None,
+            players: vec![],
+            max_players: None,
+        }
+    }
+}
+
+/// Query a server for its current status. A server that cannot be reached is reported as offline rather
+/// than as an error.
+pub async fn query_status(state: &AppState, id: ServerId) -> Result<ServerStatus, AppError> {
+    match id {
+        ServerId::MinecraftGeyser => {
+            Ok(
+                minecraft_status(id, &state.minecraft_geyser_address, &state.floodgate_prefix)
+                    .await,
+            )
+        }
+        // only the geyser server runs Floodgate
+        ServerId::MinecraftModded => {
+            Ok(minecraft_status(id, &state.minecraft_modded_address, "").await)
+        }
+        ServerId::Terraria => terraria_status(state).await,
+    }
+}
+
+async fn minecraft_status(id: ServerId, address: &str, floodgate_prefix: &str) -> ServerStatus {
+    let Some((host, port)) = address
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+    else {
+        tracing::warn!("{id} address {address:?} is not in host:port form");
+        return ServerStatus::offline(id);
+    };
+    match mc_query::status(host, port).await {
+        Ok(status) => ServerStatus {
+            id,
+            online: true,
+            version: Some(status.version.name),
+            players: status
+                .players
+                .sample
+                .map(|sample| {
+                    sample
+                        .into_iter()
+                        .map(|player| Player::minecraft(player.name, player.id, floodgate_prefix))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            max_players: Some(status.players.max),
+        },
+        Err(e) => {
+            tracing::debug!("{id} unreachable {e}");
+            ServerStatus::offline(id)
+        }
+    }
+}
+
+/// ref: https://tshock.readme.io/reference/v2status
+async fn terraria_status(state: &AppState) -> Result<ServerStatus, AppError> {
+    let url = format!(
+        "{}/v2/server/status?players=true&token={}",
+        state.tshock_base_url, state.tshock_token
+    );
+    let response = match HTTP.get(url).send().await {
+        Ok(response) => response.error_for_status()?,
+        Err(e) if e.is_connect() || e.is_timeout() => {
+            tracing::debug!("terraria unreachable {e}");
+            return Ok(ServerStatus::offline(ServerId::Terraria));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let status = response.json::<Value>().await?;
+    let players = status
+        .get("players")
+        .and_then(Value::as_array)
+        .map(|players| {
+            players
+                .iter()
+                .filter_map(|player| player.get("nickname")?.as_str())
+                .map(|nickname| Player::named(nickname.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(ServerStatus {
+        id: ServerId::Terraria,
+        online: true,
+        version: status
+            .get("serverversion")
+            .and_then(Value::as_str)
+            .map(String::from),
+        players,
+        max_players: status
+            .get("maxplayers")
+            .and_then(Value::as_u64)
+            .and_then(|max| u32::try_from(max).ok()),
+    })
+}
+
+pub async fn status(
+    claims: Claims,
+    State(state): State<Arc<Mutex<AppState>>>,
+    id: Result<Path<ServerId>, PathRejection>,
+) -> Result<Json<ServerStatus>, AppError> {
+    claims.require_scope(SERVERS_READ)?;
+    let Path(id) = id?;
+    let state = state.lock().await.clone();
+    Ok(Json(query_status(&state, id).await?))
+}
+
+pub async fn players(
+    claims: Claims,
+    State(state): State<Arc<Mutex<AppState>>>,
+    id: Result<Path<ServerId>, PathRejection>,
+) -> Result<Json<Vec<Player>>, AppError> {
+    claims.require_scope(SERVERS_READ)?;
+    let Path(id) = id?;
+    let state = state.lock().await.clone();
+    Ok(Json(query_status(&state, id).await?.players))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JAVA_UUID: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+    const FLOODGATE_UUID: &str = "00000000-0000-0000-0009-01f5b3e0c2a5";
+    const XUID: &str = "2535429586862757";
+
+    #[test]
+    fn tells_java_and_bedrock_players_apart() {
+        for (name, uuid, prefix, expected_name, platform, xuid) in [
+            ("Notch", JAVA_UUID, ".", "Notch", Platform::Java, None),
+            (
+                ".Alex",
+                FLOODGATE_UUID,
+                ".",
+                "Alex",
+                Platform::Bedrock,
+                Some(XUID),
+            ),
+            // Floodgate can be configured without a prefix, or with another one
+            (
+                "Alex",
+                FLOODGATE_UUID,
+                "",
+                "Alex",
+                Platform::Bedrock,
+                Some(XUID),
+            ),
+            (
+                "BE_Alex",
+                FLOODGATE_UUID,
+                "BE_",
+                "Alex",
+                Platform::Bedrock,
+                Some(XUID),
+            ),
+            // a Bedrock name whose prefix was left off is kept as it is
+            (
+                "Alex",
+                FLOODGATE_UUID,
+                ".",
+                "Alex",
+                Platform::Bedrock,
+                Some(XUID),
+            ),
+            // the prefix alone marks a Bedrock player when the UUID is not Floodgate's
+            (".Alex", JAVA_UUID, ".", "Alex", Platform::Bedrock, None),
+            // servers hiding the player list send anonymous entries with the nil UUID
+            (
+                "Anonymous Player",
+                "00000000-0000-0000-0000-000000000000",
+                ".",
+                "Anonymous Player",
+                Platform::Java,
+                None,
+            ),
+            ("Steve", "not a uuid", ".", "Steve", Platform::Java, None),
+        ] {
+            let player = Player::minecraft(name.to_owned(), uuid.to_owned(), prefix);
+            assert_eq!(
+                player,
+                Player {
+                    name: expected_name.to_owned(),
+                    platform: Some(platform),
+                    uuid: Some(uuid.to_owned()),
+                    xuid: xuid.map(String::from),
+                },
+                "{name} {uuid} with prefix {prefix:?}"
+            );
+        }
+    }
+}